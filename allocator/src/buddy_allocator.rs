@@ -3,9 +3,10 @@ use alloc::alloc::Layout;
 use core::alloc::GlobalAlloc;
 use core::cmp::{max, min};
 use core::mem::size_of;
+use core::ptr::null_mut;
 use spin::{Spin, SpinGuard};
 
-const BUDDY_ALLOCATOR_LEVEL: usize = 32;
+pub const BUDDY_ALLOCATOR_LEVEL: usize = 32;
 
 pub struct BuddyAllocator {
     pub allocator: Spin<BuddyAllocatorInner>,
@@ -90,13 +91,19 @@ impl BuddyAllocatorInner {
         self.total += end - start;
 
         while start < end {
-            let level = (end - start).trailing_zeros() as usize;
+            // the block should be aligned to its size, and it should not exceed the end
+            let level = min(
+                min(start.trailing_zeros() as usize, BUDDY_ALLOCATOR_LEVEL - 1),
+                (usize::BITS - 1 - (end - start).leading_zeros()) as usize,
+            );
             self.free_lists[level].push_front(start as *mut usize);
             start += 1 << level;
         }
     }
 
     /// Allocate a range of memory according to the given layout.
+    ///
+    /// A null pointer is returned when there is no free space large enough.
     pub fn alloc(&mut self, layout: Layout) -> *mut u8 {
         let size = self.calculate_size(&layout);
         let level = size.trailing_zeros() as usize;
@@ -114,10 +121,7 @@ impl BuddyAllocatorInner {
                 return result as *mut u8;
             }
         }
-        panic!(
-            "[buddy_allocator] Unable to allocate more space for size {}.",
-            size
-        );
+        null_mut()
     }
 
     /// Deallocate memory according to the address provided.
@@ -129,6 +133,15 @@ impl BuddyAllocatorInner {
         self.merge(level, ptr);
     }
 
+    /// Count the free blocks in each level, where a block in level `i` has `1 << i` bytes.
+    pub fn free_blocks(&self) -> [usize; BUDDY_ALLOCATOR_LEVEL] {
+        let mut counts = [0; BUDDY_ALLOCATOR_LEVEL];
+        for (count, list) in counts.iter_mut().zip(self.free_lists.iter()) {
+            *count = list.iter().count();
+        }
+        counts
+    }
+
     /// Split from level start to level end.
     fn split(&mut self, start: usize, end: usize) {
         for i in (start..end).rev() {
@@ -0,0 +1,13 @@
+//! The keys of the auxiliary vector that `exec()` passes to the program above its environment, which follow Linux.
+
+pub const AT_NULL: usize = 0;
+/// The address of the program headers in memory.
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+/// The address of 16 random bytes.
+pub const AT_RANDOM: usize = 25;
+/// The number of harts, which is specific to fosix.
+pub const AT_HARTS: usize = 0x1000;
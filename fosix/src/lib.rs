@@ -1,5 +1,9 @@
 #![no_std]
 
+pub mod auxv;
+pub mod errno;
 pub mod fs;
+pub mod mm;
 pub mod signal;
 pub mod syscall;
+pub mod time;
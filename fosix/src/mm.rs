@@ -0,0 +1,26 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// The protection of a mapping, which follows the `PROT_*` of Linux.
+    pub struct MapProt: u32 {
+        const NONE = 0;
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+
+    /// The flags of a mapping, which follows the `MAP_*` of Linux.
+    pub struct MapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
+
+    /// The flags of `msync()`, which follows the `MS_*` of Linux.
+    pub struct MsyncFlags: u32 {
+        const ASYNC = 1 << 0;
+        const INVALIDATE = 1 << 1;
+        const SYNC = 1 << 2;
+    }
+}
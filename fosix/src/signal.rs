@@ -70,16 +70,54 @@ bitflags! {
     }
 }
 
+impl SignalFlags {
+    /// Remove `SIGKILL` and `SIGSTOP` from the mask, which could never be masked.
+    pub fn maskable(self) -> Self {
+        self - (Self::SIGKILL | Self::SIGSTOP)
+    }
+}
+
+bitflags! {
+    /// The flags of `SignalAction`, which follow the `SA_*` of Linux.
+    pub struct SignalActionFlags: u32 {
+        /// The handler takes the `SigInfo` and the signal frame as the second and third arguments.
+        const SIGINFO = 0x4;
+        /// The handler runs on the alternate signal stack if there is one.
+        const ONSTACK = 0x0800_0000;
+        /// The syscall interrupted by the signal is restarted instead of failing with `EINTR`.
+        const RESTART = 0x1000_0000;
+        /// The signal is not masked while its handler is running.
+        const NODEFER = 0x4000_0000;
+    }
+}
+
+/// The handler that takes the default action of the signal.
+pub const SIG_DFL: usize = 0;
+/// The handler that ignores the signal.
+pub const SIG_IGN: usize = 1;
+
 #[repr(C, align(16))]
 #[derive(Clone, Copy)]
 pub struct SignalAction {
     handler: usize,
     mask: SignalFlags,
+    flags: SignalActionFlags,
+    /// The address that the handler returns to, which should call `sigreturn()`.
+    restorer: usize,
 }
 
 impl SignalAction {
     pub fn new(handler: usize, mask: SignalFlags) -> Self {
-        Self { handler, mask }
+        Self::with_flags(handler, mask, SignalActionFlags::empty())
+    }
+
+    pub fn with_flags(handler: usize, mask: SignalFlags, flags: SignalActionFlags) -> Self {
+        Self {
+            handler,
+            mask,
+            flags,
+            restorer: 0,
+        }
     }
 
     pub fn handler(&self) -> usize {
@@ -89,13 +127,101 @@ impl SignalAction {
     pub fn mask(&self) -> SignalFlags {
         self.mask
     }
+
+    pub fn set_mask(&mut self, mask: SignalFlags) {
+        self.mask = mask;
+    }
+
+    pub fn flags(&self) -> SignalActionFlags {
+        self.flags
+    }
+
+    pub fn restorer(&self) -> usize {
+        self.restorer
+    }
+
+    pub fn set_restorer(&mut self, restorer: usize) {
+        self.restorer = restorer;
+    }
 }
 
 impl Default for SignalAction {
     fn default() -> Self {
+        Self::new(0, SignalFlags::empty())
+    }
+}
+
+/// The codes of `SigInfo`, which follow the `SI_*`, `ILL_*`, `SEGV_*`, `BUS_*` and `CLD_*` of Linux.
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+pub const SI_TKILL: i32 = -6;
+pub const ILL_ILLOPC: i32 = 1;
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+pub const BUS_ADRALN: i32 = 1;
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_KILLED: i32 = 2;
+pub const CLD_STOPPED: i32 = 5;
+
+/// The information about a signal, which is passed to the handler with `SignalActionFlags::SIGINFO`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SigInfo {
+    pub signo: u32,
+    pub code: i32,
+    /// The process that sends the signal, which is 0 if it's sent by the kernel.
+    pub pid: usize,
+    /// The faulting address of `SIGSEGV` or `SIGBUS`.
+    pub addr: usize,
+}
+
+impl SigInfo {
+    pub const fn new(signo: u32, code: i32, pid: usize, addr: usize) -> Self {
+        Self {
+            signo,
+            code,
+            pid,
+            addr,
+        }
+    }
+
+    /// The information of a signal sent by the kernel itself.
+    pub const fn kernel(signo: u32) -> Self {
+        Self::new(signo, SI_KERNEL, 0, 0)
+    }
+}
+
+/// The flags of `SignalStack`, which follow the `SS_*` of Linux.
+pub const SS_ONSTACK: u32 = 1;
+pub const SS_DISABLE: u32 = 2;
+
+/// The minimum size of the alternate signal stack.
+pub const MIN_SIG_STACK_SIZE: usize = 2048;
+
+/// The alternate signal stack set by `sigaltstack()`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: u32,
+    pub size: usize,
+}
+
+impl SignalStack {
+    pub const fn disabled() -> Self {
         Self {
-            handler: 0,
-            mask: SignalFlags::empty(),
+            sp: 0,
+            flags: SS_DISABLE,
+            size: 0,
         }
     }
+
+    pub fn is_disabled(&self) -> bool {
+        self.flags & SS_DISABLE != 0
+    }
+
+    /// Whether `sp` is on the stack.
+    pub fn contains(&self, sp: usize) -> bool {
+        !self.is_disabled() && sp > self.sp && sp - self.sp <= self.size
+    }
 }
@@ -1,8 +1,11 @@
 use bitflags::bitflags;
 
+use crate::time::TimeSpec;
+
 pub const SYSCALL_DUP: usize = 24;
 pub const SYSCALL_MKDIR: usize = 34;
 pub const SYSCALL_FSTAT: usize = 43;
+pub const SYSCALL_FTRUNCATE: usize = 46;
 pub const SYSCALL_CHDIR: usize = 49;
 pub const SYSCALL_OPEN: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
@@ -11,21 +14,40 @@ pub const SYSCALL_GETDENTS: usize = 61;
 pub const SYSCALL_LSEEK: usize = 62;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_PERSONALITY: usize = 92;
 pub const SYSCALL_EXIT: usize = 93;
-pub const SYSCALL_SLEEP: usize = 101;
+pub const SYSCALL_EXIT_GROUP: usize = 94;
+pub const SYSCALL_FUTEX: usize = 98;
+pub const SYSCALL_NANOSLEEP: usize = 101;
+pub const SYSCALL_GETITIMER: usize = 102;
+pub const SYSCALL_SETITIMER: usize = 103;
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
 pub const SYSCALL_YIELD: usize = 124;
 pub const SYSCALL_KILL: usize = 129;
+pub const SYSCALL_TGKILL: usize = 131;
+pub const SYSCALL_SIGALTSTACK: usize = 132;
+pub const SYSCALL_SIGSUSPEND: usize = 133;
 pub const SYSCALL_SIGACTION: usize = 134;
 pub const SYSCALL_SIGPROCMASK: usize = 135;
+pub const SYSCALL_SIGPENDING: usize = 136;
 pub const SYSCALL_SIGRETURN: usize = 139;
+pub const SYSCALL_SETPGID: usize = 154;
+pub const SYSCALL_GETPGID: usize = 155;
+pub const SYSCALL_GETSID: usize = 156;
+pub const SYSCALL_SETSID: usize = 157;
 pub const SYSCALL_TIME: usize = 169;
 pub const SYSCALL_GETPID: usize = 172;
-pub const SYSCALL_FORK: usize = 220;
+pub const SYSCALL_BRK: usize = 214;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_CLONE: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MPROTECT: usize = 226;
+pub const SYSCALL_MSYNC: usize = 227;
 pub const SYSCALL_WAITPID: usize = 260;
-pub const SYSCALL_THREAD_CREATE: usize = 1000;
 pub const SYSCALL_GETTID: usize = 1001;
 pub const SYSCALL_WAITTID: usize = 1002;
+pub const SYSCALL_THREAD_DETACH: usize = 1003;
 pub const SYSCALL_MUTEX_CREATE: usize = 1010;
 pub const SYSCALL_MUTEX_LOCK: usize = 1011;
 pub const SYSCALL_MUTEX_UNLOCK: usize = 1012;
@@ -36,11 +58,93 @@ pub const SYSCALL_CONDVAR_CREATE: usize = 1030;
 pub const SYSCALL_CONDVAR_WAIT: usize = 1031;
 pub const SYSCALL_CONDVAR_NOTIFY_ONE: usize = 1032;
 pub const SYSCALL_CONDVAR_NOTIFY_ALL: usize = 1033;
+pub const SYSCALL_SBRK: usize = 1040;
+pub const SYSCALL_SHM_OPEN: usize = 1050;
+pub const SYSCALL_SHM_UNLINK: usize = 1051;
+pub const SYSCALL_TCGETPGRP: usize = 1060;
+pub const SYSCALL_TCSETPGRP: usize = 1061;
 pub const SYSCALL_SHUTDOWN: usize = 1100;
 pub const SYSCALL_PROCDUMP: usize = 1101;
 
 bitflags! {
     pub struct WaitFlags: u8 {
         const NOHANG = 1 << 0;
+        const UNTRACED = 1 << 1;
+    }
+}
+
+bitflags! {
+    /// The resources shared by the caller of `clone()` with the child, which are copied otherwise.
+    pub struct CloneFlags: u32 {
+        /// Share the address space, which is only supported for threads.
+        const VM = 0x100;
+        /// Share the working directory.
+        const FS = 0x200;
+        const FILES = 0x400;
+        const SIGHAND = 0x800;
+        /// Create a thread in the caller's process instead of a new process.
+        const THREAD = 0x10000;
+        /// Share the mutexes, semaphores and condition variables.
+        const SYSVSEM = 0x40000;
+        /// Set the thread pointer of the child to `tls`.
+        const SETTLS = 0x80000;
     }
 }
+
+/// The status of a child reported by `waitpid()`, which is encoded into an `i32` like the `wstatus` of Linux.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitStatus {
+    /// The child has exited with the code, which keeps only the lowest 8 bits.
+    Exited(i32),
+    /// The child has been terminated by the signal, and whether its core has been dumped.
+    Signaled(u32, bool),
+    /// The child has been stopped by the signal.
+    Stopped(u32),
+}
+
+impl WaitStatus {
+    pub const fn encode(self) -> i32 {
+        match self {
+            Self::Exited(code) => (code & 0xff) << 8,
+            Self::Signaled(sig, core) => ((sig & 0x7f) | (core as u32) << 7) as i32,
+            Self::Stopped(sig) => ((sig & 0xff) << 8 | 0x7f) as i32,
+        }
+    }
+
+    /// Decode the status, where the exit code is sign-extended so that `exit(-1)` is still reported as -1.
+    pub const fn decode(status: i32) -> Self {
+        match status & 0x7f {
+            0 => Self::Exited((status >> 8) as i8 as i32),
+            0x7f => Self::Stopped((status >> 8 & 0xff) as u32),
+            sig => Self::Signaled(sig as u32, status & 0x80 != 0),
+        }
+    }
+}
+
+/// The resource usage of a child reported by `waitpid()`, including the children it has waited for.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RUsage {
+    /// The time spent in user mode.
+    pub utime: TimeSpec,
+    /// The time spent in kernel mode.
+    pub stime: TimeSpec,
+}
+
+impl RUsage {
+    pub const fn zero() -> Self {
+        Self {
+            utime: TimeSpec::zero(),
+            stime: TimeSpec::zero(),
+        }
+    }
+}
+
+/// The flag of `personality()` that disables the randomization of the address space, which follows Linux.
+pub const ADDR_NO_RANDOMIZE: usize = 0x0040000;
+/// Passed to `personality()` to get the personality without changing it.
+pub const PER_QUERY: usize = 0xffff_ffff;
+
+/// The operations of `futex()`, which follow the `FUTEX_*` of Linux.
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
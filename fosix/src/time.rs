@@ -0,0 +1,73 @@
+pub const NSEC_PER_SEC: usize = 1_000_000_000;
+
+/// The clocks of `clock_gettime()`, which follow the `CLOCK_*` of Linux.
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// The interval timers of `setitimer()`, which follow the `ITIMER_*` of Linux.
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+/// The time in seconds and nanoseconds, which follows the `struct timespec` of Linux.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub const fn new(sec: usize, nsec: usize) -> Self {
+        Self { sec, nsec }
+    }
+
+    pub const fn zero() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub const fn from_ns(ns: usize) -> Self {
+        Self::new(ns / NSEC_PER_SEC, ns % NSEC_PER_SEC)
+    }
+
+    pub const fn from_ms(ms: usize) -> Self {
+        Self::from_ns(ms * 1_000_000)
+    }
+
+    /// The total nanoseconds, which saturates instead of overflowing.
+    pub const fn as_ns(&self) -> usize {
+        self.sec
+            .saturating_mul(NSEC_PER_SEC)
+            .saturating_add(self.nsec)
+    }
+
+    pub const fn as_ms(&self) -> usize {
+        self.as_ns() / 1_000_000
+    }
+
+    /// Whether the nanoseconds are within a second, which is required by the kernel.
+    pub const fn is_valid(&self) -> bool {
+        self.nsec < NSEC_PER_SEC
+    }
+}
+
+/// The setting of an interval timer, which is like the `struct itimerval` of Linux but in `TimeSpec`.
+///
+/// The timer fires after `value` and then every `interval`, where a zero `value` disarms the timer
+/// and a zero `interval` makes it fire only once.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ITimerVal {
+    pub interval: TimeSpec,
+    pub value: TimeSpec,
+}
+
+impl ITimerVal {
+    pub const fn new(interval: TimeSpec, value: TimeSpec) -> Self {
+        Self { interval, value }
+    }
+
+    pub const fn zero() -> Self {
+        Self::new(TimeSpec::zero(), TimeSpec::zero())
+    }
+}
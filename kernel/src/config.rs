@@ -7,6 +7,10 @@ pub const UART_MAP_SIZE: usize = 0x6;
 // pub const KERNEL_HEAP_GRANULARITY: usize = size_of::<usize>();
 pub const KERNEL_HEAP_GRANULARITY: usize = PAGE_SIZE;
 pub const KERNEL_HEAP_SIZE: usize = 0x800_000;
+/// The kernel heap grows by blocks of at least so many pages from the frame allocator once `KERNEL_HEAP_SIZE` runs out.
+pub const KERNEL_HEAP_GROW_PAGES: usize = 0x100;
+/// So many free frames are kept for the kernel heap, which the pages of user and page tables never take.
+pub const KERNEL_HEAP_RESERVED_PAGES: usize = 2 * KERNEL_HEAP_GROW_PAGES;
 
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
@@ -25,8 +29,19 @@ pub const KERNEL_PAGE_NUM: usize = KERNEL_SIZE / PAGE_SIZE;
 pub const MEM_SEC_NUM: usize = 2;
 
 pub const TRAMPOLINE_ADDR: usize = usize::MAX - PAGE_SIZE + 1; // `usize::MAX` is included.
+pub const USER_SPACE_END: usize = 1 << (VA_WIDTH - 1); // the lower half of SV39 belongs to user
+pub const MAX_USER_STR_LEN: usize = 0x1000;
 
 pub const USER_STACK_SIZE: usize = 0x10000;
+pub const USER_HEAP_BASE: usize = USER_SPACE_END / 2;
+pub const USER_HEAP_LIMIT: usize = 0x1000_0000;
+pub const USER_MMAP_BASE: usize = USER_HEAP_BASE + USER_HEAP_LIMIT;
+/// Where the position-independent executables are loaded, before being shifted by ASLR.
+pub const USER_PIE_BASE: usize = 0x4000_0000;
+/// The programs should end below it, so that the user stacks above them never reach the heap.
+pub const USER_IMAGE_END: usize = USER_HEAP_BASE / 2;
+/// The program, the user stacks and the heap are shifted by fewer pages than it when they are randomized.
+pub const USER_ASLR_PAGES: usize = 0x10000;
 pub const KERNEL_STACK_SIZE: usize = 0x10000;
 
 pub const SCHED_PERIOD: usize = 1_000_000;
@@ -50,6 +65,7 @@ pub const VIRT_PLIC_SIZE: usize = 0x210_000;
 pub const VIRT_UART: usize = 0x10_000_000;
 pub const VIRT_IO_HEADER: usize = 0x1000_1000;
 pub const VIRT_TEST: usize = 0x100000;
+pub const VIRT_RTC: usize = 0x101000; // within the mapping of VIRT_TEST
 pub const VIRT_TEST_SIZE: usize = 0x2000;
 
 pub const CPUS: usize = 4;
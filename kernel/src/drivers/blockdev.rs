@@ -1,13 +1,12 @@
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use alloc::{collections::BTreeMap, vec::Vec};
-use lazy_static::lazy_static;
+use alloc::vec::Vec;
 use simplefs::disk::DiskManager;
 use virtio_drivers::{BlkResp, Hal, RespStatus, VirtIOBlk, VirtIOHeader};
 
 use crate::{
-    config::VIRT_IO_HEADER,
-    mem::normal::page::NormalPageHandle,
+    config::{PAGE_SIZE, VIRT_IO_HEADER},
+    mem::normal::FRAME_ALLOCATOR,
     mm::page_table::KERNEL_PAGE_TABLE,
     sync::{condvar::Condvar, mcs::Mcs},
 };
@@ -20,11 +19,6 @@ pub struct BlkDev {
 
 pub struct VirIoHal;
 
-lazy_static! {
-    pub static ref VIRT_IO_FRAMES: Mcs<BTreeMap<usize, Vec<NormalPageHandle>>> =
-        Mcs::new(BTreeMap::new());
-}
-
 impl DiskManager for BlkDev {
     /// Read the block from the block device.
     ///
@@ -89,15 +83,20 @@ impl BlkDev {
 }
 
 impl Hal for VirIoHal {
+    /// Allocate `pages` contiguous frames filled with zero for DMA, or return 0 if the memory runs out.
     fn dma_alloc(pages: usize) -> virtio_drivers::PhysAddr {
-        let frames: Vec<NormalPageHandle> = (0..pages).map(|_| NormalPageHandle::new()).collect();
-        let ptr = frames[0].ppn.into();
-        VIRT_IO_FRAMES.lock().insert(ptr, frames);
+        let order = dma_order(pages);
+        let ppn = match FRAME_ALLOCATOR.alloc_pages(order) {
+            Some(ppn) => ppn,
+            None => return 0,
+        };
+        let ptr = usize::from(ppn);
+        unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, PAGE_SIZE << order).fill(0) };
         ptr
     }
 
     fn dma_dealloc(paddr: virtio_drivers::PhysAddr, pages: usize) -> i32 {
-        VIRT_IO_FRAMES.lock().remove(&paddr);
+        FRAME_ALLOCATOR.dealloc_pages(paddr.into(), dma_order(pages));
         0
     }
 
@@ -109,3 +108,8 @@ impl Hal for VirIoHal {
         KERNEL_PAGE_TABLE.translate_va(vaddr.into()).unwrap().into()
     }
 }
+
+/// The order of the block that holds `pages` frames, since the frames are allocated in blocks of power of two.
+fn dma_order(pages: usize) -> usize {
+    pages.next_power_of_two().trailing_zeros() as usize
+}
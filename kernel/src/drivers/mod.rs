@@ -1,4 +1,5 @@
 pub mod blockdev;
 pub mod exit;
 pub mod plic;
+pub mod rtc;
 pub mod uart;
@@ -0,0 +1,26 @@
+use crate::config::VIRT_RTC;
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+/// The goldfish real-time clock of QEMU, which counts the nanoseconds since the Unix epoch.
+pub struct GoldfishRtc {
+    addr: usize,
+}
+
+pub const RTC: GoldfishRtc = GoldfishRtc::new(VIRT_RTC);
+
+impl GoldfishRtc {
+    pub const fn new(addr: usize) -> Self {
+        Self { addr }
+    }
+
+    /// Read the current time in nanoseconds, where reading the lower half latches the higher half.
+    pub fn read_ns(&self) -> usize {
+        unsafe {
+            let low = ((self.addr + TIME_LOW) as *const u32).read_volatile() as usize;
+            let high = ((self.addr + TIME_HIGH) as *const u32).read_volatile() as usize;
+            high << 32 | low
+        }
+    }
+}
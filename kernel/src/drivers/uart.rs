@@ -2,13 +2,17 @@ use core::sync::atomic::{AtomicU8, Ordering};
 
 use alloc::collections::VecDeque;
 use bitflags::bitflags;
+use fosix::signal::SignalFlags;
 use lazy_static::lazy_static;
 use spin::Spin;
 use volatile::{ReadOnly, Volatile};
 
 use crate::{
     config::VIRT_UART,
+    io::stdin::Stdin,
+    proc::manager::PROC_MANAGER,
     sync::{condvar::Condvar, mutex::Mutex},
+    task::processor::Processor,
 };
 
 macro_rules! wait_for {
@@ -21,6 +25,8 @@ macro_rules! wait_for {
 
 const BS: u8 = 0x8;
 const DEL: u8 = 0x7F;
+const CTRL_C: u8 = 0x3;
+const CTRL_Z: u8 = 0x1A;
 
 /// Read port when DLAB = 0.
 pub struct ReadPort {
@@ -216,12 +222,16 @@ impl Uart {
         self.inner.lock().raw.init();
     }
 
-    pub fn read(&self) -> u8 {
+    /// Read a byte, waiting until one arrives, which fails if the task is interrupted by a signal in the meantime.
+    pub fn read(&self) -> Option<u8> {
         let mut inner = self.inner.lock();
         while inner.read_buffer.is_empty() {
-            inner = self.cond.wait_mutex(inner);
+            if Processor::curr_task().lock().has_pending_sigs() {
+                return None;
+            }
+            inner = self.cond.wait_mutex_interruptible(inner);
         }
-        inner.read_buffer.pop_front().unwrap()
+        inner.read_buffer.pop_front()
     }
 
     pub fn write(&self, data: u8) {
@@ -229,13 +239,37 @@ impl Uart {
         inner.raw.send(data);
     }
 
+    /// Receive the input, where Ctrl-C and Ctrl-Z are not buffered but sent to the foreground group as signals.
+    ///
+    /// The interrupt is only taken from user mode, where the hart holds no lock,
+    /// and the lock of the UART is released before the signals are sent,
+    /// so locking the processes and their threads here follows the same order as the syscalls do.
     pub fn handle_irq(&self) {
         let mut inner = self.inner.lock();
+        let mut sigs = SignalFlags::empty();
         while let Some(data) = inner.raw.recv() {
-            inner.read_buffer.push_back(data);
+            match data {
+                CTRL_C => sigs |= SignalFlags::SIGINT,
+                CTRL_Z => sigs |= SignalFlags::SIGTSTP,
+                _ => inner.read_buffer.push_back(data),
+            }
         }
         if !inner.read_buffer.is_empty() {
             self.cond.notify_one(); // there would be at most one that is waiting
         }
+        drop(inner);
+
+        let foreground = Stdin::foreground();
+        if foreground != 0 {
+            for sig in [SignalFlags::SIGINT, SignalFlags::SIGTSTP] {
+                if sigs.contains(sig) {
+                    PROC_MANAGER.kill_group(foreground, sig);
+                }
+            }
+        }
+        if !sigs.is_empty() {
+            // the readers check their signals once woken up, and those not signalled wait again
+            self.cond.notify_all();
+        }
     }
 }
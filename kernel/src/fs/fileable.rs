@@ -4,7 +4,7 @@ use simplefs::{dir::Dir, file::File};
 use crate::{
     drivers::blockdev::BlkDev,
     io::{stdin::Stdin, stdout::Stdout},
-    ipc::pipe::Pipe,
+    ipc::{pipe::Pipe, shm::Shm},
 };
 
 use super::segment::Segment;
@@ -16,6 +16,7 @@ pub enum Fileable {
     File(File<BlkDev>),
     Dir(Dir<BlkDev>),
     Pipe(Pipe),
+    Shm(Shm),
 }
 
 impl Fileable {
@@ -64,6 +65,7 @@ impl Fileable {
         match self {
             Fileable::File(file) => file.lock().stat(),
             Fileable::Dir(dir) => dir.lock().stat(),
+            Fileable::Shm(shm) => FileStat::new(shm.size()),
             _ => FileStat::empty(),
         }
     }
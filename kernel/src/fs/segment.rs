@@ -1,12 +1,22 @@
 use alloc::vec::Vec;
 
+use crate::mem::normal::page::NormalPageHandle;
+
+/// A piece of user memory split into slices page by page.
+///
+/// The frames behind the slices are shared by the segment, so that they are never released while it's in use,
+/// even if the process is killed on another hart.
 pub struct Segment {
     inner: Vec<&'static mut [u8]>,
+    _frames: Vec<NormalPageHandle>,
 }
 
 impl Segment {
-    pub fn new(inner: Vec<&'static mut [u8]>) -> Self {
-        Self { inner }
+    pub fn new(inner: Vec<&'static mut [u8]>, frames: Vec<NormalPageHandle>) -> Self {
+        Self {
+            inner,
+            _frames: frames,
+        }
     }
 
     pub fn iter(&self) -> core::slice::Iter<&'static mut [u8]> {
@@ -1,17 +1,46 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use crate::drivers::uart::UART;
 
+/// The foreground process group of the console, where 0 means that every process is in the foreground.
+static FOREGROUND_PGID: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Clone, Copy)]
 pub struct Stdin;
 
 impl Stdin {
-    pub fn getchar(&self) -> u8 {
+    /// Read a byte from the console, which fails if the task is interrupted by a signal before it arrives.
+    pub fn getchar(&self) -> Option<u8> {
         UART.read()
     }
 }
 
 impl Stdin {
+    /// Fill the buffer from the console, returning how many bytes are read before a signal interrupts it.
     pub fn read(&self, buf: &mut [u8]) -> usize {
-        buf.iter_mut().for_each(|b| *b = self.getchar());
+        for (i, byte) in buf.iter_mut().enumerate() {
+            match self.getchar() {
+                Some(data) => *byte = data,
+                None => return i,
+            }
+        }
         buf.len()
     }
 }
+
+impl Stdin {
+    /// The group that receives the signals from the keyboard and is allowed to read the console.
+    pub fn foreground() -> usize {
+        FOREGROUND_PGID.load(Ordering::Acquire)
+    }
+
+    pub fn set_foreground(pgid: usize) {
+        FOREGROUND_PGID.store(pgid, Ordering::Release);
+    }
+
+    /// Whether the process in the group `pgid` could read the console.
+    pub fn is_foreground(pgid: usize) -> bool {
+        let foreground = Stdin::foreground();
+        foreground == 0 || foreground == pgid
+    }
+}
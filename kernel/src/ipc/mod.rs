@@ -1 +1,2 @@
 pub mod pipe;
+pub mod shm;
@@ -0,0 +1,100 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Spin;
+
+use crate::{config::PAGE_SIZE, mem::normal::page::NormalPageHandle, sync::mcs::Mcs};
+
+/// A shared memory object, whose frames are referenced by all the areas that map it.
+///
+/// The frames live as long as any descriptor or mapping refers to them, even after the object is unlinked.
+#[derive(Clone)]
+pub struct Shm {
+    inner: Arc<Spin<ShmInner>>,
+}
+
+pub struct ShmInner {
+    size: usize,
+    frames: Vec<NormalPageHandle>,
+}
+
+impl Shm {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Spin::new(ShmInner {
+                size: 0,
+                frames: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+
+    /// Resize the object to `size` bytes, where the new pages are filled with zero.
+    ///
+    /// The pages beyond `size` are still kept by the areas that have mapped them.
+    /// Return false if the memory runs out, in which case the object is not changed.
+    pub fn resize(&self, size: usize) -> bool {
+        let mut inner = self.inner.lock();
+        let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+        if pages > inner.frames.len() {
+            let frames: Option<Vec<NormalPageHandle>> = (inner.frames.len()..pages)
+                .map(|_| NormalPageHandle::new())
+                .collect();
+            match frames {
+                Some(frames) => inner.frames.extend(frames),
+                None => return false,
+            }
+        } else {
+            inner.frames.truncate(pages);
+        }
+        inner.size = size;
+        true
+    }
+
+    /// Get the frames of `pages` pages starting from the page `start`, or `None` if they are beyond the object.
+    pub fn frames(&self, start: usize, pages: usize) -> Option<Vec<NormalPageHandle>> {
+        let inner = self.inner.lock();
+        inner
+            .frames
+            .get(start..start.checked_add(pages)?)
+            .map(|frames| frames.to_vec())
+    }
+}
+
+/// The manager of named shared memory objects.
+pub struct ShmManager {
+    objects: Mcs<BTreeMap<String, Shm>>,
+}
+
+impl ShmManager {
+    pub fn new() -> Self {
+        Self {
+            objects: Mcs::new(BTreeMap::new()),
+        }
+    }
+
+    /// Open the object called `name`, which is created with zero size if it doesn't exist and `create` is set.
+    pub fn open(&self, name: &str, create: bool) -> Option<Shm> {
+        let mut objects = self.objects.lock();
+        match objects.get(name) {
+            Some(shm) => Some(shm.clone()),
+            None if create => {
+                let shm = Shm::new();
+                objects.insert(String::from(name), shm.clone());
+                Some(shm)
+            }
+            None => None,
+        }
+    }
+
+    /// Remove the name of an object, returning whether it exists.
+    pub fn unlink(&self, name: &str) -> bool {
+        self.objects.lock().remove(name).is_some()
+    }
+}
+
+lazy_static! {
+    pub static ref SHM_MANAGER: ShmManager = ShmManager::new();
+}
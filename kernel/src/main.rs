@@ -28,6 +28,7 @@ mod ipc;
 mod mem;
 mod mm;
 mod proc;
+mod random;
 mod sync;
 mod syscall;
 mod task;
@@ -45,13 +46,17 @@ use drivers::{
 };
 use mm::page_table::activate_page_table;
 use proc::manager::PROC_MANAGER;
+use random::init_random;
 use riscv::register::*;
 use task::processor::{Processor, PROCESSORS};
-use time::init_timer;
+use time::{init_realtime, init_timer};
 
 use crate::{
     fs::FS,
-    mem::{normal::init_frame_allocator, slab::init_slab},
+    mem::{
+        normal::{init_frame_allocator, FRAME_ALLOCATOR},
+        slab::init_slab,
+    },
     time::get_time,
     trap::set_kernel_stvec,
 };
@@ -125,12 +130,18 @@ extern "C" fn rust_main() {
         infoln!("Initialized buddy allocator and slab allocator.");
 
         init_frame_allocator();
-        infoln!("Initialized frame allocator.");
+        infoln!(
+            "Initialized frame allocator with {} free pages.",
+            FRAME_ALLOCATOR.free_pages()
+        );
 
         activate_page_table(); // the kernel space is automatically init before activating page table because of the lazy_static!
         infoln!("Activated page table with satp {}.", satp::read().bits());
 
         init_devices();
+        init_realtime();
+        init_random();
+        infoln!("Initialized real time clock.");
         init_tasks();
 
         infoln!(
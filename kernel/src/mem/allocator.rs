@@ -2,6 +2,7 @@ use crate::mm::address::PhyPageNum;
 
 pub trait PageAllocator {
     unsafe fn init(&self, start: PhyPageNum, end: PhyPageNum);
-    fn alloc_page(&self) -> PhyPageNum;
+    /// Allocate a page, or return `None` if the memory runs out.
+    fn alloc_page(&self) -> Option<PhyPageNum>;
     fn dealloc_page(&self, ppn: PhyPageNum);
 }
@@ -1,61 +1,87 @@
 use alloc::vec::Vec;
-use spin::Spin;
+use allocator::buddy_allocator::BuddyAllocator;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::{mem::allocator::PageAllocator, mm::address::PhyPageNum};
+use crate::{
+    config::{PAGE_SIZE, PAGE_SIZE_BITS},
+    mem::allocator::PageAllocator,
+    mm::address::PhyPageNum,
+};
 
+/// The allocator for physical frames, which hands out `1 << order` contiguous frames aligned to their size.
+///
+/// The free blocks are kept by a buddy allocator with the granularity of a page,
+/// whose free lists lie in the free frames themselves.
 pub struct FrameAllocator {
-    inner: Spin<FrameAllocatorInner>,
+    buddy: BuddyAllocator,
+    /// The number of free frames, which could be read without the lock of the buddy allocator.
+    free: AtomicUsize,
 }
 
-/// The allocator for page table.
-pub struct FrameAllocatorInner {
-    start: PhyPageNum,
-    end: PhyPageNum,
-    recycled: Vec<PhyPageNum>,
-}
-
-impl Default for FrameAllocatorInner {
+impl Default for FrameAllocator {
     fn default() -> Self {
         Self {
-            start: PhyPageNum(0),
-            end: PhyPageNum(0),
-            recycled: Default::default(),
+            buddy: BuddyAllocator::empty(PAGE_SIZE),
+            free: AtomicUsize::new(0),
         }
     }
 }
 
-impl Default for FrameAllocator {
-    fn default() -> Self {
-        Self {
-            inner: Spin::new(FrameAllocatorInner::default()),
+impl FrameAllocator {
+    /// Allocate `1 << order` contiguous frames, or return `None` if there's no free block large enough.
+    pub fn alloc_pages(&self, order: usize) -> Option<PhyPageNum> {
+        let ptr = unsafe { self.buddy.alloc(Self::layout(order)) } as usize;
+        if ptr == 0 {
+            None
+        } else {
+            self.free.fetch_sub(1 << order, Ordering::Relaxed);
+            Some(ptr.into())
         }
     }
+
+    /// Release the frames starting from `ppn`, which should be allocated by `alloc_pages()` with the same `order`.
+    pub fn dealloc_pages(&self, ppn: PhyPageNum, order: usize) {
+        unsafe {
+            self.buddy
+                .dealloc(usize::from(ppn) as *mut u8, Self::layout(order))
+        };
+        self.free.fetch_add(1 << order, Ordering::Relaxed);
+    }
+
+    /// Count the free blocks of each order, where a block of order `i` has `1 << i` frames.
+    pub fn free_blocks(&self) -> Vec<usize> {
+        self.buddy.lock().free_blocks()[PAGE_SIZE_BITS..].to_vec()
+    }
+
+    /// Count the free frames of all orders.
+    pub fn free_pages(&self) -> usize {
+        self.free.load(Ordering::Relaxed)
+    }
+
+    fn layout(order: usize) -> Layout {
+        let size = PAGE_SIZE << order;
+        Layout::from_size_align(size, size).unwrap()
+    }
 }
 
 impl PageAllocator for FrameAllocator {
     /// The init could be done only once.
     unsafe fn init(&self, start: PhyPageNum, end: PhyPageNum) {
-        let mut guard = self.inner.lock();
-        guard.start = start;
-        guard.end = end;
-        guard.recycled.clear();
-    }
-
-    fn alloc_page(&self) -> PhyPageNum {
-        let mut guard = self.inner.lock();
-        let candidate = guard.recycled.pop();
-        if let Some(ppn) = candidate {
-            ppn
-        } else if guard.start < guard.end {
-            let ppn = guard.start;
-            guard.start += 1;
-            ppn
-        } else {
-            PhyPageNum::null()
-        }
+        self.buddy.add_segment(start.into(), end.into());
+        self.free.fetch_add(
+            (usize::from(end) - usize::from(start)) >> PAGE_SIZE_BITS,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn alloc_page(&self) -> Option<PhyPageNum> {
+        self.alloc_pages(0)
     }
 
     fn dealloc_page(&self, ppn: PhyPageNum) {
-        self.inner.lock().recycled.push(ppn);
+        self.dealloc_pages(ppn, 0)
     }
 }
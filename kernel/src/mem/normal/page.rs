@@ -3,8 +3,8 @@ use core::ops::{Deref, DerefMut};
 use spin::SpinGuard;
 
 use crate::{
-    config::PAGE_SIZE,
-    mem::{allocator::PageAllocator, page::Page},
+    config::{KERNEL_HEAP_RESERVED_PAGES, PAGE_SIZE},
+    mem::{allocator::PageAllocator, page::Page, slab::shrink_heap},
     mm::address::PhyPageNum,
 };
 
@@ -37,14 +37,40 @@ impl NormalPage {
 }
 
 impl NormalPageHandle {
-    pub fn new() -> Self {
-        let ppn = FRAME_ALLOCATOR.alloc_page();
+    /// Allocate a page filled with zero, or return `None` if the memory runs out.
+    ///
+    /// The last `KERNEL_HEAP_RESERVED_PAGES` free frames are left for the kernel heap,
+    /// and the heap gives back its free pages before the allocation fails.
+    pub fn new() -> Option<Self> {
+        let alloc = || {
+            if FRAME_ALLOCATOR.free_pages() > KERNEL_HEAP_RESERVED_PAGES {
+                FRAME_ALLOCATOR.alloc_page()
+            } else {
+                None
+            }
+        };
+        let ppn = alloc().or_else(|| {
+            shrink_heap();
+            alloc()
+        })?;
         NormalPage::alloc(ppn);
 
         // init
         let handle = Self { ppn };
         handle.init();
-        handle
+        Some(handle)
+    }
+
+    /// Share the page at `ppn` with a new handle, or return `None` if the page has been released.
+    pub fn share(ppn: PhyPageNum) -> Option<Self> {
+        let mut page = Page::from_ppn(ppn).lock();
+        match &mut *page {
+            Page::Normal(normal) if normal.refcnt > 0 => {
+                normal.refcnt += 1;
+                Some(Self { ppn })
+            }
+            _ => None,
+        }
     }
 
     pub fn init(&self) {
@@ -53,6 +79,19 @@ impl NormalPageHandle {
             core::slice::from_raw_parts_mut(ptr, PAGE_SIZE).fill(0);
         }
     }
+
+    /// Check whether the page is shared by more than one handle.
+    pub fn is_shared(&self) -> bool {
+        NormalPageGuard::new(self.ppn).refcnt > 1
+    }
+}
+
+impl Clone for NormalPageHandle {
+    /// Share the page with a new handle by increasing the referencing count, which doesn't copy the page.
+    fn clone(&self) -> Self {
+        NormalPageGuard::new(self.ppn).refcnt += 1;
+        Self { ppn: self.ppn }
+    }
 }
 
 impl Drop for NormalPageHandle {
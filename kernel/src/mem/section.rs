@@ -29,7 +29,7 @@ impl<T: PageAllocator> MemSec<T> {
         }
     }
 
-    pub fn alloc(&self) -> PhyPageNum {
+    pub fn alloc(&self) -> Option<PhyPageNum> {
         self.allocator.alloc_page()
     }
 
@@ -10,7 +10,7 @@ use core::mem::size_of;
 use core::ptr::null_mut;
 use spin::{Spin, SpinGuard};
 
-const BUDDY_ALLOCATOR_LEVEL: usize = 32;
+pub const BUDDY_ALLOCATOR_LEVEL: usize = 32;
 
 pub struct BuddyAllocator {
     pub allocator: Spin<BuddyAllocatorInner>,
@@ -66,10 +66,14 @@ impl PageAllocator for BuddyAllocator {
         self.add_segment(start.into(), end.into());
     }
 
-    fn alloc_page(&self) -> PhyPageNum {
+    fn alloc_page(&self) -> Option<PhyPageNum> {
         let layout = Layout::array::<u8>(PAGE_SIZE).unwrap();
         let ptr = unsafe { self.alloc(layout) } as usize;
-        ptr.into()
+        if ptr == 0 {
+            None
+        } else {
+            Some(ptr.into())
+        }
     }
 
     fn dealloc_page(&self, ppn: PhyPageNum) {
@@ -150,6 +154,15 @@ impl BuddyAllocatorInner {
         self.merge(level, ptr);
     }
 
+    /// Remove a free block in `level` whose address satisfies `f`, so that the memory could be used by others.
+    pub fn take_free_block(&mut self, level: usize, f: impl Fn(usize) -> bool) -> Option<usize> {
+        let node = self.free_lists[level]
+            .iter_mut()
+            .find(|node| f(node.as_ptr() as usize))?;
+        self.total -= 1 << level;
+        Some(node.pop() as usize)
+    }
+
     /// Split from level start to level end.
     fn split(&mut self, start: usize, end: usize) {
         for i in (start..end).rev() {
@@ -1,5 +1,5 @@
 use crate::{
-    mem::slab::{page::SlabPage, SLAB_MEM_SECTION},
+    mem::slab::{alloc_slab_page, page::SlabPage, SLAB_MEM_SECTION},
     mm::address::PhyPageNum,
 };
 
@@ -29,8 +29,11 @@ impl Cache {
                 page.next = PhyPageNum::null();
                 page.ppn
             } else {
-                // allocate new from buddy allocator
-                let ptr = unsafe { SLAB_MEM_SECTION.alloc() };
+                // allocate new from buddy allocator, or fail as a null pointer
+                let ptr = match alloc_slab_page() {
+                    Some(ptr) => ptr,
+                    None => return 0,
+                };
                 debugln!("Buddy allocates {:#x}.", usize::from(ptr));
                 SlabPage::alloc(ptr, self.order as u8);
                 ptr.into()
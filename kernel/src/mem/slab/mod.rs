@@ -1,18 +1,33 @@
-use core::{alloc::GlobalAlloc, cmp::max, mem::size_of};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp::max,
+    mem::size_of,
+};
 
 use spin::Spin;
 
-use crate::config::{KERNEL_HEAP_GRANULARITY, KERNEL_HEAP_SIZE, PAGE_SIZE_BITS};
+use crate::{
+    config::{
+        KERNEL_HEAP_GRANULARITY, KERNEL_HEAP_GROW_PAGES, KERNEL_HEAP_RESERVED_PAGES,
+        KERNEL_HEAP_SIZE, PAGE_SIZE, PAGE_SIZE_BITS,
+    },
+    mm::address::PhyPageNum,
+    proc::oom::request_oom_kill,
+};
 
-use self::{allocator::BuddyAllocator, cache::Cache};
+use self::{
+    allocator::{BuddyAllocator, BUDDY_ALLOCATOR_LEVEL},
+    cache::Cache,
+};
 
-use super::section::MemSec;
+use super::{normal::FRAME_ALLOCATOR, section::MemSec};
 
 pub mod allocator;
 pub mod cache;
 pub mod linked_list;
 pub mod page;
 
+/// The initial space of the heap, beyond which it grows with the frames from `grow_heap()`.
 #[link_section = ".data.heap"]
 static mut KERNEL_HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
@@ -31,6 +46,85 @@ pub fn init_slab() {
     }
 }
 
+/// Grow the heap with a block from the frame allocator that could hold `size` bytes, returning whether it succeeds.
+///
+/// A block of `KERNEL_HEAP_GROW_PAGES` pages is preferred, so that the heap doesn't grow page by page.
+/// Once the heap takes the frames kept by `KERNEL_HEAP_RESERVED_PAGES` or fails to grow,
+/// the out-of-memory killer is asked to release some, since it could not be called with the locks held here.
+fn grow_heap(size: usize) -> bool {
+    let order = (max(size, PAGE_SIZE) / PAGE_SIZE)
+        .next_power_of_two()
+        .trailing_zeros() as usize;
+    let preferred = max(order, KERNEL_HEAP_GROW_PAGES.trailing_zeros() as usize);
+    let block = [preferred, order]
+        .iter()
+        .find_map(|&order| FRAME_ALLOCATOR.alloc_pages(order).map(|ppn| (ppn, order)));
+    if FRAME_ALLOCATOR.free_pages() < KERNEL_HEAP_RESERVED_PAGES {
+        request_oom_kill();
+    }
+    match block {
+        Some((ppn, order)) => {
+            let start = usize::from(ppn);
+            debugln!("Kernel heap grows by {} pages at {:#x}.", 1 << order, start);
+            unsafe {
+                SLAB_MEM_SECTION
+                    .allocator
+                    .add_segment(start, start + (PAGE_SIZE << order))
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Return the free blocks that the heap has got from the frame allocator, which is done when the frames run out.
+///
+/// Since the slabs release their pages once they are fully free, any free page beyond `KERNEL_HEAP_SPACE` is returned.
+/// Return the number of pages returned.
+pub fn shrink_heap() -> usize {
+    extern "C" {
+        fn ekernel();
+    }
+    // the blocks merged with `KERNEL_HEAP_SPACE` start inside the kernel, which are never returned
+    let grown = |start: usize| start >= ekernel as usize;
+
+    let mut pages = 0;
+    let mut allocator = unsafe { SLAB_MEM_SECTION.allocator.lock() };
+    for level in PAGE_SIZE_BITS..BUDDY_ALLOCATOR_LEVEL {
+        while let Some(start) = allocator.take_free_block(level, grown) {
+            let order = level - PAGE_SIZE_BITS;
+            FRAME_ALLOCATOR.dealloc_pages(start.into(), order);
+            pages += 1 << order;
+        }
+    }
+    if pages > 0 {
+        debugln!("Kernel heap shrinks by {} pages.", pages);
+    }
+    pages
+}
+
+/// Allocate a page for the slabs, growing the heap if it runs out.
+pub(super) fn alloc_slab_page() -> Option<PhyPageNum> {
+    loop {
+        if let Some(ppn) = unsafe { SLAB_MEM_SECTION.alloc() } {
+            return Some(ppn);
+        }
+        if !grow_heap(PAGE_SIZE) {
+            return None;
+        }
+    }
+}
+
+/// Allocate more than a page for `layout` from the buddy allocator, growing the heap if it runs out.
+unsafe fn alloc_large(layout: Layout) -> *mut u8 {
+    loop {
+        let ptr = SLAB_MEM_SECTION.allocator.alloc(layout);
+        if !ptr.is_null() || !grow_heap(max(layout.size(), layout.align())) {
+            return ptr;
+        }
+    }
+}
+
 pub struct SlabAllocator {
     /// Different order allocate different size, which ranges from 1 byte to 4096 bytes.  
     caches: [Spin<Cache>; PAGE_SIZE_BITS + 1],
@@ -56,7 +150,7 @@ unsafe impl GlobalAlloc for SlabAllocator {
         if order <= PAGE_SIZE_BITS {
             self.caches[order].lock().alloc() as *mut u8
         } else {
-            SLAB_MEM_SECTION.allocator.alloc(layout)
+            alloc_large(layout)
         }
     }
 
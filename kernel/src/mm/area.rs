@@ -1,11 +1,12 @@
-use core::cmp::min;
+use core::cmp::{max, min};
 
 use alloc::{sync::Arc, vec::Vec};
+use simplefs::file::File;
 
-use crate::{config::PAGE_SIZE, mem::normal::page::NormalPageHandle};
+use crate::{config::PAGE_SIZE, drivers::blockdev::BlkDev, mem::normal::page::NormalPageHandle};
 
 use super::{
-    address::{PhyPageNum, VirPageNum},
+    address::{PhyPageNum, VirAddr, VirPageNum},
     memory::{MappingPermission, MappingType},
     page_table::PageTable,
     range::Range,
@@ -13,12 +14,41 @@ use super::{
 
 pub struct Area {
     range: Range<VirPageNum>,
-    frames: Vec<NormalPageHandle>,
+    /// The frames of the area, where a lazy area might have frames that are not allocated yet.
+    frames: Vec<Option<NormalPageHandle>>,
     map_type: MappingType,
     map_perm: MappingPermission,
+    backing: Option<FileBacking>,
+    /// Whether the frames are shared with the forked processes instead of being copied on write.
+    shared: bool,
     page_table: Arc<PageTable>,
 }
 
+/// The file that fills a lazy area or a shared area.
+///
+/// The virtual memory `[start, start + len)` is filled with `[offset, offset + len)` of the file,
+/// and the rest of the area is filled with zero.
+/// For a shared area, the virtual memory would be written back to the file when the area is dropped.
+#[derive(Clone)]
+pub struct FileBacking {
+    file: File<BlkDev>,
+    offset: usize,
+    start: VirAddr,
+    len: usize,
+}
+
+/// The result of handling a page fault within an area.
+pub enum AreaFault {
+    /// The access is legal and could be retried.
+    Resolved,
+    /// The access is illegal.
+    Invalid,
+    /// The page should be loaded from the file, which should be done without holding any spin lock.
+    Load(FileBacking),
+    /// The memory runs out, and the access could be retried after some memory is reclaimed.
+    OutOfMemory,
+}
+
 impl Area {
     /// Create a new area with identical mapping.
     ///
@@ -39,31 +69,119 @@ impl Area {
             frames: Default::default(),
             map_type: MappingType::Identical,
             map_perm,
+            backing: None,
+            shared: false,
             page_table: page_table.clone(),
         };
         // page_table.map_area(&res);
-        range
-            .iter()
-            .for_each(|vpn| page_table.map(vpn.into(), vpn.into(), map_perm.into()));
+        range.iter().for_each(|vpn| {
+            page_table
+                .map(vpn.into(), vpn.into(), map_perm.into())
+                .expect("[area] Fail to map an identical area.")
+        });
         res
     }
 
+    /// Create a new area whose frames are allocated and mapped at once, or return `None` if the memory runs out.
     pub fn new_framed(
         start: VirPageNum,
         end: VirPageNum,
         map_perm: MappingPermission,
         page_table: &Arc<PageTable>,
-    ) -> Self {
+    ) -> Option<Self> {
         let range = Range::new(start, end);
         let res = Self {
             range,
-            frames: range.iter().map(|_| NormalPageHandle::new()).collect(),
+            frames: range
+                .iter()
+                .map(|_| NormalPageHandle::new().map(Some))
+                .collect::<Option<_>>()?,
             map_type: MappingType::Framed,
             map_perm,
+            backing: None,
+            shared: false,
             page_table: page_table.clone(),
         };
-        page_table.map_area(&res);
-        res
+        if page_table.map_area(&res).is_none() {
+            res.discard();
+            return None;
+        }
+        Some(res)
+    }
+
+    /// Create a new area whose frames are allocated on the first access in `handle_fault()`.
+    ///
+    /// The frames are filled from `backing` if provided, or zeroed otherwise.
+    pub fn new_lazy(
+        start: VirPageNum,
+        end: VirPageNum,
+        map_perm: MappingPermission,
+        backing: Option<FileBacking>,
+        page_table: &Arc<PageTable>,
+    ) -> Self {
+        let range = Range::new(start, end);
+        Self {
+            range,
+            frames: range.iter().map(|_| None).collect(),
+            map_type: MappingType::Lazy,
+            map_perm,
+            backing,
+            shared: false,
+            page_table: page_table.clone(),
+        }
+    }
+
+    /// Create a new area whose frames are shared with the forked processes, filled from `backing` if provided.
+    ///
+    /// Reading the file might yield, so the caller should not hold any spin lock.
+    /// Return `None` if the memory runs out.
+    pub fn new_shared(
+        start: VirPageNum,
+        end: VirPageNum,
+        map_perm: MappingPermission,
+        backing: Option<FileBacking>,
+        page_table: &Arc<PageTable>,
+    ) -> Option<Self> {
+        let mut res = Self::new_lazy(start, end, map_perm, None, page_table);
+        res.shared = true;
+        let range = res.range;
+        let filled = range.iter().try_for_each(|vpn| {
+            let frame = NormalPageHandle::new()?;
+            if let Some(backing) = &backing {
+                backing.read_page(vpn, &frame);
+            }
+            res.fill(vpn, frame)
+        });
+        if filled.is_none() {
+            res.discard();
+            return None;
+        }
+        res.backing = backing;
+        Some(res)
+    }
+
+    /// Create a new area with `frames` that might be referenced by others, e.g. those of a shared memory object.
+    ///
+    /// The frames are copied on write unless the area is `shared`.
+    pub fn new_with_frames(
+        start: VirPageNum,
+        frames: Vec<NormalPageHandle>,
+        map_perm: MappingPermission,
+        shared: bool,
+        page_table: &Arc<PageTable>,
+    ) -> Option<Self> {
+        let mut res = Self::new_lazy(start, start + frames.len(), map_perm, None, page_table);
+        res.shared = shared;
+        let range = res.range;
+        let filled = range
+            .iter()
+            .zip(frames)
+            .try_for_each(|(vpn, frame)| res.fill(vpn, frame));
+        if filled.is_none() {
+            res.discard();
+            return None;
+        }
+        Some(res)
     }
 
     pub fn new_linear(
@@ -87,31 +205,109 @@ impl Area {
         todo!()
     }
 
-    pub fn renew(&self, page_table: &Arc<PageTable>) -> Self {
+    /// Renew the area in `page_table` by copying its frames, or return `None` if the memory runs out.
+    pub fn renew(&self, page_table: &Arc<PageTable>) -> Option<Self> {
         match self.map_type {
-            MappingType::Identical => {
-                Self::new_identical(self.range.start, self.range.end, self.map_perm, page_table)
-            }
+            MappingType::Identical => Some(Self::new_identical(
+                self.range.start,
+                self.range.end,
+                self.map_perm,
+                page_table,
+            )),
             MappingType::Framed => {
                 let res =
-                    Self::new_framed(self.range.start, self.range.end, self.map_perm, page_table);
+                    Self::new_framed(self.range.start, self.range.end, self.map_perm, page_table)?;
                 res.copy_from_existed(self);
-                res
+                Some(res)
             }
-            MappingType::Linear => Self::new_linear(
+            MappingType::Lazy => {
+                let mut res = Self::new_lazy(
+                    self.range.start,
+                    self.range.end,
+                    self.map_perm,
+                    self.backing.clone(),
+                    page_table,
+                );
+                let copied =
+                    self.range
+                        .iter()
+                        .zip(self.frames.iter())
+                        .try_for_each(|(vpn, frame)| {
+                            if let Some(frame) = frame {
+                                let copied = NormalPageHandle::new()?;
+                                unsafe {
+                                    copied
+                                        .ppn
+                                        .as_raw_bytes()
+                                        .copy_from_slice(frame.ppn.as_raw_bytes())
+                                };
+                                page_table.map(vpn, copied.ppn, self.map_perm.into())?;
+                                res.frames[vpn - self.range.start] = Some(copied);
+                            }
+                            Some(())
+                        });
+                if copied.is_none() {
+                    res.discard();
+                    return None;
+                }
+                Some(res)
+            }
+            MappingType::Linear => Some(Self::new_linear(
                 self.range.start,
-                self.frames[0].ppn,
+                self.frame(0).ppn,
                 self.len(),
                 self.map_perm,
                 page_table,
-            ),
+            )),
+        }
+    }
+
+    /// Renew the area by sharing its frames with copy-on-write instead of copying them.
+    ///
+    /// The shared frames are mapped as read-only in both page tables if the area is writable,
+    /// and the one who writes first would get its own copy in `handle_fault()`.
+    /// The frames of a shared area are mapped as they are, since both of them write to the same frames.
+    /// It's the caller's responsibility to flush the TLB for the original page table.
+    /// Return `None` if the memory runs out for the new page table.
+    pub fn renew_cow(&self, page_table: &Arc<PageTable>) -> Option<Self> {
+        match self.map_type {
+            MappingType::Framed | MappingType::Lazy => {
+                let res = Self {
+                    range: self.range,
+                    frames: self.frames.clone(),
+                    map_type: self.map_type,
+                    map_perm: self.map_perm,
+                    backing: self.backing.clone(),
+                    shared: self.shared,
+                    page_table: page_table.clone(),
+                };
+                let mapped =
+                    self.range
+                        .iter()
+                        .zip(res.frames.iter())
+                        .try_for_each(|(vpn, frame)| {
+                            if let Some(frame) = frame {
+                                if self.page_table.translate_vpn(vpn).is_some() {
+                                    self.page_table.protect(vpn, res.frame_perm(frame).into());
+                                }
+                                res.map_frame(vpn, frame)?;
+                            }
+                            Some(())
+                        });
+                if mapped.is_none() {
+                    res.discard();
+                    return None;
+                }
+                Some(res)
+            }
+            _ => self.renew(page_table),
         }
     }
 
     pub fn copy_from_raw_bytes(&self, data: &[u8]) {
         let mut start = 0;
         let len = data.len();
-        for frame in self.frames.iter() {
+        for frame in self.frames.iter().flatten() {
             let src = &data[start..min(len, start + PAGE_SIZE)];
             let dst = unsafe { &mut frame.ppn.as_raw_bytes()[..src.len()] };
             dst.copy_from_slice(src);
@@ -126,7 +322,8 @@ impl Area {
         assert_eq!(self.len(), other.len());
         self.frames
             .iter()
-            .zip(other.frames.iter())
+            .flatten()
+            .zip(other.frames.iter().flatten())
             .for_each(|(dst, src)| unsafe {
                 let dst_addr = dst.ppn.as_raw_bytes();
                 let src_addr = src.ppn.as_raw_bytes();
@@ -135,28 +332,47 @@ impl Area {
     }
 
     pub fn init(&self) {
-        self.frames.iter().for_each(|frame| {
+        self.frames.iter().flatten().for_each(|frame| {
             let ptr = usize::from(frame.ppn) as *mut u8;
             unsafe {
                 core::slice::from_raw_parts_mut(ptr, PAGE_SIZE).fill(0);
             }
         })
     }
+
+    /// Drop the area that fails to be created, removing the mappings made so far,
+    /// so that the page table never refers to its frames after they are released.
+    fn discard(self) {
+        self.unmap();
+        self.page_table.flush_tlb();
+    }
 }
 
 impl Drop for Area {
     fn drop(&mut self) {
         // self.page_table.unmap_area(self);
+
+        // write back the frames of shared file mapping when no one shares them any more
+        if let Some(backing) = self.backing.as_ref().filter(|_| self.shared) {
+            for (vpn, frame) in self.range.iter().zip(self.frames.iter()) {
+                if let Some(frame) = frame.as_ref().filter(|frame| !frame.is_shared()) {
+                    backing.write_page(vpn, frame);
+                }
+            }
+        }
     }
 }
 
 impl Area {
-    pub fn frames(&self) -> &Vec<NormalPageHandle> {
+    pub fn frames(&self) -> &Vec<Option<NormalPageHandle>> {
         &self.frames
     }
 
+    /// Get the frame with `index`, which should have been allocated.
     pub fn frame(&self, index: usize) -> &NormalPageHandle {
-        &self.frames[index]
+        self.frames[index]
+            .as_ref()
+            .expect("[area] Access a frame that is not allocated.")
     }
 
     pub fn map_perm(&self) -> MappingPermission {
@@ -174,4 +390,304 @@ impl Area {
     pub fn len(&self) -> usize {
         self.frames.len()
     }
+
+    pub fn contains(&self, vpn: VirPageNum) -> bool {
+        self.range.start <= vpn && vpn < self.range.end
+    }
+
+    /// The permission that `frame` should be mapped with, where the frame shared with copy-on-write is read-only.
+    fn frame_perm(&self, frame: &NormalPageHandle) -> MappingPermission {
+        if !self.shared && frame.is_shared() {
+            self.map_perm - MappingPermission::W
+        } else {
+            self.map_perm
+        }
+    }
+
+    /// Map `frame` at `vpn` with `frame_perm()`, unless the area could not be accessed at all,
+    /// since a page table entry without any of R, W and X points to the next level.
+    ///
+    /// Return `None` if the memory runs out for the page table.
+    fn map_frame(&self, vpn: VirPageNum, frame: &NormalPageHandle) -> Option<()> {
+        if self
+            .map_perm
+            .intersects(MappingPermission::R | MappingPermission::W | MappingPermission::X)
+        {
+            self.page_table
+                .map(vpn, frame.ppn, self.frame_perm(frame).into())?;
+        }
+        Some(())
+    }
+}
+
+impl Area {
+    /// Try to resolve a page fault on `vpn` that happens when accessing it with `access`.
+    ///
+    /// A page of lazy area that is not allocated yet would be allocated here, unless it should be loaded from the file.
+    /// A write to a page shared with copy-on-write would copy the page if it's still shared by others.
+    pub fn handle_fault(&mut self, vpn: VirPageNum, access: MappingPermission) -> AreaFault {
+        if !self.contains(vpn)
+            || !self.map_perm.contains(access | MappingPermission::U)
+            || self.map_type == MappingType::Identical
+        {
+            return AreaFault::Invalid;
+        }
+
+        let index = vpn - self.range.start;
+        if self.frames[index].is_none() {
+            if let Some(backing) = self.backing.as_ref().filter(|b| b.covers(vpn)) {
+                return AreaFault::Load(backing.clone());
+            }
+            return match NormalPageHandle::new().and_then(|frame| self.fill(vpn, frame)) {
+                Some(()) => AreaFault::Resolved,
+                None => AreaFault::OutOfMemory,
+            };
+        }
+
+        // the page might have been mapped by others before the fault is handled
+        let mapped = self
+            .page_table
+            .translate_vpn(vpn)
+            .map_or(false, |pte| pte.get_flags().contains(access.into()));
+        if mapped {
+            return AreaFault::Resolved;
+        }
+
+        let frame = self.frame(index);
+        if access.contains(MappingPermission::W) && !self.shared && frame.is_shared() {
+            let copied = match NormalPageHandle::new() {
+                Some(copied) => copied,
+                None => return AreaFault::OutOfMemory,
+            };
+            unsafe {
+                copied
+                    .ppn
+                    .as_raw_bytes()
+                    .copy_from_slice(frame.ppn.as_raw_bytes())
+            };
+            self.frames[index] = Some(copied);
+        }
+
+        // the page is not mapped, or it's mapped with less permission, e.g. after `mprotect()`
+        if self.map_frame(vpn, self.frame(index)).is_none() {
+            return AreaFault::OutOfMemory;
+        }
+        self.page_table.flush_tlb();
+        AreaFault::Resolved
+    }
+
+    /// Put the `frame` into the lazy area at `vpn` and map it, if there is no frame there yet.
+    ///
+    /// Otherwise, the `frame` is dropped, which happens when others has filled the page during loading.
+    /// Return `None` if the memory runs out for the page table, where the `frame` is dropped as well.
+    pub fn fill(&mut self, vpn: VirPageNum, frame: NormalPageHandle) -> Option<()> {
+        let index = vpn - self.range.start;
+        if self.frames[index].is_none() {
+            self.map_frame(vpn, &frame)?;
+            self.frames[index] = Some(frame);
+            self.page_table.flush_tlb();
+        }
+        Some(())
+    }
+
+    /// Allocate the frame of `vpn` if it's not allocated yet, which is filled from the file if the page is backed.
+    ///
+    /// Just like `populate()`, it's useful when the kernel writes to an area which is not used by the current page table.
+    /// Reading the file might yield, so the caller should not hold any spin lock.
+    /// Return `None` if the memory runs out.
+    pub fn load(&mut self, vpn: VirPageNum) -> Option<&NormalPageHandle> {
+        let index = vpn - self.range.start;
+        if self.frames[index].is_none() {
+            let frame = match self.backing.as_ref().filter(|b| b.covers(vpn)) {
+                Some(backing) => backing.load(vpn)?,
+                None => NormalPageHandle::new()?,
+            };
+            self.fill(vpn, frame)?;
+        }
+        Some(self.frame(index))
+    }
+
+    /// Allocate all the frames in `[start, end)` that are not allocated yet with zero.
+    ///
+    /// It's useful when the kernel writes to an area which is not used by the current page table.
+    /// Return `None` if the memory runs out, where the frames allocated so far are kept.
+    pub fn populate(&mut self, start: VirPageNum, end: VirPageNum) -> Option<()> {
+        let start = max(start, self.range.start);
+        let end = min(end, self.range.end);
+        if start >= end {
+            return Some(());
+        }
+        for vpn in Range::new(start, end).iter() {
+            if self.frames[vpn - self.range.start].is_none() {
+                self.fill(vpn, NormalPageHandle::new()?)?;
+            }
+        }
+        Some(())
+    }
+}
+
+impl Area {
+    /// Resize the lazy area so that it ends at `end`, where the frames beyond `end` are released.
+    pub fn resize(&mut self, end: VirPageNum) {
+        assert!(self.map_type == MappingType::Lazy && self.range.start <= end);
+        if end < self.range.end {
+            for vpn in Range::new(end, self.range.end).iter() {
+                if self.frames[vpn - self.range.start].is_some()
+                    && self.page_table.translate_vpn(vpn).is_some()
+                {
+                    self.page_table.unmap(vpn);
+                }
+            }
+            self.frames.truncate(end - self.range.start);
+            self.page_table.flush_tlb();
+        } else {
+            self.frames.resize_with(end - self.range.start, || None);
+        }
+        self.range.end = end;
+    }
+}
+
+impl Area {
+    /// Split the area at `at`, where `self` keeps `[start, at)` and the returned area holds `[at, end)`.
+    pub fn split_off(&mut self, at: VirPageNum) -> Self {
+        assert!(
+            self.range.start < at
+                && at < self.range.end
+                && (self.map_type == MappingType::Framed || self.map_type == MappingType::Lazy)
+        );
+        let res = Self {
+            range: Range::new(at, self.range.end),
+            frames: self.frames.split_off(at - self.range.start),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            backing: self.backing.clone(),
+            shared: self.shared,
+            page_table: self.page_table.clone(),
+        };
+        self.range.end = at;
+        res
+    }
+
+    /// Remove all the mappings of the area from the page table, while the frames are kept until it's dropped.
+    ///
+    /// It's the caller's responsibility to flush the TLB.
+    pub fn unmap(&self) {
+        for (vpn, frame) in self.range.iter().zip(self.frames.iter()) {
+            if frame.is_some() && self.page_table.translate_vpn(vpn).is_some() {
+                self.page_table.unmap(vpn);
+            }
+        }
+    }
+
+    /// Collect the pages of a shared file mapping in `[start, end)` with the file they should be written back to,
+    /// so that they could be written without holding the lock of the process.
+    pub fn shared_pages(
+        &self,
+        start: VirPageNum,
+        end: VirPageNum,
+    ) -> Vec<(FileBacking, VirPageNum, NormalPageHandle)> {
+        let backing = match self.backing.as_ref().filter(|_| self.shared) {
+            Some(backing) => backing,
+            None => return Vec::new(),
+        };
+        self.range
+            .iter()
+            .zip(self.frames.iter())
+            .filter(|(vpn, _)| start <= *vpn && *vpn < end)
+            .filter_map(|(vpn, frame)| Some((backing.clone(), vpn, frame.clone()?)))
+            .collect()
+    }
+
+    /// Drop the area after writing back the frames of a shared file mapping,
+    /// even if they are still shared with others, so that the file is up to date once the range is unmapped.
+    ///
+    /// Writing the file might yield, so the caller should not hold any spin lock.
+    pub fn write_back(mut self) {
+        if let Some(backing) = self.backing.take().filter(|_| self.shared) {
+            for (vpn, frame) in self.range.iter().zip(self.frames.iter()) {
+                if let Some(frame) = frame {
+                    backing.write_page(vpn, frame);
+                }
+            }
+        }
+    }
+
+    /// Flush the TLB of the page table that the area is mapped into, on every hart that runs it.
+    pub fn flush_tlb(&self) {
+        self.page_table.flush_tlb();
+    }
+
+    /// Change the permission of the area, remapping the frames that have been allocated.
+    ///
+    /// It's the caller's responsibility to flush the TLB.
+    /// A frame that fails to be remapped for lack of memory is left unmapped, and mapped again on the next fault.
+    pub fn protect(&mut self, map_perm: MappingPermission) {
+        self.unmap();
+        self.map_perm = map_perm;
+        for (vpn, frame) in self.range.iter().zip(self.frames.iter()) {
+            if let Some(frame) = frame {
+                let _ = self.map_frame(vpn, frame);
+            }
+        }
+    }
+}
+
+impl FileBacking {
+    pub fn new(file: File<BlkDev>, offset: usize, start: VirAddr, len: usize) -> Self {
+        Self {
+            file,
+            offset,
+            start,
+            len,
+        }
+    }
+
+    /// Check whether the page `vpn` contains any byte of the file.
+    fn covers(&self, vpn: VirPageNum) -> bool {
+        let page_start = usize::from(vpn);
+        let start = usize::from(self.start);
+        page_start < start + self.len && start < page_start + PAGE_SIZE
+    }
+
+    /// Create a new frame for the page `vpn`, filled with the part of file that it covers.
+    ///
+    /// Reading the file might yield, so the caller should not hold any spin lock.
+    /// Return `None` if the memory runs out.
+    pub fn load(&self, vpn: VirPageNum) -> Option<NormalPageHandle> {
+        let frame = NormalPageHandle::new()?;
+        self.read_page(vpn, &frame);
+        Some(frame)
+    }
+
+    /// Read the part of file that the page `vpn` covers into `frame`.
+    fn read_page(&self, vpn: VirPageNum, frame: &NormalPageHandle) {
+        if let Some((offset, bytes)) = self.page_bytes(vpn, frame) {
+            self.file.lock().read_at(bytes, offset);
+        }
+    }
+
+    /// Write the part of `frame` that the file covers back to the file, which goes through the block cache.
+    pub fn write_page(&self, vpn: VirPageNum, frame: &NormalPageHandle) {
+        if let Some((offset, bytes)) = self.page_bytes(vpn, frame) {
+            self.file.lock().write_at(bytes, offset);
+        }
+    }
+
+    /// Find the bytes of `frame` for the page `vpn` that the file covers, along with their offset in the file.
+    fn page_bytes(
+        &self,
+        vpn: VirPageNum,
+        frame: &NormalPageHandle,
+    ) -> Option<(usize, &'static mut [u8])> {
+        let page_start = usize::from(vpn);
+        let start = max(page_start, usize::from(self.start));
+        let end = min(page_start + PAGE_SIZE, usize::from(self.start) + self.len);
+        if start < end {
+            let bytes =
+                unsafe { &mut frame.ppn.as_raw_bytes()[start - page_start..end - page_start] };
+            Some((self.offset + start - usize::from(self.start), bytes))
+        } else {
+            None
+        }
+    }
 }
@@ -1,9 +1,17 @@
-use super::{area::Area, page_table::PageTable};
-
-use crate::mm::page_table::KERNEL_PAGE_TABLE;
+use super::{
+    address::VirPageNum,
+    area::{Area, AreaFault, FileBacking},
+    page_table::PageTable,
+};
+use crate::{
+    config::{USER_MMAP_BASE, USER_SPACE_END},
+    mem::normal::page::NormalPageHandle,
+    mm::page_table::KERNEL_PAGE_TABLE,
+};
 
 use alloc::{sync::Arc, vec::Vec};
 use bitflags::bitflags;
+use core::cmp::{max, min};
 use lazy_static::lazy_static;
 
 pub struct MemSet {
@@ -24,6 +32,7 @@ pub enum MappingType {
     Identical,
     Framed,
     Linear,
+    Lazy,
 }
 
 impl MemSet {
@@ -35,14 +44,184 @@ impl MemSet {
         self.areas.len()
     }
 
-    pub fn renew(&self, page_table: &Arc<PageTable>) -> Self {
-        Self {
+    pub fn areas(&self) -> &Vec<Area> {
+        &self.areas
+    }
+
+    /// Count the frames that have been allocated for the memory set, which is how much memory it takes.
+    pub fn frames(&self) -> usize {
+        self.areas
+            .iter()
+            .map(|area| area.frames().iter().flatten().count())
+            .sum()
+    }
+
+    pub fn renew(&self, page_table: &Arc<PageTable>) -> Option<Self> {
+        Some(Self {
             areas: self
                 .areas
                 .iter()
                 .map(|area| area.renew(page_table))
-                .collect(),
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Renew the memory set by sharing all the frames with copy-on-write, or return `None` if the memory runs out.
+    ///
+    /// It's the caller's responsibility to flush the TLB for the original page table.
+    pub fn renew_cow(&self, page_table: &Arc<PageTable>) -> Option<Self> {
+        Some(Self {
+            areas: self
+                .areas
+                .iter()
+                .map(|area| area.renew_cow(page_table))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Handle a page fault of user on `vpn` with the area that contains it.
+    pub fn handle_fault(&mut self, vpn: VirPageNum, access: MappingPermission) -> AreaFault {
+        self.areas
+            .iter_mut()
+            .find(|area| area.contains(vpn))
+            .map_or(AreaFault::Invalid, |area| area.handle_fault(vpn, access))
+    }
+
+    /// Find the area that starts at `start`.
+    pub fn area_mut(&mut self, start: VirPageNum) -> Option<&mut Area> {
+        self.areas
+            .iter_mut()
+            .find(|area| area.range().start == start)
+    }
+
+    /// Fill the page `vpn` with `frame` that is loaded after `handle_fault()`.
+    ///
+    /// Return `None` if the memory runs out for the page table.
+    pub fn fill(&mut self, vpn: VirPageNum, frame: NormalPageHandle) -> Option<()> {
+        match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area) => area.fill(vpn, frame),
+            None => Some(()),
+        }
+    }
+
+    /// Insert a new area, which should not overlap with any existing area.
+    pub fn insert(&mut self, area: Area) {
+        self.areas.push(area);
+    }
+
+    /// Find the lowest free range of `pages` pages in the user space for mappings.
+    pub fn find_free(&self, pages: usize) -> Option<VirPageNum> {
+        let mut start = VirPageNum::from(USER_MMAP_BASE);
+        loop {
+            let end = start + pages;
+            if usize::from(end) > USER_SPACE_END {
+                return None;
+            }
+            match self
+                .areas
+                .iter()
+                .filter(|area| area.range().start < end && start < area.range().end)
+                .map(|area| area.range().end)
+                .max()
+            {
+                Some(next) => start = next,
+                None => return Some(start),
+            }
+        }
+    }
+
+    /// Split the area that contains `vpn` so that `vpn` is at the boundary of areas.
+    fn split_at(&mut self, vpn: VirPageNum) {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.range().start < vpn && vpn < area.range().end)
+        {
+            let rest = area.split_off(vpn);
+            self.areas.push(rest);
+        }
+    }
+
+    /// Remove the mappings of `[start, end)`, returning the removed areas.
+    ///
+    /// The areas should be dropped by the caller after releasing any spin lock,
+    /// since writing back a shared file mapping might yield.
+    pub fn unmap(&mut self, start: VirPageNum, end: VirPageNum) -> Vec<Area> {
+        self.split_at(start);
+        self.split_at(end);
+        let (removed, kept) = self
+            .areas
+            .drain(..)
+            .partition(|area| start <= area.range().start && area.range().end <= end);
+        self.areas = kept;
+        removed.iter().for_each(|area| area.unmap());
+        if let Some(area) = removed.first() {
+            area.flush_tlb();
+        }
+        removed
+    }
+
+    /// Collect the pages of the shared file mappings in `[start, end)`, which are written back by `msync()`.
+    ///
+    /// Return `None` if any page of the range is not mapped.
+    pub fn shared_pages(
+        &self,
+        start: VirPageNum,
+        end: VirPageNum,
+    ) -> Option<Vec<(FileBacking, VirPageNum, NormalPageHandle)>> {
+        let mut covered = 0;
+        let mut pages = Vec::new();
+        for area in self.areas.iter() {
+            let range = area.range();
+            let (lo, hi) = (max(range.start, start), min(range.end, end));
+            if lo < hi {
+                covered += hi - lo;
+                pages.extend(area.shared_pages(lo, hi));
+            }
+        }
+        if covered == end - start {
+            Some(pages)
+        } else {
+            None
+        }
+    }
+
+    /// Change the permission of `[start, end)` to `map_perm`.
+    ///
+    /// Fail if any page of the range is not mapped.
+    pub fn protect(
+        &mut self,
+        start: VirPageNum,
+        end: VirPageNum,
+        map_perm: MappingPermission,
+    ) -> bool {
+        let mut covered = 0;
+        for area in self.areas.iter() {
+            let range = area.range();
+            let (lo, hi) = (max(range.start, start), min(range.end, end));
+            if lo < hi {
+                if area.map_type() == MappingType::Identical
+                    || area.map_type() == MappingType::Linear
+                {
+                    return false;
+                }
+                covered += hi - lo;
+            }
+        }
+        if covered != end - start {
+            return false;
+        }
+
+        self.split_at(start);
+        self.split_at(end);
+        self.areas
+            .iter_mut()
+            .filter(|area| start <= area.range().start && area.range().end <= end)
+            .for_each(|area| area.protect(map_perm));
+        if let Some(area) = self.areas.first() {
+            area.flush_tlb();
         }
+        true
     }
 }
 
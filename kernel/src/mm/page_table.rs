@@ -2,29 +2,38 @@ use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::bitflags;
+use fosix::errno::{ENOEXEC, ENOMEM};
 use lazy_static::lazy_static;
+use simplefs::file::File;
 use spin::Spin;
 
 use core::arch::asm;
 use core::cmp::{max, min};
-use core::mem::size_of;
+use core::hint::spin_loop;
+use core::mem::{size_of, MaybeUninit};
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
 
 use super::address::{GenOffset, PhyAddr, VirAddr};
-use super::area::Area;
+use super::area::{Area, FileBacking};
 use super::memory::MemSet;
 use super::{
     address::{PhyPageNum, VirPageNum},
     memory::MappingPermission,
 };
 use crate::config::{
-    CLINT, MEMORY_END, PAGE_SIZE, PPN_WIDTH, PTE_FLAG_WIDTH, TRAMPOLINE_ADDR, UART_BASE_ADDRESS,
-    UART_MAP_SIZE, VIRTIO_ADDR, VIRTIO_SIZE, VIRT_PLIC_ADDR, VIRT_PLIC_SIZE, VIRT_TEST,
-    VIRT_TEST_SIZE,
+    CLINT, CPUS, MAX_USER_STR_LEN, MEMORY_END, PAGE_SIZE, PPN_WIDTH, PTE_FLAG_WIDTH,
+    TRAMPOLINE_ADDR, UART_BASE_ADDRESS, UART_MAP_SIZE, USER_ASLR_PAGES, USER_HEAP_BASE,
+    USER_PIE_BASE, USER_SPACE_END, VIRTIO_ADDR, VIRTIO_SIZE, VIRT_PLIC_ADDR, VIRT_PLIC_SIZE,
+    VIRT_TEST, VIRT_TEST_SIZE,
 };
+use crate::drivers::blockdev::BlkDev;
 use crate::fs::segment::Segment;
 use crate::mem::normal::page::NormalPageHandle;
 use crate::mm::memory::KERNEL_MEMSET;
 use crate::proc::stack::UserStack;
+use crate::random::random;
+use crate::task::processor::Processor;
+use crate::time::send_ipi;
 use crate::trap::context::TrapCtxHandle;
 
 bitflags! {
@@ -142,13 +151,37 @@ impl PageTable {
             MappingPermission::R | MappingPermission::W,
         ));
 
-        self.map_trampoline();
+        self.map_trampoline()
+            .expect("[page_table] Fail to map the trampoline for kernel.");
 
         MemSet::new(areas)
     }
 
-    pub fn new_user(self: &Arc<Self>, elf_data: &[u8]) -> (VirAddr, VirAddr, MemSet) {
+    /// Create the memory set of user from the elf `file`, where `elf_data` is the prefix of it that contains all the headers.
+    ///
+    /// The segments are loaded lazily from the file when the user accesses them.
+    /// The `PT_TLS` segment is copied into a block after the loadable segments, which the thread pointer points to,
+    /// or the thread pointer is null if there's no such segment.
+    ///
+    /// A position-independent executable is loaded at `USER_PIE_BASE` instead of its linked addresses,
+    /// and its relocations are applied by `relocate()`.
+    /// If `randomize` is set, the program, the user stacks and the heap are shifted by random pages.
+    /// Return `-ENOEXEC` if the relocations are not supported, or `-ENOMEM` if the memory runs out.
+    pub fn new_user(
+        self: &Arc<Self>,
+        file: &File<BlkDev>,
+        elf_data: &[u8],
+        randomize: bool,
+    ) -> Result<UserImage, isize> {
         let mut areas = Vec::new();
+        let mut tls = None;
+        let random_offset = || {
+            if randomize {
+                (random() % USER_ASLR_PAGES) * PAGE_SIZE
+            } else {
+                0
+            }
+        };
 
         let elf_file =
             xmas_elf::ElfFile::new(elf_data).expect("[memory_set] Fail to parse ELF file.");
@@ -157,6 +190,12 @@ impl PageTable {
         if magic != [0x7f, 0x45, 0x4c, 0x46] {
             panic!("[memory_set] Invalid ELF file.");
         }
+        let pie = elf_header.pt2.type_().as_type() == xmas_elf::header::Type::SharedObject;
+        let bias = if pie {
+            USER_PIE_BASE + random_offset()
+        } else {
+            0
+        };
 
         let ph_count = elf_header.pt2.ph_count();
         let mut end_vpn = VirPageNum(0);
@@ -165,8 +204,9 @@ impl PageTable {
                 .program_header(i)
                 .expect("[memory_set] Fail to get program header.");
             if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirAddr = (ph.virtual_addr() as usize + ph.mem_size() as usize).into();
+                let start_va: VirAddr = (bias + ph.virtual_addr() as usize).into();
+                let end_va: VirAddr =
+                    (bias + ph.virtual_addr() as usize + ph.mem_size() as usize).into();
                 let mut map_perm = MappingPermission::U;
                 let ph_flags = ph.flags();
 
@@ -184,30 +224,186 @@ impl PageTable {
                     usize::from(start_va),
                     usize::from(end_va)
                 );
-                let area = self.new_framed_area(
+                let backing = FileBacking::new(
+                    file.clone(),
+                    ph.offset() as usize,
+                    start_va,
+                    ph.file_size() as usize,
+                );
+                let area = self.new_lazy_area(
                     start_va.floor_to_vir_page_num(),
                     end_va.ceil_to_vir_page_num(),
                     map_perm,
+                    Some(backing),
                 );
-                end_vpn = end_va.ceil_to_vir_page_num();
-                area.copy_from_raw_bytes(
-                    &elf_file.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
-                );
+                end_vpn = max(end_vpn, end_va.ceil_to_vir_page_num());
                 areas.push(area);
+            } else if ph.get_type().unwrap() == xmas_elf::program::Type::Tls {
+                tls = Some(ph);
             }
         }
+        if pie {
+            relocate(file, &elf_file, &mut areas, bias)?;
+        }
+
+        // the block is page-aligned, which satisfies the alignment of the segment
+        let mut tp = 0;
+        if let Some(ph) = tls {
+            let start_va: VirAddr = end_vpn.into();
+            let end_va: VirAddr = (usize::from(start_va) + ph.mem_size() as usize).into();
+            let backing = FileBacking::new(
+                file.clone(),
+                ph.offset() as usize,
+                start_va,
+                ph.file_size() as usize,
+            );
+            areas.push(self.new_lazy_area(
+                start_va.floor_to_vir_page_num(),
+                end_va.ceil_to_vir_page_num(),
+                MappingPermission::R | MappingPermission::W | MappingPermission::U,
+                Some(backing),
+            ));
+            tp = start_va.into();
+            end_vpn = end_va.ceil_to_vir_page_num();
+        }
 
-        self.map_trampoline();
+        // the heap is empty at first, and it's resized by `brk()`
+        let heap_base = USER_HEAP_BASE - random_offset();
+        let heap_start = VirAddr::from(heap_base).floor_to_vir_page_num();
+        areas.push(self.new_lazy_area(
+            heap_start,
+            heap_start,
+            MappingPermission::R | MappingPermission::W | MappingPermission::U,
+            None,
+        ));
+
+        self.map_trampoline().ok_or(-ENOMEM)?;
 
-        let base: VirAddr = (end_vpn + 1).into(); // for guard page
-        (
+        // for guard page
+        let base = VirAddr::from(end_vpn + 1) + random_offset();
+        Ok(UserImage {
             base,
-            (elf_file.header.pt2.entry_point() as usize).into(),
-            MemSet::new(areas),
-        )
+            entry: (bias + elf_file.header.pt2.entry_point() as usize).into(),
+            tp,
+            bias,
+            heap_base,
+            mem: MemSet::new(areas),
+        })
     }
 }
 
+/// The program of user mapped by `PageTable::new_user()`.
+pub struct UserImage {
+    /// The base of the user stacks.
+    pub base: VirAddr,
+    pub entry: VirAddr,
+    /// The thread pointer of the main thread.
+    pub tp: usize,
+    /// The offset from the linked addresses to where the program is loaded, which is 0 unless it's position-independent.
+    pub bias: usize,
+    pub heap_base: usize,
+    pub mem: MemSet,
+}
+
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const DT_REL: u64 = 17;
+const DYN_ENTRY_SIZE: usize = 16;
+const RELA_ENTRY_SIZE: usize = 24;
+const R_RISCV_NONE: u32 = 0;
+const R_RISCV_RELATIVE: u32 = 3;
+
+/// Apply the relocations of the position-independent executable loaded at `bias` to the loadable segments in `areas`.
+///
+/// Since there's no dynamic linker, only the `R_RISCV_RELATIVE` relocations of a static PIE are supported,
+/// while any other relocation is rejected with `-ENOEXEC`.
+fn relocate(
+    file: &File<BlkDev>,
+    elf_file: &xmas_elf::ElfFile,
+    areas: &mut Vec<Area>,
+    bias: usize,
+) -> Result<(), isize> {
+    let dynamic = match elf_file
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Dynamic))
+    {
+        Some(dynamic) => dynamic,
+        None => return Ok(()),
+    };
+    let mut dyn_data = vec![0u8; dynamic.file_size() as usize];
+    file.lock()
+        .read_at(&mut dyn_data, dynamic.offset() as usize);
+
+    let (mut rela, mut rela_size, mut rela_ent) = (None, 0, RELA_ENTRY_SIZE);
+    for entry in dyn_data.chunks_exact(DYN_ENTRY_SIZE) {
+        let tag = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        let value = u64::from_le_bytes(entry[8..].try_into().unwrap()) as usize;
+        match tag {
+            DT_NULL => break,
+            DT_RELA => rela = Some(value),
+            DT_RELASZ => rela_size = value,
+            DT_RELAENT => rela_ent = value,
+            DT_REL => return Err(-ENOEXEC),
+            _ => {}
+        }
+    }
+    let rela = match rela {
+        Some(rela) => rela,
+        None => return Ok(()),
+    };
+    if rela_ent != RELA_ENTRY_SIZE {
+        return Err(-ENOEXEC);
+    }
+
+    // the table is found by its virtual address, which lies in the file part of a loadable segment
+    let offset = elf_file
+        .program_iter()
+        .filter(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Load))
+        .find(|ph| {
+            let start = ph.virtual_addr() as usize;
+            start <= rela
+                && rela
+                    .checked_add(rela_size)
+                    .map_or(false, |end| end <= start + ph.file_size() as usize)
+        })
+        .map(|ph| rela - ph.virtual_addr() as usize + ph.offset() as usize)
+        .ok_or(-ENOEXEC)?;
+    let mut rela_data = vec![0u8; rela_size];
+    file.lock().read_at(&mut rela_data, offset);
+
+    for entry in rela_data.chunks_exact(RELA_ENTRY_SIZE) {
+        let r_offset = u64::from_le_bytes(entry[..8].try_into().unwrap()) as usize;
+        let r_info = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+        let r_addend = i64::from_le_bytes(entry[16..].try_into().unwrap());
+        match r_info as u32 {
+            R_RISCV_NONE => {}
+            R_RISCV_RELATIVE => {
+                let value = bias.wrapping_add_signed(r_addend as isize);
+                write_areas(areas, bias + r_offset, &value.to_le_bytes())?;
+            }
+            _ => return Err(-ENOEXEC),
+        }
+    }
+    Ok(())
+}
+
+/// Write `bytes` to `addr` of `areas`, which are not used by the current page table yet.
+fn write_areas(areas: &mut Vec<Area>, addr: usize, bytes: &[u8]) -> Result<(), isize> {
+    for (i, byte) in bytes.iter().enumerate() {
+        let va = VirAddr::from(addr + i);
+        let vpn = va.floor_to_vir_page_num();
+        let area = areas
+            .iter_mut()
+            .find(|area| area.contains(vpn))
+            .ok_or(-ENOEXEC)?;
+        let frame = area.load(vpn).ok_or(-ENOMEM)?;
+        unsafe { frame.ppn.as_raw_bytes()[usize::from(va.gen_offset())] = *byte };
+    }
+    Ok(())
+}
+
 impl PageTable {
     pub fn new_identical_area(
         self: &Arc<Self>,
@@ -223,10 +419,20 @@ impl PageTable {
         start_vpn: VirPageNum,
         end_vpn: VirPageNum,
         map_perm: MappingPermission,
-    ) -> Area {
+    ) -> Option<Area> {
         Area::new_framed(start_vpn, end_vpn, map_perm, self)
     }
 
+    pub fn new_lazy_area(
+        self: &Arc<Self>,
+        start_vpn: VirPageNum,
+        end_vpn: VirPageNum,
+        map_perm: MappingPermission,
+        backing: Option<FileBacking>,
+    ) -> Area {
+        Area::new_lazy(start_vpn, end_vpn, map_perm, backing, self)
+    }
+
     pub fn new_linear_area(
         self: &Arc<Self>,
         start_vpn: VirPageNum,
@@ -237,7 +443,7 @@ impl PageTable {
         Area::new_linear(start_vpn, start_ppn, len, map_perm, self)
     }
 
-    pub fn new_trap_ctx(self: &Arc<Self>, tid: usize) -> TrapCtxHandle {
+    pub fn new_trap_ctx(self: &Arc<Self>, tid: usize) -> Option<TrapCtxHandle> {
         TrapCtxHandle::new(tid, self)
     }
 
@@ -245,7 +451,7 @@ impl PageTable {
         UserStack::new(base, tid, self)
     }
 
-    pub fn map_trampoline(&self) {
+    pub fn map_trampoline(&self) -> Option<()> {
         extern "C" {
             fn strampoline();
         }
@@ -258,29 +464,39 @@ impl PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let frame = NormalPageHandle::new();
+    /// Create an empty page table, or return `None` if the memory runs out.
+    pub fn new() -> Option<Self> {
+        let frame = NormalPageHandle::new()?;
         infoln!("Created a new page table {}.", usize::from(frame.ppn));
-        Self {
+        Some(Self {
             root: frame.ppn,
             frames: Spin::new(vec![frame]),
-        }
+        })
     }
 
-    pub fn map(&self, vpn: VirPageNum, ppn: PhyPageNum, flags: PTEFlags) {
-        let pte = self.create_pte(vpn);
+    /// Map `vpn` to `ppn`, or return `None` if the memory runs out for the page table itself.
+    pub fn map(&self, vpn: VirPageNum, ppn: PhyPageNum, flags: PTEFlags) -> Option<()> {
+        let pte = self.create_pte(vpn)?;
         pte.set_ppn(ppn);
         pte.set_flags(flags | PTEFlags::V);
+        Some(())
+    }
+
+    /// Change the flags of a mapped page table entry, keeping its physical page number.
+    pub fn protect(&self, vpn: VirPageNum, flags: PTEFlags) {
+        let pte = self
+            .find_pte(vpn)
+            .expect("[page_table] Protect a non-exist page table entry.");
+        pte.set_flags(flags | PTEFlags::V);
     }
 
-    pub fn map_area(&self, area: &Area) {
+    pub fn map_area(&self, area: &Area) -> Option<()> {
         let flags = area.map_perm().into();
         area.range()
             .iter()
             .zip(area.frames().iter())
-            .for_each(|(vpn, frame)| {
-                self.map(vpn, frame.ppn, flags);
-            });
+            .filter_map(|(vpn, frame)| frame.as_ref().map(|frame| (vpn, frame)))
+            .try_for_each(|(vpn, frame)| self.map(vpn, frame.ppn, flags))
     }
 
     pub fn unmap(&self, vpn: VirPageNum) {
@@ -299,17 +515,49 @@ impl PageTable {
         8usize << 60 | self.root.0
     }
 
+    /// Flush the TLB after the mappings of the page table are changed,
+    /// which should be done before the frames that were mapped are reused or the lowered permissions are relied on.
+    ///
+    /// The other harts running the page table in user mode are interrupted, and it waits until they have trapped,
+    /// while those in the kernel would flush the TLB when returning to user mode.
+    pub fn flush_tlb(&self) {
+        unsafe {
+            asm!("sfence.vma");
+        }
+        fence(Ordering::SeqCst);
+
+        let satp = self.to_satp();
+        let mut targets = [None; CPUS];
+        for (id, space) in USER_SPACES.iter().enumerate() {
+            let traps = space.traps.load(Ordering::SeqCst);
+            if space.satp.load(Ordering::SeqCst) == satp {
+                space.shootdown.store(true, Ordering::SeqCst);
+                send_ipi(id);
+                targets[id] = Some(traps);
+            }
+        }
+        for (id, traps) in targets.iter().enumerate() {
+            if let Some(traps) = traps {
+                while USER_SPACES[id].traps.load(Ordering::SeqCst) == *traps {
+                    spin_loop();
+                }
+            }
+        }
+    }
+
     /// Find the page table entry with given virtual page number, creating new page table entry when necessary.
-    fn create_pte(&self, vpn: VirPageNum) -> &mut PageTableEntry {
+    ///
+    /// Return `None` if the memory runs out for the new page table entries.
+    fn create_pte(&self, vpn: VirPageNum) -> Option<&mut PageTableEntry> {
         let indices = vpn.indices();
         let mut ptes = unsafe { self.root.as_raw_ptes() };
         for (i, idx) in indices.iter().enumerate() {
             let pte = &mut ptes[*idx];
             if i == 2 {
-                return pte;
+                return Some(pte);
             }
             if !pte.is_valid() {
-                let frame = NormalPageHandle::new();
+                let frame = NormalPageHandle::new()?;
                 pte.set_ppn(frame.ppn);
                 pte.set_flags(PTEFlags::V);
                 self.frames.lock().push(frame);
@@ -348,101 +596,171 @@ impl PageTable {
     pub fn translate_vpn(&self, vpn: VirPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
     }
+}
 
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_bytes(&self, ptr: VirAddr, len: usize) -> Vec<&'static mut u8> {
-        let ptr = usize::from(ptr);
-        let mut vpn = VirPageNum::from(ptr);
-        let mut result: Vec<&'static mut u8> = Vec::new();
-        while usize::from(vpn) < ptr as usize + len {
-            let ppn = self.find_pte(vpn).unwrap().get_ppn();
-            let start = ptr.saturating_sub(usize::from(vpn));
-            let end = min(ptr + len - usize::from(vpn), PAGE_SIZE);
-            unsafe {
-                ppn.as_raw_bytes()[start..end]
-                    .iter_mut()
-                    .for_each(|byte| result.push(byte));
-            }
-            vpn += 1;
+/// The error returned when the kernel fails to access user memory on behalf of the user.
+///
+/// It carries the first virtual address that could not be accessed.
+#[derive(Clone, Copy, Debug)]
+pub struct UserFault(pub usize);
+
+impl PageTable {
+    /// Find the frame behind a user page, checking that the user could access it with `flags`.
+    ///
+    /// If the page is not ready yet, e.g. not loaded or shared for copy-on-write, it's resolved as if the user faults on it.
+    /// Hence, the caller should hold neither the lock of the current process nor that of any of its tasks,
+    /// since the process is locked first.
+    /// The frame is shared with the process locked, so that it's kept even if the process exits in the meantime.
+    fn user_page(&self, vpn: VirPageNum, flags: PTEFlags) -> Result<NormalPageHandle, UserFault> {
+        let proc = Processor::curr_proc();
+        let share = || {
+            let proc_guard = proc.lock();
+            let frame = self
+                .find_pte(vpn)
+                .filter(|pte| pte.get_flags().contains(flags | PTEFlags::U | PTEFlags::V))
+                .and_then(|pte| NormalPageHandle::share(pte.get_ppn()));
+            let owned = Arc::as_ptr(&proc_guard.page_table()) == self as *const Self;
+            (frame, owned)
+        };
+        let fault = UserFault(usize::from(vpn));
+        let (frame, owned) = share();
+        if let Some(frame) = frame {
+            return Ok(frame);
         }
-        result
-    }
-
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_segment(&self, ptr: VirAddr, len: usize) -> Segment {
-        let ptr = usize::from(ptr);
-        let mut vpn = VirPageNum::from(ptr);
-        let mut result: Vec<&'static mut [u8]> = Vec::new();
-        while usize::from(vpn) < ptr + len {
-            let ppn = self.find_pte(vpn).unwrap().get_ppn();
-            let start = max(ptr - usize::from(vpn), 0);
-            let end = min(ptr + len - usize::from(vpn), PAGE_SIZE);
-            result.push(unsafe { &mut ppn.as_raw_bytes()[start..end] });
-            vpn += 1;
+
+        let access = MappingPermission::from_bits_truncate(flags.bits());
+        if owned && proc.handle_fault(vpn, access) {
+            share().0.ok_or(fault)
+        } else {
+            Err(fault)
         }
-        Segment::new(result)
     }
 
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_str(&self, ptr: VirAddr) -> String {
-        let mut ptr = usize::from(ptr);
-        let mut vpn = VirPageNum::from(ptr);
-        ptr -= usize::from(vpn);
-        let mut result = String::new();
-        loop {
-            let ppn = self.find_pte(vpn).unwrap().get_ppn();
-            let bytes = unsafe { ppn.as_raw_bytes() };
-            let mut c = bytes[ptr];
-            loop {
-                if c == b'\0' {
-                    return result;
-                }
-                if ptr == PAGE_SIZE {
-                    break;
-                }
-                result.push(c as char);
-                ptr += 1;
-                c = bytes[ptr];
-            }
-            vpn += 1;
-            ptr = 0;
+    /// Translate a user address into the physical one, checking that the user could write to it if `writable`.
+    ///
+    /// A page shared with copy-on-write would be copied first when `writable`, so that the result stays the same
+    /// until the page is unmapped.
+    pub fn translate_user(&self, ptr: VirAddr, writable: bool) -> Result<PhyAddr, UserFault> {
+        let addr = usize::from(ptr);
+        if addr >= USER_SPACE_END {
+            return Err(UserFault(addr));
+        }
+        let flags = if writable {
+            PTEFlags::R | PTEFlags::W
+        } else {
+            PTEFlags::R
+        };
+        let frame = self
+            .user_page(ptr.floor_to_vir_page_num(), flags)
+            .map_err(|_| UserFault(addr))?;
+        Ok(PhyAddr::new(frame.ppn, ptr.gen_offset()))
+    }
+
+    /// Split a piece of user memory into slices page by page, checking every page with `flags`.
+    ///
+    /// The frames are held by the segment, so the slices stay valid until it's dropped.
+    fn user_slices(&self, ptr: VirAddr, len: usize, flags: PTEFlags) -> Result<Segment, UserFault> {
+        let start = usize::from(ptr);
+        let end = start.checked_add(len).ok_or(UserFault(start))?;
+        if end > USER_SPACE_END {
+            return Err(UserFault(max(start, USER_SPACE_END)));
+        }
+
+        let mut slices = Vec::new();
+        let mut frames = Vec::new();
+        let mut curr = start;
+        while curr < end {
+            let vpn = VirPageNum::from(curr);
+            let offset = curr - usize::from(vpn);
+            let len = min(end - curr, PAGE_SIZE - offset);
+            let frame = self.user_page(vpn, flags).map_err(|_| UserFault(curr))?;
+            slices.push(unsafe { &mut frame.ppn.as_raw_bytes()[offset..offset + len] });
+            frames.push(frame);
+            curr += len;
+        }
+        Ok(Segment::new(slices, frames))
+    }
+
+    /// Copy `buf.len()` bytes from user memory starting at `ptr` into `buf`.
+    pub fn copy_from_user(&self, ptr: VirAddr, buf: &mut [u8]) -> Result<(), UserFault> {
+        let mut start = 0;
+        for src in self.user_slices(ptr, buf.len(), PTEFlags::R)?.iter() {
+            buf[start..start + src.len()].copy_from_slice(src);
+            start += src.len();
         }
+        Ok(())
     }
 
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_ptr(&self, ptr: VirAddr) -> &'static mut u8 {
-        let vpn = VirPageNum::from(ptr);
-        let ppn = self.find_pte(vpn).unwrap().get_ppn();
-        let offset = usize::from(ptr) - usize::from(vpn);
-        unsafe { &mut ppn.as_raw_bytes()[offset] }
+    /// Copy `data` into user memory starting at `ptr`.
+    pub fn copy_to_user(&self, ptr: VirAddr, data: &[u8]) -> Result<(), UserFault> {
+        let mut start = 0;
+        for dst in self.user_slices(ptr, data.len(), PTEFlags::W)?.iter_mut() {
+            let len = dst.len();
+            dst.copy_from_slice(&data[start..start + len]);
+            start += len;
+        }
+        Ok(())
     }
 
-    /// This function translate a piece of virtual memory into the type specified within just one page.
+    /// Read a value of type `T` from user memory, which is allowed to cross pages.
     ///
-    /// It doesn't support translation across pages.  
+    /// The caller should hold neither the lock of the current process nor that of its tasks, just like `user_page()`.
+    pub fn read_user<T: Copy>(&self, ptr: VirAddr) -> Result<T, UserFault> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        self.copy_from_user(ptr, bytes)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Write a value of type `T` to user memory, which is allowed to cross pages.
     ///
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_any<T>(&self, ptr: VirAddr) -> &'static mut T {
-        let size = size_of::<T>();
-        let vpn = VirPageNum::from(ptr);
-        let ppn = self.find_pte(vpn).unwrap().get_ppn();
-        let offset = usize::from(ptr) - usize::from(vpn);
-        assert!(offset + size <= PAGE_SIZE);
-        unsafe {
-            (&mut ppn.as_raw_bytes()[offset] as *mut u8 as *mut T)
-                .as_mut()
-                .unwrap()
-        }
+    /// The caller should hold neither the lock of the current process nor that of its tasks, just like `user_page()`.
+    pub fn write_user<T>(&self, ptr: VirAddr, value: &T) -> Result<(), UserFault> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        self.copy_to_user(ptr, bytes)
     }
 
-    /// Please provide a correct pointer, otherwise it will cause undefined behavior.
-    pub unsafe fn translate_array<T>(&self, ptr: VirAddr, len: usize) -> Vec<&'static mut T> {
-        let mut res = Vec::new();
-        for i in 0..len {
-            let ptr = ptr + i * size_of::<T>();
-            res.push(self.translate_any(ptr));
+    /// Read a string ended with `\0` from user memory.
+    ///
+    /// The string is bounded by `MAX_USER_STR_LEN`, and a longer one is treated as a fault.
+    pub fn read_user_cstr(&self, ptr: VirAddr) -> Result<String, UserFault> {
+        let mut result = String::new();
+        let start = usize::from(ptr);
+        let mut curr = start;
+        while curr - start < MAX_USER_STR_LEN {
+            if curr >= USER_SPACE_END {
+                return Err(UserFault(curr));
+            }
+            let vpn = VirPageNum::from(curr);
+            let frame = self
+                .user_page(vpn, PTEFlags::R)
+                .map_err(|_| UserFault(curr))?;
+            let offset = curr - usize::from(vpn);
+            let len = min(PAGE_SIZE - offset, MAX_USER_STR_LEN - (curr - start));
+            let bytes = unsafe { &frame.ppn.as_raw_bytes()[offset..offset + len] };
+            for &byte in bytes.iter() {
+                if byte == b'\0' {
+                    return Ok(result);
+                }
+                result.push(byte as char);
+            }
+            curr += len;
         }
-        res
+        Err(UserFault(curr))
+    }
+
+    /// Translate a piece of user memory into segment, which could be used for reading or writing in place.
+    pub fn user_segment(
+        &self,
+        ptr: VirAddr,
+        len: usize,
+        writable: bool,
+    ) -> Result<Segment, UserFault> {
+        let flags = if writable { PTEFlags::W } else { PTEFlags::R };
+        self.user_slices(ptr, len, flags)
     }
 }
 
@@ -458,6 +776,44 @@ impl From<MappingPermission> for PTEFlags {
     }
 }
 
+/// The user page table that a hart is running, which is zero when the hart is in the kernel.
+///
+/// The trampoline flushes the TLB whenever a hart traps or returns to user mode,
+/// so only the harts running a page table in user mode might keep its stale entries.
+#[derive(Default)]
+struct UserSpace {
+    satp: AtomicUsize,
+    /// The number of traps taken from user mode, by which `flush_tlb()` knows that the hart has trapped.
+    traps: AtomicUsize,
+    /// Whether the software interrupt is sent by `flush_tlb()` instead of the timer.
+    shootdown: AtomicBool,
+}
+
+lazy_static! {
+    static ref USER_SPACES: [UserSpace; CPUS] = Default::default();
+}
+
+/// Record that the current hart is returning to user mode with the page table `satp`.
+pub fn enter_user(satp: usize) {
+    USER_SPACES[Processor::hart_id()]
+        .satp
+        .store(satp, Ordering::SeqCst);
+}
+
+/// Record that the current hart has trapped from user mode.
+pub fn leave_user() {
+    let space = &USER_SPACES[Processor::hart_id()];
+    space.satp.store(0, Ordering::SeqCst);
+    space.traps.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Clear the request of TLB shootdown to the current hart, returning whether there was one.
+pub fn take_shootdown() -> bool {
+    USER_SPACES[Processor::hart_id()]
+        .shootdown
+        .swap(false, Ordering::SeqCst)
+}
+
 pub fn activate_page_table() {
     assert_ne!(KERNEL_MEMSET.len(), 0);
     let satp = KERNEL_PAGE_TABLE.to_satp();
@@ -503,5 +859,6 @@ impl PageTableEntry {
 }
 
 lazy_static! {
-    pub static ref KERNEL_PAGE_TABLE: Arc<PageTable> = Arc::new(PageTable::new());
+    pub static ref KERNEL_PAGE_TABLE: Arc<PageTable> =
+        Arc::new(PageTable::new().expect("[page_table] Fail to create the kernel page table."));
 }
@@ -47,6 +47,16 @@ impl<T: Clone> AllocTable<T> {
         }
     }
 
+    /// Create a table with the same ids, whose entries are converted by `f`.
+    pub fn map<U: Clone>(&self, f: impl Fn(&T) -> U) -> AllocTable<U> {
+        AllocTable::new(
+            self.entries
+                .iter()
+                .map(|entry| entry.as_ref().map(&f))
+                .collect(),
+        )
+    }
+
     pub fn len(&self) -> usize {
         self.entries.len() - self.entries.iter().filter(|x| x.is_none()).count()
     }
@@ -0,0 +1,321 @@
+use alloc::{format, sync::Arc, vec, vec::Vec};
+use core::{cmp::min, mem::size_of};
+
+use fosix::{fs::OpenFlags, time::TimeSpec};
+
+use crate::{
+    config::PAGE_SIZE,
+    mem::normal::page::NormalPageHandle,
+    mm::{area::Area, memory::MappingPermission},
+    task::{task::Task, time::CpuTimes},
+};
+
+use super::proc::Proc;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+
+#[repr(C)]
+struct ElfHeader {
+    ident: [u8; 16],
+    ty: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[repr(C)]
+struct ProgramHeader {
+    ty: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+/// The status of a thread, which is laid out like the `elf_prstatus` of Linux on riscv64.
+#[repr(C)]
+struct PrStatus {
+    signo: i32,
+    code: i32,
+    errno: i32,
+    cursig: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    utime: [u64; 2],
+    stime: [u64; 2],
+    cutime: [u64; 2],
+    cstime: [u64; 2],
+    /// The pc followed by x1 to x31.
+    regs: [u64; 32],
+    fpvalid: i32,
+}
+
+/// The information of a process, which is laid out like the `elf_prpsinfo` of Linux on riscv64.
+#[repr(C)]
+struct PrPsInfo {
+    state: u8,
+    sname: u8,
+    zomb: u8,
+    nice: i8,
+    flag: u64,
+    uid: u32,
+    gid: u32,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    fname: [u8; 16],
+    psargs: [u8; 80],
+}
+
+/// A piece of user memory to dump, whose pages that are not allocated yet are dumped as zero.
+struct Segment {
+    start: usize,
+    perm: MappingPermission,
+    frames: Vec<Option<NormalPageHandle>>,
+}
+
+/// Write the core of the process terminated by `sigid` into `core.<pid>` under its working directory,
+/// returning whether it's written.
+///
+/// The core is an ELF file with a note for every thread and a loadable segment for every readable area,
+/// where `task` comes first as the crashing thread.
+/// The memory is snapshotted with the lock held, but the file is written without holding it.
+pub fn dump_core(proc: &Arc<Proc>, task: &Arc<Task>, sigid: usize) -> bool {
+    let mut notes = Vec::new();
+    let mut segments = Vec::new();
+    let (cwd, name) = {
+        let proc_guard = proc.lock();
+        let pid = proc.pid() as i32;
+        let ppid = proc_guard.parent().map_or(0, |parent| parent.pid()) as i32;
+        let (pgrp, sid) = (proc_guard.pgid as i32, proc_guard.sid as i32);
+
+        let mut tasks = vec![task.clone()];
+        tasks.extend(
+            proc_guard
+                .tasks
+                .iter()
+                .filter(|other| !Arc::ptr_eq(other, task))
+                .cloned(),
+        );
+        for task in tasks.iter() {
+            let task_guard = task.lock();
+            let trap_ctx = task_guard.trap_ctx();
+            let mut regs = [0; 32];
+            regs[0] = trap_ctx.user_sepc as u64;
+            for (reg, saved) in regs.iter_mut().zip(trap_ctx.saved_regs).skip(1) {
+                *reg = saved as u64;
+            }
+            let times = task_guard.task_time.times();
+            let status = PrStatus {
+                signo: sigid as i32,
+                code: 0,
+                errno: 0,
+                cursig: sigid as i16,
+                sigpend: (task_guard.sigs | proc_guard.sigs).bits() as u64,
+                sighold: task_guard.sig_mask.bits() as u64,
+                pid: task_guard.tid() as i32,
+                ppid,
+                pgrp,
+                sid,
+                utime: timeval(&times, true),
+                stime: timeval(&times, false),
+                cutime: timeval(&proc_guard.dead_times, true),
+                cstime: timeval(&proc_guard.dead_times, false),
+                regs,
+                fpvalid: 0,
+            };
+            push_note(&mut notes, NT_PRSTATUS, as_bytes(&status));
+            segments.push(Segment::new(task_guard.user_stack.area()));
+        }
+
+        let mut info = PrPsInfo {
+            state: 0,
+            sname: b'R',
+            zomb: 0,
+            nice: proc_guard.niceness() as i8,
+            flag: 0,
+            uid: 0,
+            gid: 0,
+            pid,
+            ppid,
+            pgrp,
+            sid,
+            fname: [0; 16],
+            psargs: [0; 80],
+        };
+        let cmdline = proc_guard.cmdline.as_bytes();
+        let fname = cmdline.split(|&c| c == b' ').next().unwrap_or(&[]);
+        let fname = fname.rsplit(|&c| c == b'/').next().unwrap_or(&[]);
+        let len = min(fname.len(), info.fname.len() - 1);
+        info.fname[..len].copy_from_slice(&fname[..len]);
+        let len = min(cmdline.len(), info.psargs.len() - 1);
+        info.psargs[..len].copy_from_slice(&cmdline[..len]);
+        push_note(&mut notes, NT_PRPSINFO, as_bytes(&info));
+
+        segments.extend(
+            proc_guard
+                .user_mem()
+                .areas()
+                .iter()
+                .filter(|area| {
+                    area.map_perm()
+                        .contains(MappingPermission::R | MappingPermission::U)
+                })
+                .map(Segment::new),
+        );
+        (proc_guard.cwd(), format!("core.{}", pid))
+    };
+    segments.sort_by_key(|segment| segment.start);
+
+    let file = match cwd.lock().open(
+        &name,
+        OpenFlags::WRONLY | OpenFlags::CREATE | OpenFlags::TRUNC,
+    ) {
+        Some(file) => file,
+        None => return false,
+    };
+    let file_guard = file.lock();
+
+    // the segments start from the first page after the headers and the notes
+    let phnum = segments.len() + 1;
+    let notes_offset = size_of::<ElfHeader>() + phnum * size_of::<ProgramHeader>();
+    let mut offset = (notes_offset + notes.len() + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+
+    let mut headers = Vec::new();
+    let mut ident = [0; 16];
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    let elf_header = ElfHeader {
+        ident,
+        ty: ET_CORE,
+        machine: EM_RISCV,
+        version: 1,
+        entry: 0,
+        phoff: size_of::<ElfHeader>() as u64,
+        shoff: 0,
+        flags: 0,
+        ehsize: size_of::<ElfHeader>() as u16,
+        phentsize: size_of::<ProgramHeader>() as u16,
+        phnum: phnum as u16,
+        shentsize: 0,
+        shnum: 0,
+        shstrndx: 0,
+    };
+    headers.extend_from_slice(as_bytes(&elf_header));
+    let note_header = ProgramHeader {
+        ty: PT_NOTE,
+        flags: 0,
+        offset: notes_offset as u64,
+        vaddr: 0,
+        paddr: 0,
+        filesz: notes.len() as u64,
+        memsz: 0,
+        align: 4,
+    };
+    headers.extend_from_slice(as_bytes(&note_header));
+    for segment in segments.iter() {
+        let size = (segment.frames.len() * PAGE_SIZE) as u64;
+        let load_header = ProgramHeader {
+            ty: PT_LOAD,
+            flags: segment.flags(),
+            offset: offset as u64,
+            vaddr: segment.start as u64,
+            paddr: 0,
+            filesz: size,
+            memsz: size,
+            align: PAGE_SIZE as u64,
+        };
+        headers.extend_from_slice(as_bytes(&load_header));
+        offset += size as usize;
+    }
+    headers.extend_from_slice(&notes);
+    if file_guard.write_at(&headers, 0) != headers.len() {
+        return false;
+    }
+
+    let zero = vec![0; PAGE_SIZE];
+    let mut offset = (headers.len() + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    for segment in segments.iter() {
+        for frame in segment.frames.iter() {
+            let page = match frame {
+                Some(frame) => unsafe { &*frame.ppn.as_raw_bytes() },
+                None => &zero[..],
+            };
+            if file_guard.write_at(page, offset) != PAGE_SIZE {
+                return false;
+            }
+            offset += PAGE_SIZE;
+        }
+    }
+    true
+}
+
+impl Segment {
+    fn new(area: &Area) -> Self {
+        Self {
+            start: usize::from(area.range().start),
+            perm: area.map_perm(),
+            frames: area.frames().clone(),
+        }
+    }
+
+    fn flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.perm.contains(MappingPermission::R) {
+            flags |= PF_R;
+        }
+        if self.perm.contains(MappingPermission::W) {
+            flags |= PF_W;
+        }
+        if self.perm.contains(MappingPermission::X) {
+            flags |= PF_X;
+        }
+        flags
+    }
+}
+
+/// Append a note owned by "CORE", where the name and the description are both padded to 4 bytes.
+fn push_note(notes: &mut Vec<u8>, ty: u32, desc: &[u8]) {
+    let name = b"CORE\0\0\0\0";
+    notes.extend_from_slice(&5u32.to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&ty.to_le_bytes());
+    notes.extend_from_slice(name);
+    notes.extend_from_slice(desc);
+    notes.resize((notes.len() + 3) / 4 * 4, 0);
+}
+
+/// Convert the user or system time into the `timeval` of Linux.
+fn timeval(times: &CpuTimes, user: bool) -> [u64; 2] {
+    let time = times.to_rusage();
+    let time: TimeSpec = if user { time.utime } else { time.stime };
+    [time.sec as u64, (time.nsec / 1000) as u64]
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
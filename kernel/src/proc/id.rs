@@ -43,6 +43,18 @@ impl IdAllocatorInner {
     pub fn dealloc(&mut self, id: usize) {
         self.recycled.push(id);
     }
+
+    /// Allocate the given id, which fails if it's in use.
+    pub fn alloc_at(&mut self, id: usize) -> Option<usize> {
+        if id >= self.next_id {
+            self.recycled.extend(self.next_id..id);
+            self.next_id = id + 1;
+            Some(id)
+        } else {
+            let pos = self.recycled.iter().position(|&recycled| recycled == id)?;
+            Some(self.recycled.swap_remove(pos))
+        }
+    }
 }
 
 impl IdAllocator {
@@ -56,6 +68,11 @@ impl IdAllocator {
         Id::new(self.inner.lock().alloc(), self.clone())
     }
 
+    pub fn alloc_at(self: &Arc<Self>, id: usize) -> Option<Arc<Id>> {
+        let id = self.inner.lock().alloc_at(id)?;
+        Some(Id::new(id, self.clone()))
+    }
+
     fn dealloc(&self, id: &Id) {
         self.inner.lock().dealloc(id.id)
     }
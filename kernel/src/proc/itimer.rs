@@ -0,0 +1,76 @@
+use fosix::time::{ITimerVal, TimeSpec};
+
+use crate::time::{get_time, ns_to_ticks, ticks_to_ns};
+
+/// An interval timer of a process, counted in CLINT ticks.
+///
+/// For the real timer, `value` is the deadline, otherwise it's the remaining runtime of the process.
+/// The timer is disarmed if `value` is zero.
+#[derive(Clone, Copy)]
+pub struct ITimer {
+    interval: usize,
+    value: usize,
+}
+
+impl ITimer {
+    pub const fn disarmed() -> Self {
+        Self {
+            interval: 0,
+            value: 0,
+        }
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn set(&mut self, interval: usize, value: usize) {
+        self.interval = interval;
+        self.value = value;
+    }
+
+    /// Consume `ticks` of the runtime, returning whether the timer fires.
+    ///
+    /// The timer would be reloaded with the interval after firing, or disarmed if there is no interval.
+    pub fn consume(&mut self, ticks: usize) -> bool {
+        if self.value == 0 {
+            return false;
+        }
+        if ticks < self.value {
+            self.value -= ticks;
+            false
+        } else {
+            self.value = self.interval;
+            true
+        }
+    }
+
+    /// Convert the timer into the form of user, where `is_real` indicates that `value` is a deadline.
+    pub fn to_user(&self, is_real: bool) -> ITimerVal {
+        let value = if is_real {
+            self.value.saturating_sub(get_time())
+        } else {
+            self.value
+        };
+        ITimerVal::new(
+            TimeSpec::from_ns(ticks_to_ns(self.interval)),
+            TimeSpec::from_ns(ticks_to_ns(value)),
+        )
+    }
+
+    /// Convert the setting of user into a timer, where `is_real` indicates that `value` should be a deadline.
+    pub fn from_user(val: &ITimerVal, is_real: bool) -> Self {
+        let mut value = ns_to_ticks(val.value.as_ns());
+        if is_real && value != 0 {
+            value = value.saturating_add(get_time());
+        }
+        Self {
+            interval: ns_to_ticks(val.interval.as_ns()),
+            value,
+        }
+    }
+}
@@ -26,4 +26,12 @@ impl Lockable {
             Lockable::BlockMutex(mutex) => mutex.is_locked(),
         }
     }
+
+    /// Create a new mutex of the same kind in the same state.
+    pub fn duplicate(&self) -> Self {
+        match self {
+            Lockable::SpinMutex(mutex) => Lockable::SpinMutex(mutex.duplicate()),
+            Lockable::BlockMutex(mutex) => Lockable::BlockMutex(mutex.duplicate()),
+        }
+    }
 }
@@ -5,8 +5,12 @@ use super::proc::Proc;
 use alloc::{
     collections::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
+};
+use fosix::{
+    fs::OpenFlags,
+    signal::{SigInfo, SignalFlags},
 };
-use fosix::fs::OpenFlags;
 use lazy_static::lazy_static;
 
 pub struct ProcManager {
@@ -43,6 +47,37 @@ impl ProcManager {
     pub fn get(&self, key: usize) -> Option<Arc<Proc>> {
         self.procs.lock().get(&key).and_then(|proc| proc.upgrade())
     }
+
+    pub fn all(&self) -> Vec<Arc<Proc>> {
+        self.procs
+            .lock()
+            .values()
+            .filter_map(|proc| proc.upgrade())
+            .collect()
+    }
+
+    /// Get the processes in the group `pgid`.
+    pub fn group(&self, pgid: usize) -> Vec<Arc<Proc>> {
+        // the processes are locked after the manager is unlocked
+        self.all()
+            .into_iter()
+            .filter(|proc| proc.lock().pgid == pgid)
+            .collect()
+    }
+
+    /// Send `sig` to every process in the group `pgid`, returning whether there is any.
+    pub fn kill_group(&self, pgid: usize, sig: SignalFlags) -> bool {
+        self.send_group(pgid, sig, SigInfo::kernel(sig.bits().trailing_zeros()))
+    }
+
+    /// Send `sig` with the information of it to every process in the group `pgid`, just like `kill_group()`.
+    pub fn send_group(&self, pgid: usize, sig: SignalFlags, info: SigInfo) -> bool {
+        let group = self.group(pgid);
+        for proc in group.iter() {
+            proc.send(sig, info);
+        }
+        !group.is_empty()
+    }
 }
 
 lazy_static! {
@@ -1,6 +1,9 @@
 pub mod alloc_table;
+pub mod coredump;
 pub mod id;
+pub mod itimer;
 pub mod lockable;
 pub mod manager;
+pub mod oom;
 pub mod proc;
 pub mod stack;
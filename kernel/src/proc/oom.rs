@@ -0,0 +1,76 @@
+//! The out-of-memory killer, which reclaims memory when an allocation could not be done for lack of frames.
+//!
+//! The page faults and the system calls that allocate frames for user call it before they fail with `ENOMEM`.
+//! The kernel heap could not call it when it grows, since the heap might be used with any lock held,
+//! so a kill is requested by `request_oom_kill()` instead, which is done on the next trap by `handle_oom_request()`.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use fosix::signal::SIGKILL;
+
+use super::{
+    manager::{INITPROC, PROC_MANAGER},
+    proc::{Proc, ProcStatus},
+};
+use crate::task::processor::Processor;
+
+/// Whether the kernel heap has asked for a kill.
+static OOM_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Kill the process that takes the most frames of user memory, except the initial process and those exiting.
+///
+/// The victim exits at once as if it's killed by `SIGKILL`, even if its threads are blocked,
+/// so its frames have been released when this returns.
+/// The caller should hold no lock, and the current process might be the victim.
+/// Return the victim, or `None` if there's no process that could be killed.
+pub fn oom_kill() -> Option<Arc<Proc>> {
+    let (frames, victim) = PROC_MANAGER
+        .all()
+        .into_iter()
+        .filter(|proc| proc.pid() != INITPROC.pid())
+        .filter_map(|proc| {
+            let proc_guard = proc.lock();
+            if proc_guard.proc_status == ProcStatus::Zombie {
+                return None;
+            }
+            let frames = proc_guard.user_mem().frames();
+            drop(proc_guard);
+            Some((frames, proc))
+        })
+        .filter(|(frames, _)| *frames > 0)
+        .max_by_key(|(frames, _)| *frames)?;
+    warnln!(
+        "Out of memory, kill process {} with {} frames.",
+        victim.pid(),
+        frames
+    );
+    victim.lock().term_sig.get_or_insert(SIGKILL as usize);
+    victim.exit(-2);
+    Some(victim)
+}
+
+/// Kill a process by `oom_kill()` after an allocation of the current process fails,
+/// returning whether the allocation should be tried again.
+///
+/// It's given up if there's no process to kill or the current process is the victim,
+/// where the caller should fail with `ENOMEM`.
+pub fn reclaim() -> bool {
+    match oom_kill() {
+        Some(victim) => victim.pid() != Processor::curr_proc().pid(),
+        None => false,
+    }
+}
+
+/// Ask for a kill by `handle_oom_request()`, which could be done with any lock held.
+pub fn request_oom_kill() {
+    OOM_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Do the kill asked by `request_oom_kill()` if any, which should be done with no lock held.
+///
+/// The current process might be the victim, which should exit before it returns to user.
+pub fn handle_oom_request() {
+    if OOM_REQUESTED.swap(false, Ordering::Relaxed) {
+        oom_kill();
+    }
+}
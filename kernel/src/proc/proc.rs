@@ -5,27 +5,56 @@ use alloc::{
 };
 use simplefs::{dir::Dir, file::File};
 
-use core::mem::size_of;
-use fosix::signal::{SignalAction, SignalFlags};
+use core::{
+    cmp::{max, min},
+    mem::{self, size_of},
+};
+use fosix::{
+    auxv::{AT_ENTRY, AT_HARTS, AT_NULL, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_RANDOM},
+    errno::{ENOEXEC, ENOMEM},
+    signal::{
+        SigInfo, SignalAction, SignalFlags, CLD_EXITED, CLD_KILLED, CLD_STOPPED, SIGCHLD, SIG_DFL,
+        SIG_IGN,
+    },
+    syscall::{CloneFlags, WaitStatus, ADDR_NO_RANDOMIZE},
+    time::{ITIMER_PROF, ITIMER_REAL, ITIMER_VIRTUAL},
+};
 use spin::{Spin, SpinGuard};
 
 use crate::{
-    config::NUM_SIGNAL,
+    config::{
+        CPUS, NUM_SIGNAL, PAGE_SIZE, USER_ASLR_PAGES, USER_HEAP_LIMIT, USER_IMAGE_END,
+        USER_PIE_BASE,
+    },
     drivers::blockdev::BlkDev,
     fs::fileable::Fileable,
     io::{stdin::Stdin, stdout::Stdout},
-    mm::{address::VirAddr, memory::MemSet, page_table::PageTable},
+    mm::{
+        address::{VirAddr, VirPageNum},
+        area::AreaFault,
+        memory::{MappingPermission, MemSet},
+        page_table::{PageTable, UserImage},
+    },
     proc::{
         id::{GID_ALLOCATOR, PID_ALLOCATOR},
         manager::{INITPROC, PROC_MANAGER},
+        oom::oom_kill,
     },
-    sync::{observable::Observable, semaphore::Semaphore},
-    task::task::Task,
+    random::fill_random,
+    sync::{observable::Observable, semaphore::Semaphore, waiting_queue::WaitingQueue},
+    task::{
+        processor::Processor,
+        task::{Task, TaskStatus},
+        time::CpuTimes,
+        timer::TIMER,
+    },
+    trap::signal::{default_action, DefaultAction},
 };
 
 use super::{
     alloc_table::AllocTable,
     id::{Id, IdAllocator},
+    itimer::ITimer,
     lockable::Lockable,
 };
 
@@ -41,16 +70,46 @@ pub struct ProcInner {
     pub proc_status: ProcStatus,
     parent: Option<Weak<Proc>>,
     pub children: Vec<Arc<Proc>>,
+    /// The process group, which is inherited by `fork()` and changed by `setpgid()` or `setsid()`.
+    pub pgid: usize,
+    /// The session, which contains the process groups that share the console.
+    pub sid: usize,
     pub tasks: Vec<Arc<Task>>,
-    pub fd_table: AllocTable<Fileable>,
+    /// The opened files, which are shared with the processes cloned with `CloneFlags::FILES`.
+    pub fd_table: Arc<Spin<AllocTable<Fileable>>>,
     pub exit_code: isize,
-    cwd: Dir<BlkDev>,
+    /// The signal that has terminated the process, which overrides `exit_code`.
+    pub term_sig: Option<usize>,
+    /// Whether the core has been dumped when the process is terminated by `term_sig`.
+    pub core_dumped: bool,
+    /// The signal that has stopped the process, which is taken once it's reported by `waitpid()`.
+    pub stop_sig: Option<usize>,
+    /// The tasks waiting in `waitpid()` or `waittid()` for the children or threads to change state.
+    pub exit_waiters: WaitingQueue,
+    /// The CPU times of the threads that have been waited for or exited and of the children that have been waited for.
+    pub dead_times: CpuTimes,
+    /// The working directory, which is shared with the processes cloned with `CloneFlags::FS`.
+    cwd: Arc<Spin<Dir<BlkDev>>>,
+    /// The arguments of the program that the process executes, which are joined by spaces.
+    pub cmdline: String,
     pub sig_actions: [SignalAction; NUM_SIGNAL],
+    /// The process-directed signals that are pending since every thread masks them.
+    pub sigs: SignalFlags,
+    /// The information of the process-directed signals, indexed by the signal.
+    pub sig_infos: [SigInfo; NUM_SIGNAL],
     base: VirAddr,
-    pub lock_table: AllocTable<Arc<Lockable>>,
-    pub sema_table: AllocTable<Arc<Semaphore>>,
-    pub condvar_table: AllocTable<Arc<Observable>>,
+    heap_base: usize,
+    /// The end of the heap, which is adjusted by `brk()`.
+    brk: usize,
+    /// The personality set by `personality()`, which is inherited by `fork()` and kept across `exec()`.
+    pub personality: usize,
+    /// The mutexes, which are shared with the processes cloned with `CloneFlags::SYSVSEM` like the other sync tables.
+    pub lock_table: Arc<Spin<AllocTable<Arc<Lockable>>>>,
+    pub sema_table: Arc<Spin<AllocTable<Arc<Semaphore>>>>,
+    pub condvar_table: Arc<Spin<AllocTable<Arc<Observable>>>>,
     niceness: isize,
+    /// The interval timers indexed by `ITIMER_*`, which are kept across `exec()` but not inherited by `fork()`.
+    pub itimers: [ITimer; 3],
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -62,45 +121,64 @@ pub enum ProcStatus {
 impl Proc {
     /// Create a new task from elf data.
     pub fn from_elf(file: File<BlkDev>, parent: Option<Weak<Proc>>, niceness: isize) -> Arc<Self> {
-        let file_size = file.lock().size();
-        let mut elf_data = vec![0u8; file_size];
-        assert_eq!(file.lock().read_at(&mut elf_data, 0), file_size);
+        let elf_data = read_elf_headers(&file).expect("[proc] Invalid ELF file.");
 
-        let page_table = Arc::new(PageTable::new());
-        let (base, user_sepc, user_mem) = page_table.new_user(&elf_data);
+        let page_table = Arc::new(PageTable::new().expect("[proc] Fail to create the page table."));
+        let image = page_table
+            .new_user(&file, &elf_data, true)
+            .expect("[proc] Invalid ELF file.");
         let tid_allocator = Arc::new(IdAllocator::new());
 
+        // the first process leads its own session and group
+        let pid = PID_ALLOCATOR.alloc();
+        let pgid = pid.id();
         let res = Arc::new(Self {
-            pid: PID_ALLOCATOR.alloc(),
+            pid,
             inner: Spin::new(ProcInner {
                 tid_allocator: tid_allocator.clone(),
-                user_mem,
+                user_mem: image.mem,
                 page_table: page_table.clone(),
                 proc_status: ProcStatus::Running,
                 parent,
                 children: Vec::new(),
+                pgid,
+                sid: pgid,
                 tasks: Vec::new(),
                 exit_code: 0,
-                fd_table: Proc::new_fd_table(),
-                cwd: file.lock().parent(),
+                term_sig: None,
+                core_dumped: false,
+                stop_sig: None,
+                exit_waiters: WaitingQueue::new(),
+                dead_times: CpuTimes::default(),
+                fd_table: Arc::new(Spin::new(Proc::new_fd_table())),
+                cwd: Arc::new(Spin::new(file.lock().parent())),
+                cmdline: String::new(),
                 sig_actions: [SignalAction::default(); NUM_SIGNAL],
-                base,
-                lock_table: AllocTable::default(),
-                sema_table: AllocTable::default(),
-                condvar_table: AllocTable::default(),
+                sigs: SignalFlags::empty(),
+                sig_infos: [SigInfo::default(); NUM_SIGNAL],
+                base: image.base,
+                heap_base: image.heap_base,
+                brk: image.heap_base,
+                personality: 0,
+                lock_table: Arc::new(Spin::new(AllocTable::default())),
+                sema_table: Arc::new(Spin::new(AllocTable::default())),
+                condvar_table: Arc::new(Spin::new(AllocTable::default())),
                 niceness,
+                itimers: [ITimer::disarmed(); 3],
             }),
         });
 
         let task = Task::new(
             tid_allocator.alloc(),
             GID_ALLOCATOR.alloc(),
-            base,
-            user_sepc,
+            image.base,
+            image.entry,
             Arc::downgrade(&res),
             page_table,
             res.lock().weight(),
-        );
+        )
+        .expect("[proc] Fail to create the main thread.");
+        *task.lock().trap_ctx_mut().tp_mut() = image.tp;
         res.lock().tasks.push(task);
 
         res
@@ -118,104 +196,203 @@ impl Proc {
         self.pid.id()
     }
 
-    /// Fork a new task from an existing task with both of the return values unchanged.  
+    /// Fork a new process that contains only a copy of `task` with both of the return values unchanged.
     ///
-    /// The difference between this function and clone is that it maintains the parent-child relationship.
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
-        let new_proc = self.renew();
+    /// The resources selected by `flags` are shared with the new process, while the others are copied.
+    /// The difference between this function and renew is that it maintains the parent-child relationship.
+    /// Return `None` if the memory runs out.
+    pub fn fork(self: &Arc<Self>, task: &Arc<Task>, flags: CloneFlags) -> Option<Arc<Self>> {
+        let new_proc = self.renew(task, flags)?;
 
         // make new process the original's children
         self.lock().children.push(new_proc.clone());
         *new_proc.lock().parent_mut() = Some(Arc::downgrade(&self));
 
-        new_proc
+        Some(new_proc)
     }
 
     /// Replace the current task with new elf data. Therefore, all user configurations would be reset.
-    pub fn exec(self: &Arc<Self>, file: File<BlkDev>, args: &Vec<String>) {
-        let file_size = file.lock().size();
-        let mut elf_data = vec![0u8; file_size];
-        assert_eq!(file.lock().read_at(&mut elf_data, 0), file_size);
-
-        let mut proc = self.lock();
-        let page_table = Arc::new(PageTable::new());
-        let (base, user_sepc, user_mem) = page_table.new_user(&elf_data);
+    ///
+    /// `task` becomes the only thread of the process, while the other threads exit.
+    /// The new user stack is laid out like Linux, where `sp` points to argc, followed by argv, envp and the auxiliary vector,
+    /// while argc, argv and envp are passed by `a0` to `a2` as well.
+    ///
+    /// The layout of the new address space is randomized unless the personality contains `ADDR_NO_RANDOMIZE`.
+    /// Return `-ENOEXEC` if `file` is not a valid executable, or `-ENOMEM` if the memory runs out,
+    /// in which case nothing is changed.
+    pub fn exec(
+        self: &Arc<Self>,
+        task: &Arc<Task>,
+        file: &File<BlkDev>,
+        args: &Vec<String>,
+        envs: &Vec<String>,
+    ) -> Result<(), isize> {
+        let elf_data = read_elf_headers(file)?;
+        let randomize = self.lock().personality & ADDR_NO_RANDOMIZE == 0;
+
+        // the relocations are read from the file, which should not hold the lock
+        let page_table = Arc::new(PageTable::new().ok_or(-ENOMEM)?);
+        let image = page_table.new_user(file, &elf_data, randomize)?;
         let tid_allocator = Arc::new(IdAllocator::new());
-        let task = proc.main_task();
-        task.exec(tid_allocator.alloc(), base, user_sepc, page_table.clone());
-        let mut user_sp: usize = task.lock().user_stack.top().into();
-
-        // push args
-        let mut acc = 0;
-        for i in (0..=args.len()).rev() {
-            let ptr = if i == args.len() {
-                0
-            } else {
-                user_sp - (args.len() + 1) * size_of::<usize>() - acc - args[i].len()
-            };
-            let offset = (args.len() + 1 - i) * size_of::<usize>();
-            let src_bytes = ptr.to_ne_bytes();
-            let mut dst_bytes =
-                unsafe { page_table.translate_bytes((user_sp - offset).into(), src_bytes.len()) };
-            dst_bytes
-                .iter_mut()
-                .enumerate()
-                .for_each(|(i, byte)| **byte = src_bytes[i]);
-
-            if i != args.len() {
-                acc += args[i].len();
-            }
-        }
-        user_sp -= (args.len() + 1) * size_of::<usize>();
-        let argv = user_sp;
-
-        for arg in args.iter().rev() {
-            let src_bytes = arg.as_bytes();
-            user_sp -= src_bytes.len();
-            let mut dst_bytes =
-                unsafe { page_table.translate_bytes(user_sp.into(), src_bytes.len()) };
-            dst_bytes
-                .iter_mut()
-                .enumerate()
-                .for_each(|(i, byte)| **byte = src_bytes[i]);
+        let tid = tid_allocator.alloc();
+        let mut user_stack = page_table.new_user_stack(image.base, tid.id());
+        let stack_top: usize = user_stack.top().into();
+
+        // the strings and the random bytes lie at the top, below which are the pointers aligned to 16 bytes
+        let aux_len = aux_vector(&elf_data, &image, 0).len();
+        let words = 1 + (args.len() + 1) + (envs.len() + 1) + (aux_len + 1) * 2;
+        let strs_size = args
+            .iter()
+            .chain(envs.iter())
+            .map(|str| str.len())
+            .sum::<usize>();
+        let strs_bottom = stack_top - strs_size - RANDOM_SIZE;
+        let user_sp = (strs_bottom - words * size_of::<usize>()) & !0xf;
+        user_stack
+            .area_mut()
+            .populate(
+                VirAddr::from(user_sp).floor_to_vir_page_num(),
+                VirAddr::from(stack_top).ceil_to_vir_page_num(),
+            )
+            .ok_or(-ENOMEM)?;
+
+        let mut strs_sp = stack_top;
+        let mut push = |bytes: &[u8]| {
+            strs_sp -= bytes.len();
+            page_table
+                .copy_to_user(strs_sp.into(), bytes)
+                .expect("[proc] Fail to push args onto the new user stack.");
+            strs_sp
+        };
+        let mut random = [0; RANDOM_SIZE];
+        fill_random(&mut random);
+        let random_ptr = push(&random);
+        let arg_ptrs: Vec<usize> = args.iter().map(|arg| push(arg.as_bytes())).collect();
+        let env_ptrs: Vec<usize> = envs.iter().map(|env| push(env.as_bytes())).collect();
+
+        let mut ptrs = vec![args.len()];
+        ptrs.extend(arg_ptrs);
+        ptrs.push(0);
+        ptrs.extend(env_ptrs);
+        ptrs.push(0);
+        for (key, value) in aux_vector(&elf_data, &image, random_ptr) {
+            ptrs.push(key);
+            ptrs.push(value);
         }
+        ptrs.push(AT_NULL);
+        ptrs.push(0);
+        page_table
+            .copy_to_user(user_sp.into(), unsafe {
+                core::slice::from_raw_parts(
+                    ptrs.as_ptr() as *const u8,
+                    ptrs.len() * size_of::<usize>(),
+                )
+            })
+            .expect("[proc] Fail to push args onto the new user stack.");
+
+        // the trap context is the last to allocate, so that nothing is changed if the memory runs out
+        let mut proc = self.lock();
+        let task = task.clone();
+        task.exec(tid, user_stack, image.entry, page_table.clone())
+            .ok_or(-ENOMEM)?;
 
         // replace some
+        let argv = user_sp + size_of::<usize>();
+        let envp = argv + (args.len() + 1) * size_of::<usize>();
         *task.lock().trap_ctx_mut().a0_mut() = args.len();
         *task.lock().trap_ctx_mut().a1_mut() = argv;
-        *task.lock().trap_ctx_mut().user_sp_mut() = user_sp.into();
-
-        proc.page_table = page_table;
-        proc.base = base;
-        proc.user_mem = user_mem;
+        *task.lock().trap_ctx_mut().a2_mut() = envp;
+        *task.lock().trap_ctx_mut().user_sp_mut() = user_sp;
+        *task.lock().trap_ctx_mut().tp_mut() = image.tp;
+
+        // the caught signals are reset since the handlers are gone, while the ignored ones are kept
+        for action in proc.sig_actions.iter_mut() {
+            if action.handler() != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        proc.cmdline = args
+            .iter()
+            .map(|arg| arg.trim_end_matches('\0'))
+            .collect::<Vec<&str>>()
+            .join(" ");
+        proc.base = image.base;
+        proc.heap_base = image.heap_base;
+        proc.brk = image.heap_base;
+        let old_mem = mem::replace(&mut proc.user_mem, image.mem);
+        let old_page_table = mem::replace(&mut proc.page_table, page_table);
         proc.tid_allocator = tid_allocator;
-        proc.tasks = vec![task];
+        for other in mem::replace(&mut proc.tasks, vec![task.clone()]) {
+            if !Arc::ptr_eq(&other, &task) {
+                other.lock().task_status = TaskStatus::Zombie;
+            }
+        }
+        // the other threads running on other harts should trap before the frames are released
+        old_page_table.flush_tlb();
+
+        // shared file mappings are written back when dropped, which should not hold the lock
+        drop(proc);
+        drop(old_mem);
+        Ok(())
     }
 
-    /// Create a new thread that starts from `entry` with `arg` as its argument.
-    pub fn new_task(self: &Arc<Self>, entry: VirAddr, arg: usize) -> Arc<Task> {
+    /// Create a new thread that continues from where `task` traps with the same registers except its own stack.
+    ///
+    /// The signal mask of `task` is inherited, while its pending signals are not.
+    /// Return `None` if the memory runs out.
+    pub fn new_task(self: &Arc<Self>, task: &Arc<Task>) -> Option<Arc<Task>> {
         let mut proc = self.lock();
-        let task = Task::new(
+        let (trap_ctx, sig_mask) = {
+            let task_guard = task.lock();
+            (task_guard.trap_ctx().clone(), task_guard.sig_mask)
+        };
+        let new_task = Task::new(
             proc.tid_allocator.alloc(),
             GID_ALLOCATOR.alloc(),
             proc.base,
-            entry,
+            trap_ctx.user_sepc.into(),
             Arc::downgrade(self),
             proc.page_table.clone(),
             proc.weight(),
-        );
-        *task.lock().trap_ctx_mut().a0_mut() = arg;
-        proc.tasks.push(task.clone());
-        task
+        )?;
+        {
+            let mut task_guard = new_task.lock();
+            let new_ctx = task_guard.trap_ctx_mut();
+            let user_sp = new_ctx.user_sp();
+            new_ctx.saved_regs = trap_ctx.saved_regs;
+            *new_ctx.user_sp_mut() = user_sp;
+            task_guard.sig_mask = sig_mask;
+        }
+        proc.tasks.push(new_task.clone());
+        Some(new_task)
     }
 
+    /// Exit the process with all its threads, which is reported to the parent by `waitpid()`.
+    ///
+    /// The other threads are never scheduled again, while those running on other harts exit on their next trap.
     pub fn exit(&self, exit_code: isize) {
         let mut proc = self.lock();
         let pid = self.pid();
+        if proc.proc_status == ProcStatus::Zombie {
+            return;
+        }
 
         proc.proc_status = ProcStatus::Zombie;
         proc.exit_code = exit_code;
-        proc.tasks = vec![];
+        let times = proc.times();
+        proc.dead_times = times;
+        for task in mem::take(&mut proc.tasks) {
+            let mut task_guard = task.lock();
+            if task_guard.task_status != TaskStatus::Zombie {
+                task_guard.task_status = TaskStatus::Zombie;
+                task_guard.exit_code = exit_code;
+            }
+        }
+        let user_mem = mem::replace(&mut proc.user_mem, MemSet::new(Vec::new()));
+        // the threads in the kernel on other harts share the frames they are accessing by `user_page()`,
+        // which never find the frames again once the mappings are removed with the lock held
+        user_mem.areas().iter().for_each(|area| area.unmap());
+        proc.page_table.flush_tlb();
 
         PROC_MANAGER.remove(pid);
 
@@ -223,38 +400,232 @@ impl Proc {
             *child.lock().parent_mut() = Some(INITPROC.phantom());
             INITPROC.lock().children.push(child.clone());
         }
+        let orphaned = !proc.children.is_empty();
+        let code = if proc.term_sig.is_some() {
+            CLD_KILLED
+        } else {
+            CLD_EXITED
+        };
         let parent = proc.parent().unwrap();
-        parent.kill(SignalFlags::SIGCHLD);
+        drop(proc);
+        drop(user_mem);
+
+        // the orphans might have exited, which should be waited for by the initial process instead
+        if orphaned {
+            INITPROC.notify_waiters();
+        }
+        parent.notify_waiters();
+        parent.send(SignalFlags::SIGCHLD, SigInfo::new(SIGCHLD, code, pid, 0));
         infoln!("Process {} has ended.", pid);
     }
 
+    /// Handle a page fault of user on `vpn`, returning whether the faulting access could be retried.
+    ///
+    /// The page that should be loaded from the file is read without holding the lock of the process.
+    /// If the memory runs out, some is reclaimed by `oom_kill()` before the fault is handled again.
+    /// The fault is given up if the process itself is killed, where it exits on its way back to user,
+    /// and it fails like an invalid access if there's no process to kill.
+    pub fn handle_fault(&self, vpn: VirPageNum, access: MappingPermission) -> bool {
+        loop {
+            let fault = self.lock().handle_fault(vpn, access);
+            let filled = match fault {
+                AreaFault::Resolved => return true,
+                AreaFault::Invalid => return false,
+                AreaFault::Load(backing) => backing
+                    .load(vpn)
+                    .and_then(|frame| self.lock().user_mem.fill(vpn, frame)),
+                AreaFault::OutOfMemory => None,
+            };
+            if filled.is_none() {
+                match oom_kill() {
+                    Some(victim) if victim.pid() == self.pid() => return true,
+                    Some(_) => {}
+                    None => return false,
+                }
+            }
+        }
+    }
+
+    /// Post a process-directed signal, which is delivered to a thread that doesn't mask it.
+    ///
+    /// The thread that could handle it soon is preferred, i.e. one that is neither stopped nor blocked uninterruptibly.
+    /// The signal is kept pending on the process if every thread masks it,
+    /// while `SIGKILL`, `SIGSTOP` and `SIGCONT` apply to every thread.
+    /// An ignored signal is discarded at once, and so is any signal that the initial process doesn't catch.
     pub fn kill(&self, sig: SignalFlags) {
-        let proc = self.lock();
-        let task = &proc.tasks[0];
-        task.kill(sig);
+        self.send(sig, SigInfo::kernel(sig.bits().trailing_zeros()));
+    }
+
+    /// Post a process-directed signal with the information of it, just like `kill()`.
+    pub fn send(&self, sig: SignalFlags, info: SigInfo) {
+        let mut proc = self.lock();
+        if sig.contains(SignalFlags::SIGCONT) {
+            proc.stop_sig = None;
+        }
+        let sigid = info.signo as usize;
+        let uncaught = self.pid() == INITPROC.pid() && proc.sig_actions[sigid].handler() == SIG_DFL;
+        if proc.ignores(sigid) || uncaught {
+            return;
+        }
+
+        let whole = SignalFlags::SIGKILL | SignalFlags::SIGSTOP | SignalFlags::SIGCONT;
+        if whole.intersects(sig) {
+            for task in proc.tasks.iter() {
+                task.send(sig, info);
+            }
+        } else {
+            let candidates: Vec<(&Arc<Task>, bool)> = proc
+                .tasks
+                .iter()
+                .filter_map(|task| {
+                    let task_guard = task.lock();
+                    if task_guard.task_status == TaskStatus::Zombie
+                        || task_guard.sig_mask.contains(sig)
+                    {
+                        None
+                    } else {
+                        let ready = task_guard.task_status != TaskStatus::Stopped
+                            && task_guard.task_status != TaskStatus::Blocked;
+                        Some((task, ready))
+                    }
+                })
+                .collect();
+            let target = candidates
+                .iter()
+                .find(|(_, ready)| *ready)
+                .or(candidates.first())
+                .map(|(task, _)| (*task).clone());
+            match target {
+                Some(task) => task.send(sig, info),
+                None => {
+                    proc.sigs |= sig;
+                    proc.sig_infos[info.signo as usize] = info;
+                }
+            }
+        }
 
         infoln!("Process {} receives signal {}.", self.pid(), sig.bits());
     }
+
+    /// Record that the process has been stopped by `sig`, which would be reported to the parent by `waitpid()`.
+    pub fn report_stop(&self, sig: usize) {
+        let parent = {
+            let mut proc = self.lock();
+            proc.stop_sig = Some(sig);
+            proc.parent()
+        };
+        if let Some(parent) = parent {
+            parent.notify_waiters();
+            parent.send(
+                SignalFlags::SIGCHLD,
+                SigInfo::new(SIGCHLD, CLD_STOPPED, self.pid(), 0),
+            );
+        }
+    }
+
+    /// Wake up the tasks waiting for the children or threads of the process to change state.
+    pub fn notify_waiters(&self) {
+        let mut proc = self.lock();
+        while let Some(task) = proc.exit_waiters.pop() {
+            task.wakeup();
+        }
+    }
+
+    /// Fire the real timer subscribed at `time`, which is ignored if the timer has been reset since then.
+    pub fn alarm(self: &Arc<Self>, time: usize) {
+        let mut proc = self.lock();
+        if proc.proc_status == ProcStatus::Zombie {
+            return;
+        }
+        let itimer = &mut proc.itimers[ITIMER_REAL];
+        if itimer.value() != time {
+            return;
+        }
+        if itimer.interval() == 0 {
+            *itimer = ITimer::disarmed();
+        } else {
+            let next = time + itimer.interval();
+            itimer.set(itimer.interval(), next);
+            TIMER.subscribe_alarm(next, self);
+        }
+        drop(proc);
+
+        self.kill(SignalFlags::SIGALRM);
+    }
+
+    /// Charge `ticks` of runtime to the virtual or profiling timer, posting the signal if it fires.
+    pub fn charge(&self, which: usize, ticks: usize) {
+        let mut proc = self.lock();
+        if proc.proc_status == ProcStatus::Zombie || !proc.itimers[which].consume(ticks) {
+            return;
+        }
+        drop(proc);
+
+        match which {
+            ITIMER_VIRTUAL => self.kill(SignalFlags::SIGVTALRM),
+            ITIMER_PROF => self.kill(SignalFlags::SIGPROF),
+            _ => unreachable!(),
+        }
+    }
 }
 
 impl Proc {
-    /// Renew a process with a new page table. It's cloning that wrap inside `Arc`.
-    fn renew(self: &Arc<Self>) -> Arc<Self> {
+    /// Renew a process with a new page table and a copy of `task` as its only thread,
+    /// whose resources are shared or copied according to `flags`.
+    ///
+    /// Return `None` if the memory runs out, where the pages of the original process that have been made read-only
+    /// are mapped writable again on the next write, since they are no longer shared.
+    fn renew(self: &Arc<Self>, task: &Arc<Task>, flags: CloneFlags) -> Option<Arc<Self>> {
         let proc = self.lock();
 
-        let page_table = Arc::new(PageTable::new());
-        let user_mem = proc.user_mem.renew(&page_table);
+        let page_table = Arc::new(PageTable::new()?);
+        let user_mem = proc.user_mem.renew_cow(&page_table)?;
+        // the writable pages of the original process have been made read-only,
+        // which should not be written through the stale TLB by its other threads after the fork
+        proc.page_table.flush_tlb();
         let base = proc.base;
 
-        page_table.map_trampoline();
+        page_table.map_trampoline()?;
 
-        let cwd = proc.cwd.clone();
-        let fd_table = proc.fd_table.clone();
+        let cwd = if flags.contains(CloneFlags::FS) {
+            proc.cwd.clone()
+        } else {
+            Arc::new(Spin::new(proc.cwd()))
+        };
+        let fd_table = if flags.contains(CloneFlags::FILES) {
+            proc.fd_table.clone()
+        } else {
+            Arc::new(Spin::new(proc.fd_table.lock().clone()))
+        };
+        // the copied mutexes and semaphores keep their states, but nobody is waiting for them
+        let (lock_table, sema_table, condvar_table) = if flags.contains(CloneFlags::SYSVSEM) {
+            (
+                proc.lock_table.clone(),
+                proc.sema_table.clone(),
+                proc.condvar_table.clone(),
+            )
+        } else {
+            (
+                Arc::new(Spin::new(
+                    proc.lock_table
+                        .lock()
+                        .map(|lock| Arc::new(lock.duplicate())),
+                )),
+                Arc::new(Spin::new(
+                    proc.sema_table
+                        .lock()
+                        .map(|sema| Arc::new(sema.duplicate())),
+                )),
+                Arc::new(Spin::new(
+                    proc.condvar_table
+                        .lock()
+                        .map(|_| Arc::new(Observable::new())),
+                )),
+            )
+        };
         let tid_allocator = Arc::new(IdAllocator::new());
         let parent = proc.parent.clone();
         let niceness = proc.niceness;
-        assert_eq!(proc.lock_table.len(), 0); // the cloning of Spines is not supported yet
-        assert_eq!(proc.sema_table.len(), 0); // the cloning of Spines is not supported yet
 
         let forked = Arc::new(Self {
             pid: PID_ALLOCATOR.alloc(),
@@ -265,34 +636,44 @@ impl Proc {
                 proc_status: ProcStatus::Running,
                 parent,
                 children: Vec::new(),
+                pgid: proc.pgid,
+                sid: proc.sid,
                 tasks: Vec::new(),
                 exit_code: 0,
+                term_sig: None,
+                core_dumped: false,
+                stop_sig: None,
+                exit_waiters: WaitingQueue::new(),
+                dead_times: CpuTimes::default(),
                 fd_table,
                 cwd,
-                sig_actions: [SignalAction::default(); NUM_SIGNAL],
+                cmdline: proc.cmdline.clone(),
+                sig_actions: proc.sig_actions,
+                sigs: SignalFlags::empty(),
+                sig_infos: [SigInfo::default(); NUM_SIGNAL],
                 base,
-                lock_table: AllocTable::default(),
-                sema_table: AllocTable::default(),
-                condvar_table: AllocTable::default(),
+                heap_base: proc.heap_base,
+                brk: proc.brk,
+                personality: proc.personality,
+                lock_table,
+                sema_table,
+                condvar_table,
                 niceness,
+                itimers: [ITimer::disarmed(); 3],
             }),
         });
 
-        let tasks = proc
-            .tasks
-            .iter()
-            .map(|task| {
-                task.renew(
-                    tid_allocator.alloc(),
-                    GID_ALLOCATOR.alloc(),
-                    Arc::downgrade(&forked),
-                    page_table.clone(),
-                    forked.lock().weight(),
-                )
-            })
-            .collect();
-        forked.inner.lock().tasks = tasks;
-        forked
+        // the thread keeps its tid, where its trap context and user stack lie
+        let tid = tid_allocator.alloc_at(task.lock().tid()).unwrap();
+        let task = task.renew(
+            tid,
+            GID_ALLOCATOR.alloc(),
+            Arc::downgrade(&forked),
+            page_table.clone(),
+            forked.lock().weight(),
+        )?;
+        forked.inner.lock().tasks = vec![task];
+        Some(forked)
     }
 
     fn new_fd_table() -> AllocTable<Fileable> {
@@ -304,6 +685,134 @@ impl Proc {
     }
 }
 
+/// The number of random bytes pointed by `AT_RANDOM`.
+const RANDOM_SIZE: usize = 16;
+
+/// The auxiliary vector of the program without `AT_NULL`, where `random` is the address of the random bytes.
+fn aux_vector(elf_data: &[u8], image: &UserImage, random: usize) -> Vec<(usize, usize)> {
+    let elf_file = xmas_elf::ElfFile::new(elf_data).expect("[proc] Fail to parse ELF file.");
+    let pt2 = &elf_file.header.pt2;
+    let ph_offset = pt2.ph_offset();
+
+    // the program headers are found by `PT_PHDR`, or by the loadable segment that contains them
+    let phdr = elf_file
+        .program_iter()
+        .find_map(|ph| match ph.get_type() {
+            Ok(xmas_elf::program::Type::Phdr) => Some(ph.virtual_addr()),
+            Ok(xmas_elf::program::Type::Load)
+                if ph.offset() <= ph_offset && ph_offset < ph.offset() + ph.file_size() =>
+            {
+                Some(ph.virtual_addr() + ph_offset - ph.offset())
+            }
+            _ => None,
+        })
+        .map_or(0, |phdr| image.bias + phdr as usize);
+    vec![
+        (AT_PHDR, phdr),
+        (AT_PHENT, pt2.ph_entry_size() as usize),
+        (AT_PHNUM, pt2.ph_count() as usize),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, image.entry.into()),
+        (AT_RANDOM, random),
+        (AT_HARTS, CPUS),
+    ]
+}
+
+/// Read the prefix of the elf `file` that contains the ELF header and all the program headers,
+/// which is checked by `check_elf()` so that the file could be mapped safely.
+///
+/// Return `-ENOEXEC` if the file is not a valid executable.
+fn read_elf_headers(file: &File<BlkDev>) -> Result<Vec<u8>, isize> {
+    let file_size = file.lock().size();
+    let mut elf_data = vec![0u8; min(file_size, PAGE_SIZE)];
+    file.lock().read_at(&mut elf_data, 0);
+
+    let len = {
+        let elf_file = xmas_elf::ElfFile::new(&elf_data).map_err(|_| -ENOEXEC)?;
+        let pt2 = &elf_file.header.pt2;
+        (pt2.ph_count() as usize)
+            .checked_mul(pt2.ph_entry_size() as usize)
+            .and_then(|size| size.checked_add(pt2.ph_offset() as usize))
+            .filter(|len| *len <= file_size)
+            .ok_or(-ENOEXEC)?
+    };
+    if len > elf_data.len() {
+        elf_data = vec![0u8; len];
+        file.lock().read_at(&mut elf_data, 0);
+    }
+    check_elf(&elf_data, file_size)?;
+    Ok(elf_data)
+}
+
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_TYPE_EXEC: u16 = 2;
+const ELF_TYPE_DYN: u16 = 3;
+const ELF_MACHINE_RISCV: u16 = 243;
+const ELF_PH_ENTRY_SIZE: usize = 56;
+
+/// Check that `elf_data` belongs to a 64-bit little-endian RISC-V executable of `file_size` bytes,
+/// whose segments lie within the file and below `USER_IMAGE_END` wherever it's loaded, and whose entry point is executable.
+///
+/// Both the executables at fixed addresses and the position-independent ones are supported.
+fn check_elf(elf_data: &[u8], file_size: usize) -> Result<(), isize> {
+    let elf_file = xmas_elf::ElfFile::new(elf_data).map_err(|_| -ENOEXEC)?;
+    let pt2 = &elf_file.header.pt2;
+    let elf_type = u16::from_le_bytes([elf_data[16], elf_data[17]]);
+    let machine = u16::from_le_bytes([elf_data[18], elf_data[19]]);
+    if elf_data[4] != ELF_CLASS_64
+        || elf_data[5] != ELF_DATA_LSB
+        || (elf_type != ELF_TYPE_EXEC && elf_type != ELF_TYPE_DYN)
+        || machine != ELF_MACHINE_RISCV
+        || pt2.ph_entry_size() as usize != ELF_PH_ENTRY_SIZE
+    {
+        return Err(-ENOEXEC);
+    }
+
+    let max_bias = if elf_type == ELF_TYPE_DYN {
+        USER_PIE_BASE + USER_ASLR_PAGES * PAGE_SIZE
+    } else {
+        0
+    };
+    let entry = pt2.entry_point() as usize;
+    let mut has_entry = false;
+    for ph in elf_file.program_iter() {
+        let ph_type = ph.get_type().map_err(|_| -ENOEXEC)?;
+        if ph_type != xmas_elf::program::Type::Load
+            && ph_type != xmas_elf::program::Type::Tls
+            && ph_type != xmas_elf::program::Type::Dynamic
+        {
+            continue;
+        }
+        let start = ph.virtual_addr() as usize;
+        let mem_size = ph.mem_size() as usize;
+        let seg_file_size = ph.file_size() as usize;
+        let file_end = (ph.offset() as usize).checked_add(seg_file_size);
+        let mem_end = start
+            .checked_add(mem_size)
+            .and_then(|end| end.checked_add(max_bias));
+        match (file_end, mem_end) {
+            (Some(file_end), Some(mem_end))
+                if file_end <= file_size
+                    && seg_file_size <= mem_size
+                    && mem_end <= USER_IMAGE_END => {}
+            _ => return Err(-ENOEXEC),
+        }
+        if ph_type == xmas_elf::program::Type::Load
+            && ph.flags().is_execute()
+            && start <= entry
+            && entry < start + mem_size
+        {
+            has_entry = true;
+        }
+    }
+    if has_entry {
+        Ok(())
+    } else {
+        Err(-ENOEXEC)
+    }
+}
+
 impl ProcInner {
     pub fn page_table(&self) -> Arc<PageTable> {
         self.page_table.clone()
@@ -318,17 +827,104 @@ impl ProcInner {
     }
 
     pub fn cwd(&self) -> Dir<BlkDev> {
-        self.cwd.clone()
+        self.cwd.lock().clone()
+    }
+
+    pub fn set_cwd(&self, cwd: Dir<BlkDev>) {
+        *self.cwd.lock() = cwd;
+    }
+
+    pub fn user_mem(&self) -> &MemSet {
+        &self.user_mem
+    }
+
+    pub fn user_mem_mut(&mut self) -> &mut MemSet {
+        &mut self.user_mem
+    }
+
+    /// Handle a page fault of user on `vpn`, consulting both the memory set and the user stacks.
+    pub fn handle_fault(&mut self, vpn: VirPageNum, access: MappingPermission) -> AreaFault {
+        match self.user_mem.handle_fault(vpn, access) {
+            AreaFault::Invalid => {}
+            fault => return fault,
+        }
+        for task in self.tasks.iter() {
+            let mut task_guard = task.lock();
+            let stack = task_guard.user_stack.area_mut();
+            if stack.contains(vpn) {
+                return stack.handle_fault(vpn, access);
+            }
+        }
+        AreaFault::Invalid
+    }
+
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    /// Whether `[start, end)` overlaps the heap, even if the heap is empty,
+    /// which should only be resized by `brk()` instead of being split by other mappings.
+    pub fn overlaps_heap(&self, start: VirPageNum, end: VirPageNum) -> bool {
+        let heap_start = VirAddr::from(self.heap_base).floor_to_vir_page_num();
+        let heap_end = max(
+            VirAddr::from(self.brk).ceil_to_vir_page_num(),
+            heap_start + 1,
+        );
+        start < heap_end && heap_start < end
+    }
+
+    /// Move the end of the heap to `brk`, returning whether it succeeds.
+    pub fn set_brk(&mut self, brk: usize) -> bool {
+        if brk < self.heap_base || brk > self.heap_base + USER_HEAP_LIMIT {
+            return false;
+        }
+        // the heap might have been unmapped by the user
+        let heap = match self
+            .user_mem
+            .area_mut(VirAddr::from(self.heap_base).floor_to_vir_page_num())
+        {
+            Some(heap) => heap,
+            None => return false,
+        };
+        heap.resize(VirAddr::from(brk).ceil_to_vir_page_num());
+        self.brk = brk;
+        true
+    }
+
+    /// The CPU times of the process, including those of the children that have been waited for.
+    pub fn times(&self) -> CpuTimes {
+        let mut times = self.dead_times;
+        for task in self.tasks.iter() {
+            times += task.lock().task_time.times();
+        }
+        times
+    }
+
+    /// Whether the signal is ignored by the process, either by `SIG_IGN` or by its default action.
+    pub fn ignores(&self, sigid: usize) -> bool {
+        match self.sig_actions[sigid].handler() {
+            SIG_IGN => true,
+            SIG_DFL => default_action(sigid) == DefaultAction::Ignore,
+            _ => false,
+        }
     }
 
-    pub fn cwd_mut(&mut self) -> &mut Dir<BlkDev> {
-        &mut self.cwd
+    /// The status reported to the parent by `waitpid()` after the process exits.
+    pub fn wait_status(&self) -> WaitStatus {
+        match self.term_sig {
+            Some(sig) => WaitStatus::Signaled(sig as u32, self.core_dumped),
+            None => WaitStatus::Exited(self.exit_code as i32),
+        }
     }
 
     pub fn main_task(&self) -> Arc<Task> {
         self.tasks[0].clone()
     }
 
+    pub fn niceness(&self) -> isize {
+        self.niceness
+    }
+
     pub fn weight(&self) -> usize {
         if self.niceness < 0 {
             let positive = (-self.niceness) as u32;
@@ -16,15 +16,16 @@ pub struct KernelStack {
 }
 
 impl KernelStack {
-    pub fn new(gid: usize) -> Self {
+    /// Allocate the kernel stack of `gid`, or return `None` if the memory runs out.
+    pub fn new(gid: usize) -> Option<Self> {
         let start_va = VirAddr::from(TRAMPOLINE_ADDR - KERNEL_STACK_SIZE * (gid + 1));
         let end_va = VirAddr::from(TRAMPOLINE_ADDR - KERNEL_STACK_SIZE * gid);
         let area = KERNEL_PAGE_TABLE.new_framed_area(
             start_va.floor_to_vir_page_num(),
             end_va.ceil_to_vir_page_num(),
             MappingPermission::R | MappingPermission::W,
-        );
-        Self { gid, area }
+        )?;
+        Some(Self { gid, area })
     }
 
     pub fn top(&self) -> VirAddr {
@@ -43,20 +44,30 @@ impl UserStack {
         Self {
             base,
             tid,
-            area: page_table.new_framed_area(
+            area: page_table.new_lazy_area(
                 (base + (tid - 1) * (USER_STACK_SIZE + PAGE_SIZE)).floor_to_vir_page_num(),
                 (base + tid * USER_STACK_SIZE + (tid - 1) * PAGE_SIZE).ceil_to_vir_page_num(),
                 MappingPermission::R | MappingPermission::W | MappingPermission::U,
+                None,
             ),
         }
     }
 
-    pub fn renew(&self, page_table: &Arc<PageTable>) -> Self {
-        Self {
+    /// Renew the user stack by sharing its frames with copy-on-write.
+    pub fn renew(&self, page_table: &Arc<PageTable>) -> Option<Self> {
+        Some(Self {
             base: self.base,
             tid: self.tid,
-            area: self.area.renew(page_table),
-        }
+            area: self.area.renew_cow(page_table)?,
+        })
+    }
+
+    pub fn area(&self) -> &Area {
+        &self.area
+    }
+
+    pub fn area_mut(&mut self) -> &mut Area {
+        &mut self.area
     }
 
     pub fn top(&self) -> VirAddr {
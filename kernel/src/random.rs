@@ -0,0 +1,79 @@
+//! The pseudo-random numbers of kernel, e.g. for the layout of user programs and the bytes of `AT_RANDOM`.
+//!
+//! They come from xorshift generators seeded by the real time, which are not cryptographically secure.
+//! The time of every draw is stirred into the state, and the output is scrambled,
+//! so that the numbers could hardly be predicted from the earlier ones.
+//! The bytes given to user come from another generator than the layout,
+//! so that they tell nothing about where the program lies.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::time::{get_time, real_time};
+
+/// A generator whose state is shared by all the harts.
+struct Stream {
+    state: AtomicUsize,
+}
+
+/// The generator for the layout of user programs.
+static LAYOUT: Stream = Stream::new();
+/// The generator for the random bytes given to user.
+static BYTES: Stream = Stream::new();
+
+/// Seed the generators, which should be called after `init_realtime()`.
+pub fn init_random() {
+    let seed = real_time().as_ns() ^ get_time().rotate_left(32);
+    LAYOUT.seed(seed);
+    BYTES.seed(scramble(seed));
+}
+
+pub fn random() -> usize {
+    LAYOUT.next()
+}
+
+/// Fill `buf` with pseudo-random bytes.
+pub fn fill_random(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = BYTES.next().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+impl Stream {
+    const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(1),
+        }
+    }
+
+    fn seed(&self, seed: usize) {
+        self.state.store(seed | 1, Ordering::Relaxed);
+    }
+
+    /// Draw the next number, where the ticks of CLINT at the moment are mixed into the state.
+    fn next(&self) -> usize {
+        let jitter = get_time().wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let step = |state: usize| xorshift(state) ^ jitter;
+        let prev = self
+            .state
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+                Some(step(state))
+            })
+            .unwrap();
+        scramble(step(prev))
+    }
+}
+
+fn xorshift(mut state: usize) -> usize {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+/// The finalizer of splitmix64, which hides the state behind the output.
+fn scramble(mut x: usize) -> usize {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
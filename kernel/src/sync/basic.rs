@@ -70,6 +70,13 @@ impl SpinLock {
             lock: AtomicBool::new(false),
         }
     }
+
+    /// Create a new lock in the same state, which is used to copy the lock into a forked process.
+    pub fn duplicate(&self) -> Self {
+        Self {
+            lock: AtomicBool::new(self.is_locked()),
+        }
+    }
 }
 
 impl BlockLock {
@@ -79,4 +86,12 @@ impl BlockLock {
             queue: Spin::new(WaitingQueue::new()),
         }
     }
+
+    /// Create a new lock in the same state but without waiters, which is used to copy the lock into a forked process.
+    pub fn duplicate(&self) -> Self {
+        Self {
+            lock: AtomicBool::new(self.is_locked()),
+            queue: Spin::new(WaitingQueue::new()),
+        }
+    }
 }
@@ -16,6 +16,14 @@ impl Condvar {
         lock.lock()
     }
 
+    /// Wait like `wait_mutex()`, while the task could also be woken up by a signal.
+    pub fn wait_mutex_interruptible<'a, T>(&'a self, guard: MutexGuard<'a, T>) -> MutexGuard<T> {
+        let lock = guard.mutex();
+        drop(guard);
+        self.inner.wait_interruptible(Processor::curr_task());
+        lock.lock()
+    }
+
     pub fn wait_spin<'a, T>(&'a self, guard: SpinGuard<'a, T>) -> SpinGuard<T> {
         let lock = guard.spin();
         drop(guard);
@@ -0,0 +1,112 @@
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::lazy_static;
+
+use crate::{
+    mm::address::PhyAddr,
+    task::{processor::Processor, task::Task, timer::TIMER},
+    time::get_time,
+};
+
+use super::{mcs::Mcs, waiting_queue::WaitingQueue};
+
+/// The queues of tasks waiting on user words, keyed by the physical address of the word.
+///
+/// Keying by the physical address makes the same word found by all the processes that share it.
+pub struct FutexTable {
+    queues: Mcs<BTreeMap<usize, WaitingQueue>>,
+}
+
+/// The result of waiting on a futex.
+pub enum FutexWait {
+    /// The task is woken up by `wake()`.
+    Woken,
+    /// The word doesn't hold the expected value, so the task doesn't wait at all.
+    Mismatched,
+    /// The task is woken up by the timer before anyone wakes it.
+    TimedOut,
+    /// The task is woken up by something else, e.g. a signal.
+    Interrupted,
+}
+
+lazy_static! {
+    pub static ref FUTEX_TABLE: FutexTable = FutexTable::new();
+}
+
+impl FutexTable {
+    pub fn new() -> Self {
+        Self {
+            queues: Mcs::new(BTreeMap::new()),
+        }
+    }
+
+    /// Wait on the word at `addr` if it still holds `val`, until it's woken up or the time reaches `deadline`.
+    ///
+    /// The word is checked with the table locked, so a wakeup after changing the word would never be lost.
+    pub fn wait(&self, addr: PhyAddr, val: u32, deadline: Option<usize>) -> FutexWait {
+        {
+            let task = Processor::curr_task();
+            let mut queues = self.queues.lock();
+            let word = unsafe { addr.as_ref::<AtomicU32>().unwrap() };
+            if word.load(Ordering::SeqCst) != val {
+                return FutexWait::Mismatched;
+            }
+            queues
+                .entry(addr.0)
+                .or_insert_with(WaitingQueue::new)
+                .push(&task);
+            if let Some(deadline) = deadline {
+                TIMER.subscribe(deadline, &task);
+            }
+        }
+        Processor::suspend_interruptible();
+
+        let task = Processor::curr_task();
+        // the task is still in the queue only if no one has woken it up
+        let removed = self.remove(addr, &task);
+        task.discard_wakeup();
+        if deadline.is_some() {
+            TIMER.unsubscribe(&task);
+        }
+        if !removed {
+            FutexWait::Woken
+        } else if deadline.map_or(false, |deadline| get_time() >= deadline) {
+            FutexWait::TimedOut
+        } else {
+            FutexWait::Interrupted
+        }
+    }
+
+    /// Wake up at most `count` tasks waiting on the word at `addr`, returning how many are woken up.
+    pub fn wake(&self, addr: PhyAddr, count: usize) -> usize {
+        let mut queues = self.queues.lock();
+        let mut woken = 0;
+        if let Some(queue) = queues.get_mut(&addr.0) {
+            while woken < count {
+                match queue.pop() {
+                    Some(task) => {
+                        task.wakeup();
+                        woken += 1;
+                    }
+                    None => break,
+                }
+            }
+            if queue.is_empty() {
+                queues.remove(&addr.0);
+            }
+        }
+        woken
+    }
+
+    /// Remove `task` from the queue of `addr`, returning whether it's still there.
+    fn remove(&self, addr: PhyAddr, task: &Arc<Task>) -> bool {
+        let mut queues = self.queues.lock();
+        let removed = queues
+            .get_mut(&addr.0)
+            .map_or(false, |queue| queue.remove(task));
+        if queues.get(&addr.0).map_or(false, |queue| queue.is_empty()) {
+            queues.remove(&addr.0);
+        }
+        removed
+    }
+}
@@ -1,7 +1,8 @@
 pub mod basic;
 pub mod condvar;
+pub mod futex;
 pub mod mcs;
 pub mod mutex;
 pub mod observable;
 pub mod semaphore;
-mod waiting_queue;
+pub mod waiting_queue;
@@ -22,9 +22,18 @@ impl Observable {
         Processor::suspend();
     }
 
+    /// Wait like `wait()`, while the task could also be woken up by a signal, after which it's no longer waiting.
+    pub fn wait_interruptible(&self, task: Arc<Task>) {
+        self.waitings.lock().push(&task);
+        Processor::suspend_interruptible();
+        self.waitings.lock().remove(&task);
+        task.discard_wakeup();
+    }
+
+    /// Wake up one of the waiting tasks, with the queue locked so that the task that has been removed is never woken.
     pub fn notify_one(&self) {
-        let task = self.waitings.lock().pop();
-        if let Some(task) = task {
+        let mut waitings = self.waitings.lock();
+        if let Some(task) = waitings.pop() {
             task.wakeup();
         }
     }
@@ -23,6 +23,11 @@ impl Semaphore {
         }
     }
 
+    /// Create a new semaphore with the same counter but without waiters.
+    pub fn duplicate(&self) -> Semaphore {
+        Self::new(self.inner.lock().counter)
+    }
+
     pub fn down(&self) {
         let sema = &self.inner;
         while sema.lock().counter == 0 {
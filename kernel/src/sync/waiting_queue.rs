@@ -20,6 +20,18 @@ impl WaitingQueue {
         self.waitings.push(task.phantom());
     }
 
+    /// Remove `task` from the queue, returning whether it's still waiting.
+    pub fn remove(&mut self, task: &Arc<Task>) -> bool {
+        let len = self.waitings.len();
+        self.waitings
+            .retain(|waiting| !core::ptr::eq(waiting.as_ptr(), Arc::as_ptr(task)));
+        self.waitings.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waitings.is_empty()
+    }
+
     pub fn pop(&mut self) -> Option<Arc<Task>> {
         loop {
             let opt_task = self.waitings.pop();
@@ -1,29 +1,66 @@
-use core::mem::size_of;
+use fosix::{
+    errno::{EFAULT, EINVAL, ENOENT},
+    fs::OpenFlags,
+};
 
-use crate::{fs::fileable::Fileable, ipc::pipe::Pipe, task::processor::Processor};
+use crate::{
+    fs::fileable::Fileable,
+    ipc::{pipe::Pipe, shm::SHM_MANAGER},
+    task::processor::Processor,
+};
+
+use super::parse_str;
 
 pub fn sys_pipe(pipe_ptr: usize) -> isize {
     let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    let fd_table = &mut proc_guard.fd_table;
+    let proc_guard = proc.lock();
+    let page_table = proc_guard.page_table();
+    let mut fd_table = proc_guard.fd_table.lock();
 
     let (pipe_read, pipe_write) = Pipe::new();
     let fd_read = fd_table.alloc(Fileable::Pipe(pipe_read));
     let fd_write = fd_table.alloc(Fileable::Pipe(pipe_write));
-    let src_fds = [fd_read, fd_write];
-    let src_bytes = unsafe {
-        core::slice::from_raw_parts(&src_fds as *const _ as *const u8, size_of::<[usize; 2]>())
-    };
+    drop(fd_table);
+    drop(proc_guard);
 
-    let mut dst_bytes = unsafe {
-        proc_guard
-            .page_table()
-            .translate_bytes(pipe_ptr.into(), size_of::<[usize; 2]>())
-    };
-    dst_bytes
-        .iter_mut()
-        .enumerate()
-        .for_each(|(i, byte)| **byte = src_bytes[i]);
+    if page_table
+        .write_user(pipe_ptr.into(), &[fd_read, fd_write])
+        .is_err()
+    {
+        let proc_guard = proc.lock();
+        proc_guard.fd_table.lock().dealloc(fd_read);
+        proc_guard.fd_table.lock().dealloc(fd_write);
+        return -EFAULT;
+    }
 
     0
 }
+
+/// Open the shared memory object called `name`, which is created with zero size if `OpenFlags::CREATE` is set.
+///
+/// The object could be resized by `ftruncate()` and mapped by `mmap()` with the returned descriptor.
+pub fn sys_shm_open(name: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    let name = try_user!(parse_str(name.into()));
+    match SHM_MANAGER.open(&name, flags.contains(OpenFlags::CREATE)) {
+        Some(shm) => Processor::curr_proc()
+            .lock()
+            .fd_table
+            .lock()
+            .alloc(Fileable::Shm(shm)) as isize,
+        None => -ENOENT,
+    }
+}
+
+/// Remove the name of a shared memory object, while it's still available to those who have opened or mapped it.
+pub fn sys_shm_unlink(name: usize) -> isize {
+    let name = try_user!(parse_str(name.into()));
+    if SHM_MANAGER.unlink(&name) {
+        0
+    } else {
+        -ENOENT
+    }
+}
@@ -1,9 +1,73 @@
-use crate::{drivers::exit::QEMU_EXIT, time::get_time};
+use fosix::{
+    errno::{EINTR, EINVAL, EPERM},
+    signal::{SignalFlags, SIGTTOU},
+    time::{CLOCK_MONOTONIC, CLOCK_REALTIME},
+};
+
+use crate::{
+    drivers::exit::QEMU_EXIT,
+    io::stdin::Stdin,
+    proc::manager::PROC_MANAGER,
+    task::processor::Processor,
+    time::{get_time, monotonic_time, real_time},
+};
 
 pub fn sys_shutdown(exit_code: usize) -> ! {
     QEMU_EXIT.exit(exit_code as u32);
 }
 
+/// Get the raw CLINT ticks since boot.
 pub fn sys_time() -> isize {
     get_time() as isize
 }
+
+/// Write the time of `clock_id` into the `TimeSpec` at `ts_ptr`.
+pub fn sys_clock_gettime(clock_id: usize, ts_ptr: usize) -> isize {
+    let time = match clock_id {
+        CLOCK_REALTIME => real_time(),
+        CLOCK_MONOTONIC => monotonic_time(),
+        _ => return -EINVAL,
+    };
+    let page_table = Processor::curr_proc().lock().page_table();
+    try_user!(page_table.write_user(ts_ptr.into(), &time));
+    0
+}
+
+/// Get the foreground group of the console, or 0 if every process is in the foreground.
+pub fn sys_tcgetpgrp() -> isize {
+    Stdin::foreground() as isize
+}
+
+/// Set the foreground group of the console to `pgid`, which should be a group in the session of the caller.
+///
+/// A caller in the background gets `SIGTTOU` sent to its group and fails with `EINTR`,
+/// unless it masks or ignores the signal.
+pub fn sys_tcsetpgrp(pgid: usize) -> isize {
+    let proc = Processor::curr_proc();
+    let (caller_pgid, sid, ignored) = {
+        let proc_guard = proc.lock();
+        (
+            proc_guard.pgid,
+            proc_guard.sid,
+            proc_guard.ignores(SIGTTOU as usize),
+        )
+    };
+    let masked = Processor::curr_task()
+        .lock()
+        .sig_mask
+        .contains(SignalFlags::SIGTTOU);
+    if !Stdin::is_foreground(caller_pgid) && !masked && !ignored {
+        PROC_MANAGER.kill_group(caller_pgid, SignalFlags::SIGTTOU);
+        return -EINTR;
+    }
+
+    if !PROC_MANAGER
+        .group(pgid)
+        .iter()
+        .any(|member| member.lock().sid == sid)
+    {
+        return -EPERM;
+    }
+    Stdin::set_foreground(pgid);
+    0
+}
@@ -1,33 +1,51 @@
 use core::mem::size_of;
 
-use fosix::fs::{DirEntry, FileStat, OpenFlags, SeekFlag};
-
-use crate::{fs::fileable::Fileable, task::processor::Processor};
+use alloc::vec::Vec;
+use fosix::{
+    errno::{EBADF, EINTR, EINVAL, ENOMEM},
+    fs::{DirEntry, OpenFlags, SeekFlag},
+    signal::SignalFlags,
+};
+
+use crate::{
+    fs::fileable::Fileable, io::stdin::Stdin, proc::manager::PROC_MANAGER,
+    task::processor::Processor,
+};
 
 use super::{create_dir, open_dir, open_file, parse_str};
 
+/// Read from `fd` into the buffer.
+///
+/// A process that reads the console in the background gets `SIGTTIN` sent to its group and the read is interrupted.
+/// Reading the console fails with `-EINTR` if a signal arrives before any byte does.
 pub fn sys_read(fd: usize, buffer_ptr: usize, buffer_len: usize) -> isize {
-    let (mut fileable, mut seg) = {
+    let (mut fileable, page_table, pgid) = {
         let proc = Processor::curr_proc();
         let proc_guard = proc.lock();
-        let page_table = proc_guard.page_table();
-        let fileable = proc_guard.fd_table.get(fd).unwrap();
-        let seg = unsafe { page_table.translate_segment(buffer_ptr.into(), buffer_len) };
-        (fileable, seg)
+        let fileable = proc_guard.fd_table.lock().get(fd).unwrap();
+        (fileable, proc_guard.page_table(), proc_guard.pgid)
     };
+    if fileable.as_stdin().is_some() && !Stdin::is_foreground(pgid) {
+        PROC_MANAGER.kill_group(pgid, SignalFlags::SIGTTIN);
+        return -EINTR;
+    }
+    let mut seg = try_user!(page_table.user_segment(buffer_ptr.into(), buffer_len, true));
 
-    fileable.read_seg(&mut seg) as isize
+    let bytes = fileable.read_seg(&mut seg);
+    if bytes == 0 && buffer_len != 0 && fileable.as_stdin().is_some() {
+        return -EINTR;
+    }
+    bytes as isize
 }
 
 pub fn sys_write(fd: usize, buffer_ptr: usize, buffer_len: usize) -> isize {
-    let (mut fileable, seg) = {
+    let (mut fileable, page_table) = {
         let proc = Processor::curr_proc();
         let proc_guard = proc.lock();
-        let page_table = proc_guard.page_table();
-        let fileable = proc_guard.fd_table.get(fd).unwrap();
-        let seg = unsafe { page_table.translate_segment(buffer_ptr.into(), buffer_len) };
-        (fileable, seg)
+        let fileable = proc_guard.fd_table.lock().get(fd).unwrap();
+        (fileable, proc_guard.page_table())
     };
+    let seg = try_user!(page_table.user_segment(buffer_ptr.into(), buffer_len, false));
 
     fileable.write_seg(&seg) as isize
 }
@@ -35,7 +53,7 @@ pub fn sys_write(fd: usize, buffer_ptr: usize, buffer_len: usize) -> isize {
 pub fn sys_open(path: usize, flags: u32) -> isize {
     let flags = OpenFlags::from_bits(flags).unwrap();
     let cwd = Processor::curr_proc().lock().cwd();
-    let path = &unsafe { parse_str(path.into()) };
+    let path = &try_user!(parse_str(path.into()));
     let fileable = if flags.contains(OpenFlags::DIR) {
         let dir = open_dir(cwd, path);
         if dir.is_none() {
@@ -49,13 +67,17 @@ pub fn sys_open(path: usize, flags: u32) -> isize {
         }
         Fileable::File(file.unwrap())
     };
-    Processor::curr_proc().lock().fd_table.alloc(fileable) as isize
+    Processor::curr_proc()
+        .lock()
+        .fd_table
+        .lock()
+        .alloc(fileable) as isize
 }
 
 pub fn sys_close(fd: usize) -> isize {
     let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    let flag = proc_guard.fd_table.dealloc(fd);
+    let proc_guard = proc.lock();
+    let flag = proc_guard.fd_table.lock().dealloc(fd);
     if flag {
         0
     } else {
@@ -64,11 +86,12 @@ pub fn sys_close(fd: usize) -> isize {
 }
 
 pub fn sys_mkdir(dfd: usize, path: usize) -> isize {
-    let path = unsafe { parse_str(path.into()) };
+    let path = try_user!(parse_str(path.into()));
     let dir = create_dir(
         Processor::curr_proc()
             .lock()
             .fd_table
+            .lock()
             .get(dfd)
             .unwrap()
             .as_dir()
@@ -83,13 +106,11 @@ pub fn sys_mkdir(dfd: usize, path: usize) -> isize {
 }
 
 pub fn sys_chdir(path: usize) -> isize {
-    let path = unsafe { parse_str(path.into()) };
+    let path = try_user!(parse_str(path.into()));
     let cwd = Processor::curr_proc().lock().cwd();
     let dir = open_dir(cwd, &path);
     if let Some(dir) = dir {
-        let proc = Processor::curr_proc();
-        let mut proc_guard = proc.lock();
-        *proc_guard.cwd_mut() = dir;
+        Processor::curr_proc().lock().set_cwd(dir);
         0
     } else {
         -1
@@ -100,6 +121,7 @@ pub fn sys_getdents(dfd: usize, des_ptr: usize, des_len: usize) -> isize {
     let cwd = Processor::curr_proc()
         .lock()
         .fd_table
+        .lock()
         .get(dfd)
         .unwrap()
         .as_dir()
@@ -107,59 +129,66 @@ pub fn sys_getdents(dfd: usize, des_ptr: usize, des_len: usize) -> isize {
     let dir = open_dir(cwd, ".").unwrap();
     let dir_entries = dir.lock().to_dir_entries();
 
-    let proc = Processor::curr_proc();
-    let proc_guard = proc.lock();
-    let mut dst_bytes = unsafe {
-        proc_guard
-            .page_table()
-            .translate_bytes(des_ptr.into(), des_len * size_of::<DirEntry>())
-    };
+    let mut src_bytes = Vec::new();
+    dir_entries
+        .iter()
+        .for_each(|de| src_bytes.extend_from_slice(de.as_bytes()));
+    src_bytes.truncate(des_len.saturating_mul(size_of::<DirEntry>()));
 
-    let mut i = 0;
-    for de in dir_entries {
-        let src_bytes = de.as_bytes();
-        for byte in src_bytes {
-            *dst_bytes[i] = *byte;
-            i += 1;
-            if i >= dst_bytes.len() {
-                return i as isize;
-            }
-        }
-    }
-    i as isize
+    let page_table = Processor::curr_proc().lock().page_table();
+    try_user!(page_table.copy_to_user(des_ptr.into(), &src_bytes));
+    src_bytes.len() as isize
 }
 
 pub fn sys_fstat(fd: usize, stat_ptr: usize) -> isize {
-    let dir = Processor::curr_proc().lock().fd_table.get(fd).unwrap();
+    let dir = Processor::curr_proc()
+        .lock()
+        .fd_table
+        .lock()
+        .get(fd)
+        .unwrap();
     let stat = dir.stat();
-    let src_bytes = stat.as_bytes();
-
-    let proc = Processor::curr_proc();
-    let proc_guard = proc.lock();
-    let mut dst_bytes = unsafe {
-        proc_guard
-            .page_table()
-            .translate_bytes(stat_ptr.into(), size_of::<FileStat>())
-    };
-
-    assert_eq!(src_bytes.len(), dst_bytes.len());
-    for (i, byte) in dst_bytes.iter_mut().enumerate() {
-        **byte = src_bytes[i];
-    }
 
+    let page_table = Processor::curr_proc().lock().page_table();
+    try_user!(page_table.write_user(stat_ptr.into(), &stat));
     0
 }
 
 pub fn sys_lseek(fd: usize, offset: isize, flags: usize) -> isize {
-    let mut fileable = Processor::curr_proc().lock().fd_table.get(fd).unwrap();
+    let mut fileable = Processor::curr_proc()
+        .lock()
+        .fd_table
+        .lock()
+        .get(fd)
+        .unwrap();
     fileable.seek(offset as usize, SeekFlag::from_bits(flags as u8).unwrap());
     0
 }
 
+/// Resize the file of `fd` to `len` bytes.
+///
+/// A shared memory object could be resized freely, while a regular file could only be truncated to zero.
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    let fileable = Processor::curr_proc().lock().fd_table.lock().get(fd);
+    match fileable {
+        Some(Fileable::Shm(shm)) => {
+            if !shm.resize(len) {
+                return -ENOMEM;
+            }
+        }
+        Some(Fileable::File(file)) if len == 0 => {
+            file.lock().trunc();
+        }
+        Some(_) => return -EINVAL,
+        None => return -EBADF,
+    }
+    0
+}
+
 pub fn sys_dup(fd: usize) -> isize {
     let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    let fd_table = &mut proc_guard.fd_table;
+    let proc_guard = proc.lock();
+    let mut fd_table = proc_guard.fd_table.lock();
     let fileable = fd_table.get(fd);
     if let Some(fileable) = fileable {
         fd_table.alloc(fileable) as isize
@@ -0,0 +1,259 @@
+use core::cmp::min;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use fosix::{
+    errno::{EACCES, EBADF, EINVAL, ENOMEM},
+    fs::FilePerm,
+    mm::{MapFlags, MapProt, MsyncFlags},
+};
+
+use crate::{
+    config::{PAGE_SIZE, USER_SPACE_END},
+    fs::fileable::Fileable,
+    mm::{
+        address::{VirAddr, VirPageNum},
+        area::{Area, FileBacking},
+        memory::MappingPermission,
+    },
+    task::processor::Processor,
+};
+
+/// Set the end of the heap to `addr`, returning the new end.
+///
+/// Just like Linux, the current end is returned if `addr` is 0 or invalid.
+pub fn sys_brk(addr: usize) -> isize {
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    if addr != 0 {
+        proc_guard.set_brk(addr);
+    }
+    proc_guard.brk() as isize
+}
+
+/// Grow or shrink the heap by `increment`, returning the previous end of the heap.
+pub fn sys_sbrk(increment: isize) -> isize {
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    let brk = proc_guard.brk();
+    match brk.checked_add_signed(increment) {
+        Some(new_brk) if proc_guard.set_brk(new_brk) => brk as isize,
+        _ => -ENOMEM,
+    }
+}
+
+/// Map `len` bytes at `addr` with `prot`, returning the start address of the mapping.
+///
+/// The mapping is anonymous if `MapFlags::ANONYMOUS` is set, otherwise it's backed by `[offset, offset + len)` of `fd`.
+/// A private mapping is loaded lazily and copied on write after `fork()`,
+/// while a shared one is loaded at once and written back to the file when no one maps it.
+/// A shared memory object is mapped with its own frames, which should cover the whole range.
+/// `addr` is only a hint unless `MapFlags::FIXED` is set, where the range should not overlap the heap.
+/// Fail with `EACCES` if the file is not readable, or a shared writable mapping is asked of a file not writable.
+pub fn sys_mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    let (prot, flags) = match (MapProt::from_bits(prot), MapFlags::from_bits(flags)) {
+        (Some(prot), Some(flags)) => (prot, flags),
+        _ => return -EINVAL,
+    };
+    let shared = flags.contains(MapFlags::SHARED);
+    if len == 0
+        || len > USER_SPACE_END
+        || offset % PAGE_SIZE != 0
+        || shared == flags.contains(MapFlags::PRIVATE)
+    {
+        return -EINVAL;
+    }
+    let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+    if flags.contains(MapFlags::FIXED)
+        && (addr % PAGE_SIZE != 0
+            || addr
+                .checked_add(pages * PAGE_SIZE)
+                .map_or(true, |end| end > USER_SPACE_END))
+    {
+        return -EINVAL;
+    }
+    let map_perm = map_perm(prot);
+
+    let proc = Processor::curr_proc();
+    let (file, shm_frames) = if flags.contains(MapFlags::ANONYMOUS) {
+        (None, None)
+    } else {
+        match proc.lock().fd_table.lock().get(fd) {
+            Some(Fileable::File(file)) => {
+                let perm = file.lock().perm();
+                if !perm.contains(FilePerm::READABLE)
+                    || shared
+                        && prot.contains(MapProt::WRITE)
+                        && !perm.contains(FilePerm::WRITEABLE)
+                {
+                    return -EACCES;
+                }
+                (Some(file), None)
+            }
+            Some(Fileable::Shm(shm)) => match shm.frames(offset / PAGE_SIZE, pages) {
+                Some(frames) => (None, Some(frames)),
+                None => return -EINVAL,
+            },
+            Some(_) => return -EACCES,
+            None => return -EBADF,
+        }
+    };
+
+    let file_len = file.as_ref().map_or(0, |file| {
+        min(len, file.lock().size().saturating_sub(offset))
+    });
+
+    let mut proc_guard = proc.lock();
+    let page_table = proc_guard.page_table();
+    if flags.contains(MapFlags::FIXED)
+        && proc_guard.overlaps_heap(VirPageNum::from(addr), VirPageNum::from(addr) + pages)
+    {
+        return -EINVAL;
+    }
+    let user_mem = proc_guard.user_mem_mut();
+    let (start, unmapped) = if flags.contains(MapFlags::FIXED) {
+        let start = VirPageNum::from(addr);
+        (start, user_mem.unmap(start, start + pages))
+    } else {
+        match user_mem.find_free(pages) {
+            Some(start) => (start, Vec::new()),
+            None => return -ENOMEM,
+        }
+    };
+    let end = start + pages;
+    let backing = file.map(|file| FileBacking::new(file, offset, start.into(), file_len));
+
+    if shm_frames.is_some() || !shared {
+        let area = match shm_frames {
+            Some(frames) => Area::new_with_frames(start, frames, map_perm, shared, &page_table),
+            None => Some(page_table.new_lazy_area(start, end, map_perm, backing)),
+        };
+        let res = match area {
+            Some(area) => {
+                user_mem.insert(area);
+                usize::from(start) as isize
+            }
+            None => -ENOMEM,
+        };
+        drop(proc_guard);
+        unmapped.into_iter().for_each(Area::write_back);
+        return res;
+    }
+
+    // filling a shared mapping might yield, so the range is reserved by an inaccessible area in the meantime
+    user_mem.insert(page_table.new_lazy_area(start, end, MappingPermission::U, None));
+    drop(proc_guard);
+    unmapped.into_iter().for_each(Area::write_back);
+    let area = Area::new_shared(start, end, map_perm, backing, &page_table);
+
+    let mut proc_guard = proc.lock();
+    if !Arc::ptr_eq(&proc_guard.page_table(), &page_table) {
+        // the process has executed another program in the meantime
+        return -ENOMEM;
+    }
+    let user_mem = proc_guard.user_mem_mut();
+    let reserved = user_mem.unmap(start, end);
+    let res = match area {
+        Some(area) => {
+            user_mem.insert(area);
+            usize::from(start) as isize
+        }
+        None => -ENOMEM,
+    };
+    drop(proc_guard);
+    drop(reserved);
+    res
+}
+
+/// Remove the mappings of `len` bytes at `addr`, where `addr` should be page-aligned and the range should not overlap the heap.
+///
+/// The pages of shared file mappings in the range are written back to the file.
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let (start, end) = match user_range(addr, len) {
+        Some(range) => range,
+        None => return -EINVAL,
+    };
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    if proc_guard.overlaps_heap(start, end) {
+        return -EINVAL;
+    }
+    let unmapped = proc_guard.user_mem_mut().unmap(start, end);
+    drop(proc_guard);
+    unmapped.into_iter().for_each(Area::write_back);
+    0
+}
+
+/// Change the protection of `len` bytes at `addr`, where `addr` should be page-aligned and the range should not overlap the heap.
+///
+/// Fail with `ENOMEM` if any page of the range is not mapped.
+pub fn sys_mprotect(addr: usize, len: usize, prot: u32) -> isize {
+    let (start, end, prot) = match (user_range(addr, len), MapProt::from_bits(prot)) {
+        (Some((start, end)), Some(prot)) => (start, end, prot),
+        _ => return -EINVAL,
+    };
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    if proc_guard.overlaps_heap(start, end) {
+        return -EINVAL;
+    }
+    if proc_guard
+        .user_mem_mut()
+        .protect(start, end, map_perm(prot))
+    {
+        0
+    } else {
+        -ENOMEM
+    }
+}
+
+/// Write the pages of shared file mappings in `len` bytes at `addr` back to the file, where `addr` should be page-aligned.
+///
+/// The writing is always done before returning, and `MsyncFlags::INVALIDATE` has nothing to do,
+/// since the frames of a shared mapping are the only copy of the file in memory.
+/// Fail with `ENOMEM` if any page of the range is not mapped.
+pub fn sys_msync(addr: usize, len: usize, flags: u32) -> isize {
+    let (start, end) = match (user_range(addr, len), MsyncFlags::from_bits(flags)) {
+        (Some(range), Some(flags)) if !flags.contains(MsyncFlags::ASYNC | MsyncFlags::SYNC) => {
+            range
+        }
+        _ => return -EINVAL,
+    };
+    let proc = Processor::curr_proc();
+    // writing the file might yield, which should not hold the lock
+    let pages = match proc.lock().user_mem().shared_pages(start, end) {
+        Some(pages) => pages,
+        None => return -ENOMEM,
+    };
+    for (backing, vpn, frame) in pages {
+        backing.write_page(vpn, &frame);
+    }
+    0
+}
+
+/// Convert the protection of user to the permission of mapping, where writable implies readable as RISC-V requires.
+fn map_perm(prot: MapProt) -> MappingPermission {
+    let mut map_perm = MappingPermission::U;
+    if prot.contains(MapProt::READ) {
+        map_perm |= MappingPermission::R;
+    }
+    if prot.contains(MapProt::WRITE) {
+        map_perm |= MappingPermission::R | MappingPermission::W;
+    }
+    if prot.contains(MapProt::EXEC) {
+        map_perm |= MappingPermission::X;
+    }
+    map_perm
+}
+
+/// Convert the page-aligned `[addr, addr + len)` in the user space into pages.
+fn user_range(addr: usize, len: usize) -> Option<(VirPageNum, VirPageNum)> {
+    let end = addr.checked_add(len)?;
+    if addr % PAGE_SIZE != 0 || len == 0 || end > USER_SPACE_END {
+        return None;
+    }
+    Some((
+        VirPageNum::from(addr),
+        VirAddr::from(end).ceil_to_vir_page_num(),
+    ))
+}
@@ -1,7 +1,18 @@
+/// Unwrap the result of accessing user memory, or return `-EFAULT` from the syscall when it fails.
+macro_rules! try_user {
+    ($e: expr) => {
+        match $e {
+            Ok(value) => value,
+            Err(_) => return -fosix::errno::EFAULT,
+        }
+    };
+}
+
 mod com;
 mod debug;
 mod dev;
 mod file;
+mod mm;
 mod proc;
 mod task;
 
@@ -9,15 +20,21 @@ use alloc::{string::String, vec::Vec};
 use fosix::{fs::OpenFlags, syscall::*};
 use simplefs::{dir::Dir, file::File};
 
-use crate::{drivers::blockdev::BlkDev, fs::FS, mm::address::VirAddr, task::processor::Processor};
+use crate::{
+    drivers::blockdev::BlkDev,
+    fs::FS,
+    mm::{address::VirAddr, page_table::UserFault},
+    task::processor::Processor,
+};
 
-use self::{com::*, debug::*, dev::*, file::*, proc::*, task::*};
+use self::{com::*, debug::*, dev::*, file::*, mm::*, proc::*, task::*};
 
-pub fn syscall(id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(id: usize, args: [usize; 6]) -> isize {
     match id {
         SYSCALL_DUP => sys_dup(args[0]),
         SYSCALL_MKDIR => sys_mkdir(args[0], args[1]),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1]),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1]),
         SYSCALL_CHDIR => sys_chdir(args[0]),
         SYSCALL_OPEN => sys_open(args[0], args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
@@ -26,21 +43,47 @@ pub fn syscall(id: usize, args: [usize; 3]) -> isize {
         SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1], args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1], args[2]),
+        SYSCALL_PERSONALITY => sys_personality(args[0]),
         SYSCALL_EXIT => sys_exit(args[0] as isize),
-        SYSCALL_SLEEP => sys_sleep(args[0]),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as isize),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2], args[3]),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args[0], args[1]),
+        SYSCALL_GETITIMER => sys_getitimer(args[0], args[1]),
+        SYSCALL_SETITIMER => sys_setitimer(args[0], args[1], args[2]),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1]),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1]),
+        SYSCALL_TGKILL => sys_tgkill(args[0], args[1], args[2]),
+        SYSCALL_SIGALTSTACK => sys_sigaltstack(args[0], args[1]),
+        SYSCALL_SIGSUSPEND => sys_sigsuspend(args[0] as u32),
         SYSCALL_SIGACTION => sys_sigaction(args[0], args[1], args[2]),
+        SYSCALL_SIGPENDING => sys_sigpending(),
         SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
         SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
         SYSCALL_TIME => sys_time(),
         SYSCALL_GETPID => sys_getpid(),
-        SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0], args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1]),
-        SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MMAP => sys_mmap(
+            args[0],
+            args[1],
+            args[2] as u32,
+            args[3] as u32,
+            args[4],
+            args[5],
+        ),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2] as u32),
+        SYSCALL_MSYNC => sys_msync(args[0], args[1], args[2] as u32),
+        SYSCALL_CLONE => sys_clone(args[0] as u32, args[1], args[2]),
+        SYSCALL_EXEC => sys_exec(args[0], args[1], args[2]),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1], args[2] as u32, args[3]),
         SYSCALL_GETTID => sys_gettid(),
-        SYSCALL_WAITTID => sys_waittid(args[0] as isize, args[1]),
+        SYSCALL_WAITTID => sys_waittid(args[0] as isize, args[1], args[2] as u32),
+        SYSCALL_THREAD_DETACH => sys_thread_detach(args[0]),
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
@@ -51,6 +94,11 @@ pub fn syscall(id: usize, args: [usize; 3]) -> isize {
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
         SYSCALL_CONDVAR_NOTIFY_ONE => sys_condvar_notify_one(args[0]),
         SYSCALL_CONDVAR_NOTIFY_ALL => sys_condvar_notify_all(args[0]),
+        SYSCALL_SBRK => sys_sbrk(args[0] as isize),
+        SYSCALL_SHM_OPEN => sys_shm_open(args[0], args[1] as u32),
+        SYSCALL_SHM_UNLINK => sys_shm_unlink(args[0]),
+        SYSCALL_TCGETPGRP => sys_tcgetpgrp(),
+        SYSCALL_TCSETPGRP => sys_tcsetpgrp(args[0]),
         SYSCALL_SHUTDOWN => sys_shutdown(args[0]),
         SYSCALL_PROCDUMP => sys_procdump(),
         _ => panic!("[kernel] Unknown syscall id: {}", id),
@@ -111,9 +159,7 @@ fn create_dir(cwd: Dir<BlkDev>, path: &str) -> Option<Dir<BlkDev>> {
     }
 }
 
-unsafe fn parse_str(path: VirAddr) -> String {
-    Processor::curr_proc()
-        .lock()
-        .page_table()
-        .translate_str(path)
+fn parse_str(path: VirAddr) -> Result<String, UserFault> {
+    let page_table = Processor::curr_proc().lock().page_table();
+    page_table.read_user_cstr(path)
 }
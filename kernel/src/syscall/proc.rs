@@ -1,135 +1,458 @@
-use alloc::{sync::Arc, vec::Vec};
+use core::{
+    cmp::min,
+    mem::{self, size_of},
+};
+
+use alloc::{string::String, sync::Arc, vec::Vec};
 use fosix::{
+    errno::{
+        E2BIG, EAGAIN, ECHILD, EFAULT, EINTR, EINVAL, ELOOP, ENOENT, ENOEXEC, ENOMEM, ENOSYS,
+        EPERM, ESRCH, ETIMEDOUT,
+    },
     fs::OpenFlags,
-    signal::{SignalAction, SignalFlags, SIGCONT, SIGKILL, SIGSTOP},
+    signal::{
+        SigInfo, SignalAction, SignalFlags, SignalStack, MIN_SIG_STACK_SIZE, SIGCONT, SIGKILL,
+        SIGSTOP, SI_TKILL, SI_USER, SS_DISABLE, SS_ONSTACK,
+    },
+    syscall::{
+        CloneFlags, WaitFlags, WaitStatus, ADDR_NO_RANDOMIZE, FUTEX_WAIT, FUTEX_WAKE, PER_QUERY,
+    },
+    time::{ITimerVal, TimeSpec, ITIMER_PROF, ITIMER_REAL},
 };
+use simplefs::file::File;
 
 use crate::{
-    proc::{lockable::Lockable, manager::PROC_MANAGER, proc::ProcStatus},
+    config::{NUM_SIGNAL, USER_STACK_SIZE},
+    drivers::blockdev::BlkDev,
+    mm::page_table::PageTable,
+    proc::{
+        itimer::ITimer,
+        lockable::Lockable,
+        manager::{INITPROC, PROC_MANAGER},
+        oom::reclaim,
+        proc::{Proc, ProcStatus},
+    },
     sync::{
         basic::{BlockLock, SpinLock},
+        futex::{FutexWait, FUTEX_TABLE},
         observable::Observable,
         semaphore::Semaphore,
     },
-    task::processor::{Processor, PROCESSORS},
+    task::{
+        processor::{Processor, PROCESSORS},
+        task::TaskStatus,
+        timer::TIMER,
+    },
+    time::{get_time, ns_to_ticks},
+    trap::signal::SignalFrame,
 };
 
 use super::{open_file, parse_str};
 
+/// Exit the calling thread, which ends the process if it's the last running thread.
 pub fn sys_exit(exit_code: isize) -> isize {
     Processor::exit(exit_code);
     0
 }
 
+/// Exit the process with all its threads.
+pub fn sys_exit_group(exit_code: isize) -> isize {
+    Processor::curr_proc().exit(exit_code);
+    Processor::exit(exit_code);
+    0
+}
+
 pub fn sys_yield() -> isize {
     Processor::curr_task().lock().task_time.runout();
     0
 }
 
-pub fn sys_fork() -> isize {
-    let proc = Processor::curr_proc().fork();
-    let pid = proc.pid();
-    let task = proc.lock().main_task();
-    *task.lock().trap_ctx_mut().a0_mut() = 0;
-    PROC_MANAGER.push(&proc);
+/// Create a thread or a child process that shares the resources selected by `flags` with the caller,
+/// returning its tid or pid, while it returns 0 from the same place.
+///
+/// The child runs on `stack` if it's not null, and its thread pointer is set to `tls` with `CloneFlags::SETTLS`.
+/// Since the address space could only be shared between threads,
+/// `CloneFlags::VM`, `CloneFlags::SIGHAND` and `CloneFlags::THREAD` must come with all the other sharing flags.
+/// Return `-ENOMEM` if the memory runs out even after some process is killed by `reclaim()`.
+pub fn sys_clone(flags: u32, stack: usize, tls: usize) -> isize {
+    let flags = match CloneFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    let thread_flags = CloneFlags::VM
+        | CloneFlags::FS
+        | CloneFlags::FILES
+        | CloneFlags::SIGHAND
+        | CloneFlags::THREAD
+        | CloneFlags::SYSVSEM;
+    let threaded = CloneFlags::VM | CloneFlags::SIGHAND | CloneFlags::THREAD;
+    if flags.intersects(threaded) && !flags.contains(thread_flags) {
+        return -EINVAL;
+    }
+
+    let proc = Processor::curr_proc();
+    let curr_task = Processor::curr_task();
+    let (task, id) = if flags.contains(CloneFlags::THREAD) {
+        let task = loop {
+            match proc.new_task(&curr_task) {
+                Some(task) => break task,
+                None if reclaim() => {}
+                None => return -ENOMEM,
+            }
+        };
+        let tid = task.lock().tid();
+        infoln!("Process {} has created new thread {}", proc.pid(), tid);
+        (task, tid)
+    } else {
+        let child = loop {
+            match proc.fork(&curr_task, flags) {
+                Some(child) => break child,
+                None if reclaim() => {}
+                None => return -ENOMEM,
+            }
+        };
+        let pid = child.pid();
+        let task = child.lock().main_task();
+        PROC_MANAGER.push(&child);
+        infoln!("Fork a new process {}.", pid);
+        (task, pid)
+    };
+
+    {
+        let mut task_guard = task.lock();
+        let trap_ctx = task_guard.trap_ctx_mut();
+        *trap_ctx.a0_mut() = 0;
+        if stack != 0 {
+            *trap_ctx.user_sp_mut() = stack;
+        }
+        if flags.contains(CloneFlags::SETTLS) {
+            *trap_ctx.tp_mut() = tls;
+        }
+    }
     PROCESSORS[Processor::hart_id()].lock().push_normal(&task);
-    infoln!("Fork a new process {}.", pid);
-    pid as isize
+    id as isize
 }
 
-pub fn sys_exec(path: usize, mut args_ptr: *const usize) -> isize {
-    let name = unsafe { parse_str(path.into()) };
+/// The most interpreters that a script could be nested in, just like Linux.
+const MAX_INTERP_DEPTH: usize = 4;
+/// The longest `#!` line that is recognized, which is truncated otherwise.
+const MAX_SHEBANG_LEN: usize = 256;
+
+/// Execute the program at `path` with the null-terminated arrays of args and envs, where `envp_ptr` could be null.
+///
+/// A script starting with `#!interpreter [arg]` is executed by the interpreter,
+/// whose args are the interpreter, the optional arg, `path`, and the args except the first one.
+/// Return `-ENOENT` if any file is missing, `-ELOOP` if the interpreters are nested too deep,
+/// `-ENOEXEC` if the file is neither a script nor a valid executable,
+/// or `-ENOMEM` if the memory runs out even after some process is killed by `reclaim()`.
+pub fn sys_exec(path: usize, args_ptr: usize, envp_ptr: usize) -> isize {
+    let mut name = try_user!(parse_str(path.into()));
+
+    // parse args and envs, which share the space on the new user stack
+    let page_table = Processor::curr_proc().lock().page_table();
+    let mut size = 0;
+    let mut args = match read_str_array(&page_table, args_ptr, &mut size) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let envs = match read_str_array(&page_table, envp_ptr, &mut size) {
+        Ok(envs) => envs,
+        Err(err) => return err,
+    };
+    drop(page_table);
+
     let cwd = Processor::curr_proc().lock().cwd();
-    let file = open_file(cwd, &name, OpenFlags::RDONLY);
-    if let Some(file) = file {
-        infoln!(
-            "Process {} executes {}.",
-            Processor::curr_proc().pid(),
-            name
-        );
-
-        // parse args
-        let mut args = Vec::new();
-        loop {
-            let arg = {
-                let page_table = Processor::curr_proc().lock().page_table();
-                unsafe { page_table.translate_any::<usize>((args_ptr as usize).into()) }
-            };
-            if *arg == 0 {
+    let mut file = None;
+    for depth in 0..=MAX_INTERP_DEPTH {
+        let script = match open_file(cwd.clone(), &name, OpenFlags::RDONLY) {
+            Some(script) => script,
+            None => {
+                warnln!(
+                    "Process {} fails to execute {}.",
+                    Processor::curr_proc().pid(),
+                    name
+                );
+                return -ENOENT;
+            }
+        };
+        let (interp, arg) = match parse_shebang(&script) {
+            Ok(Some(shebang)) => shebang,
+            Ok(None) => {
+                file = Some(script);
                 break;
             }
-            let mut str = unsafe { parse_str((*arg).into()) };
-            str.push('\0');
-            args.push(str);
-            args_ptr = unsafe { args_ptr.add(1) };
+            Err(err) => return err,
+        };
+        if depth == MAX_INTERP_DEPTH {
+            return -ELOOP;
         }
 
-        Processor::curr_proc().exec(file, &args);
-        args.len() as isize // otherwise it would be overrided
-    } else {
-        warnln!(
-            "Process {} fails to execute {}.",
-            Processor::curr_proc().pid(),
-            name
-        );
-        -1
+        // the interpreter takes the place of the first arg
+        let mut interp_args = vec![interp.clone() + "\0"];
+        interp_args.extend(arg.map(|arg| arg + "\0"));
+        interp_args.push(name + "\0");
+        size += interp_args
+            .iter()
+            .map(|arg| arg.len() + size_of::<usize>())
+            .sum::<usize>();
+        if size > USER_STACK_SIZE / 2 {
+            return -E2BIG;
+        }
+        interp_args.extend(args.drain(..).skip(1));
+        args = interp_args;
+        name = interp;
+    }
+    let file = file.unwrap();
+
+    infoln!(
+        "Process {} executes {}.",
+        Processor::curr_proc().pid(),
+        name
+    );
+    loop {
+        match Processor::curr_proc().exec(&Processor::curr_task(), &file, &args, &envs) {
+            Ok(()) => return args.len() as isize, // otherwise it would be overrided
+            Err(err) if err == -ENOMEM && reclaim() => {}
+            Err(err) => return err,
+        }
+    }
+}
+
+/// Parse the `#!interpreter [arg]` line at the beginning of `file`, where everything after the interpreter is one arg.
+///
+/// Return `None` if `file` is not a script, or `-ENOEXEC` if the interpreter is missing.
+fn parse_shebang(file: &File<BlkDev>) -> Result<Option<(String, Option<String>)>, isize> {
+    let mut buf = vec![0u8; min(file.lock().size(), MAX_SHEBANG_LEN)];
+    file.lock().read_at(&mut buf, 0);
+    if !buf.starts_with(b"#!") {
+        return Ok(None);
+    }
+
+    let line = buf[2..].split(|c| *c == b'\n').next().unwrap();
+    let line = core::str::from_utf8(line).map_err(|_| -ENOEXEC)?.trim();
+    let (interp, arg) = match line.split_once(|c: char| c == ' ' || c == '\t') {
+        Some((interp, arg)) => (interp, Some(arg.trim())),
+        None => (line, None),
+    };
+    if interp.is_empty() {
+        return Err(-ENOEXEC);
+    }
+    Ok(Some((
+        String::from(interp),
+        arg.filter(|arg| !arg.is_empty()).map(String::from),
+    )))
+}
+
+/// Read the null-terminated array of strings at `ptr` from user, where each string keeps its trailing '\0'.
+///
+/// `size` accumulates the space that the strings and their pointers take on the new user stack,
+/// which should leave space for the program.
+fn read_str_array(
+    page_table: &PageTable,
+    ptr: usize,
+    size: &mut usize,
+) -> Result<Vec<String>, isize> {
+    let mut strs = Vec::new();
+    if ptr == 0 {
+        return Ok(strs);
+    }
+    loop {
+        let str_ptr = ptr + strs.len() * size_of::<usize>();
+        let str_ptr: usize = page_table.read_user(str_ptr.into()).map_err(|_| -EFAULT)?;
+        if str_ptr == 0 {
+            break;
+        }
+        let mut str = page_table
+            .read_user_cstr(str_ptr.into())
+            .map_err(|_| -EFAULT)?;
+        str.push('\0');
+
+        *size += str.len() + size_of::<usize>();
+        if *size > USER_STACK_SIZE / 2 {
+            return Err(-E2BIG);
+        }
+        strs.push(str);
+    }
+    Ok(strs)
+}
+
+/// Set the personality of the process to `persona` unless it's `PER_QUERY`, returning the previous one.
+///
+/// Only `ADDR_NO_RANDOMIZE` is supported, which takes effect on the next `exec()`.
+pub fn sys_personality(persona: usize) -> isize {
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    let old = proc_guard.personality;
+    if persona != PER_QUERY {
+        if persona & !ADDR_NO_RANDOMIZE != 0 {
+            return -EINVAL;
+        }
+        proc_guard.personality = persona;
     }
+    old as isize
 }
 
 pub fn sys_getpid() -> isize {
     Processor::curr_proc().pid() as isize
 }
 
-pub fn sys_waitpid(pid: isize, exit_code_ptr: usize) -> isize {
+/// Wait for a child matching `pid` to change state, where -1 matches any child.
+///
+/// The wait status of the child is written to `status_ptr`, and its resource usage to `rusage_ptr`, if they are not null.
+/// An exited child is reaped, while a stopped one is reported only once and only if `WaitFlags::UNTRACED` is set.
+/// Return 0 if no child has changed state with `WaitFlags::NOHANG` set, or `-ECHILD` if there is no such child.
+/// The wait is interrupted with `-EINTR` if the task is woken up by a signal.
+pub fn sys_waitpid(pid: isize, status_ptr: usize, flags: u32, rusage_ptr: usize) -> isize {
+    let flags = match WaitFlags::from_bits(flags as u8) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
     let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
+    let task = Processor::curr_task();
+    let mut woken = false;
+    let (mut proc_guard, pos) = loop {
+        let mut proc_guard = proc.lock();
+        if woken {
+            proc_guard.exit_waiters.remove(&task);
+            task.discard_wakeup();
+        }
+        if !proc_guard
+            .children
+            .iter()
+            .any(|child| pid == -1 || pid as usize == child.pid())
+        {
+            return -ECHILD;
+        }
 
-    // find satisfied children
-    let result = proc_guard.children.iter().position(|child| {
-        (pid == -1 || pid as usize == child.pid()) && child.lock().proc_status == ProcStatus::Zombie
-    });
+        // find satisfied children
+        let result = proc_guard.children.iter().position(|child| {
+            if pid != -1 && pid as usize != child.pid() {
+                return false;
+            }
+            let child_guard = child.lock();
+            child_guard.proc_status == ProcStatus::Zombie
+                || (flags.contains(WaitFlags::UNTRACED) && child_guard.stop_sig.is_some())
+        });
+        if let Some(pos) = result {
+            break (proc_guard, pos);
+        }
 
-    return if let Some(pos) = result {
-        let removed_proc = proc_guard.children.remove(pos);
-        unsafe {
-            *proc_guard
-                .page_table()
-                .translate_any::<isize>(exit_code_ptr.into()) = removed_proc.lock().exit_code;
+        if flags.contains(WaitFlags::NOHANG) {
+            return 0;
         }
-        removed_proc.pid() as isize
-    } else if proc_guard
-        .children
-        .iter()
-        .any(|task| pid == -1 || task.pid() == pid as usize)
-    {
-        -2
-    } else {
-        -1
+        if woken && task.lock().has_pending_sigs() {
+            return -EINTR;
+        }
+        proc_guard.exit_waiters.push(&task);
+        drop(proc_guard);
+        Processor::suspend_interruptible();
+        woken = true;
     };
+
+    let child = proc_guard.children[pos].clone();
+    let (status, times) = {
+        let mut child_guard = child.lock();
+        let status = if child_guard.proc_status == ProcStatus::Zombie {
+            child_guard.wait_status()
+        } else {
+            WaitStatus::Stopped(child_guard.stop_sig.take().unwrap() as u32)
+        };
+        (status, child_guard.times())
+    };
+    let reaped = matches!(status, WaitStatus::Exited(_) | WaitStatus::Signaled(..));
+    if reaped {
+        proc_guard.children.remove(pos);
+    }
+    let page_table = proc_guard.page_table();
+    drop(proc_guard);
+
+    let written = (status_ptr == 0
+        || page_table
+            .write_user(status_ptr.into(), &status.encode())
+            .is_ok())
+        && (rusage_ptr == 0
+            || page_table
+                .write_user(rusage_ptr.into(), &times.to_rusage())
+                .is_ok());
+    let mut proc_guard = proc.lock();
+    if !written {
+        // keep the child for the next try
+        if reaped {
+            proc_guard.children.push(child);
+        }
+        return -EFAULT;
+    }
+    if reaped {
+        proc_guard.dead_times += times;
+    }
+    child.pid() as isize
 }
 
+/// Return from the innermost handler, restoring the context and the mask saved in its frame.
+///
+/// The frame is tracked by the task instead of found by `sp`, so a handler could also call it directly.
 #[no_mangle]
 pub fn sys_sigreturn() -> isize {
     let task = Processor::curr_task();
+    let (frame_ptr, page_table) = {
+        let task_guard = task.lock();
+        (task_guard.sig_frame, task_guard.page_table())
+    };
+    if frame_ptr == 0 {
+        return -EINVAL;
+    }
+    // the frame is read without holding the lock of the task, since the page might fault and lock the process
+    let frame: SignalFrame = try_user!(page_table.read_user(frame_ptr.into()));
     let mut task_guard = task.lock();
-    task_guard.sig_handling.take();
-    *task_guard.trap_ctx_mut() = task_guard.trap_ctx_backup.take().unwrap();
+    frame.restore(&mut task_guard);
     task_guard.trap_ctx().a0() as isize
 }
 
-pub fn sys_kill(pid: usize, sig: usize) -> isize {
-    let target = PROC_MANAGER.get(pid);
-    if let Some(proc) = target {
-        proc.kill(SignalFlags::from_bits(1 << sig).unwrap());
+/// Send the signal `sig` to the process `pid` or to a group of processes.
+///
+/// Just like Linux, `pid` 0 means the group of the caller, -1 means every process except the initial one and the caller,
+/// and any other negative `pid` means the group `-pid`.
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    if sig >= NUM_SIGNAL {
+        return -EINVAL;
+    }
+    let proc = Processor::curr_proc();
+    let info = SigInfo::new(sig as u32, SI_USER, proc.pid(), 0);
+    let sig = SignalFlags::from_bits(1 << sig).unwrap();
+    let found = match pid {
+        0 => {
+            let pgid = proc.lock().pgid;
+            PROC_MANAGER.send_group(pgid, sig, info)
+        }
+        -1 => {
+            let targets: Vec<Arc<Proc>> = PROC_MANAGER
+                .all()
+                .into_iter()
+                .filter(|target| target.pid() != INITPROC.pid() && target.pid() != proc.pid())
+                .collect();
+            targets.iter().for_each(|target| target.send(sig, info));
+            !targets.is_empty()
+        }
+        pid if pid < 0 => PROC_MANAGER.send_group(pid.unsigned_abs(), sig, info),
+        pid => match PROC_MANAGER.get(pid as usize) {
+            Some(target) => {
+                target.send(sig, info);
+                true
+            }
+            None => false,
+        },
+    };
+    if found {
         0
     } else {
-        -1
+        -ESRCH
     }
 }
 
 pub fn sys_sigaction(sig_id: usize, new_action_ptr: usize, old_action_ptr: usize) -> isize {
     if new_action_ptr == 0
         || old_action_ptr == 0
+        || sig_id >= NUM_SIGNAL
         || sig_id == SIGKILL as usize
         || sig_id == SIGSTOP as usize
         || sig_id == SIGCONT as usize
@@ -138,13 +461,22 @@ pub fn sys_sigaction(sig_id: usize, new_action_ptr: usize, old_action_ptr: usize
     }
 
     let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    let page_table = proc_guard.page_table();
-    let new_action = unsafe { page_table.translate_any::<SignalAction>(new_action_ptr.into()) };
-    let old_action = unsafe { page_table.translate_any::<SignalAction>(old_action_ptr.into()) };
+    let page_table = proc.lock().page_table();
+    let mut new_action: SignalAction = try_user!(page_table.read_user(new_action_ptr.into()));
+    new_action.set_mask(new_action.mask().maskable());
+    let old_action = proc.lock().sig_actions[sig_id];
+    try_user!(page_table.write_user(old_action_ptr.into(), &old_action));
 
-    *old_action = proc_guard.sig_actions[sig_id];
-    proc_guard.sig_actions[sig_id] = *new_action;
+    let mut proc_guard = proc.lock();
+    proc_guard.sig_actions[sig_id] = new_action;
+    // just like Linux, the pending signal is discarded once it's ignored
+    if proc_guard.ignores(sig_id) {
+        let sig = SignalFlags::from_bits(1 << sig_id).unwrap();
+        proc_guard.sigs.remove(sig);
+        for task in proc_guard.tasks.iter() {
+            task.lock().sigs.remove(sig);
+        }
+    }
     0
 }
 
@@ -153,29 +485,249 @@ pub fn sys_sigprocmask(mask: u32) -> isize {
     let mut task_guard = task.lock();
     if let Some(mask) = SignalFlags::from_bits(mask) {
         let old_mask = task_guard.sig_mask;
-        task_guard.sig_mask = mask;
+        task_guard.sig_mask = mask.maskable();
         old_mask.bits() as isize
     } else {
         -1
     }
 }
 
-pub fn sys_mutex_create(blocked: bool) -> isize {
+/// Send the signal `sig` to the thread `tid` of the process `pid`.
+pub fn sys_tgkill(pid: usize, tid: usize, sig: usize) -> isize {
+    if sig >= NUM_SIGNAL {
+        return -EINVAL;
+    }
+    let info = SigInfo::new(sig as u32, SI_TKILL, Processor::curr_proc().pid(), 0);
+    let sig = SignalFlags::from_bits(1 << sig).unwrap();
+    let target = PROC_MANAGER.get(pid).and_then(|proc| {
+        let proc_guard = proc.lock();
+        let ignored = proc_guard.ignores(info.signo as usize);
+        proc_guard
+            .tasks
+            .iter()
+            .find(|task| {
+                let task_guard = task.lock();
+                task_guard.tid() == tid && task_guard.task_status != TaskStatus::Zombie
+            })
+            .map(|task| (task.clone(), ignored))
+    });
+    match target {
+        Some((task, ignored)) => {
+            // an ignored signal is discarded just like `kill()`
+            if !ignored {
+                task.send(sig, info);
+            }
+            0
+        }
+        None => -ESRCH,
+    }
+}
+
+/// Set the alternate signal stack to the one at `new_ptr` and get the previous one into `old_ptr`,
+/// where either could be 0 to skip it.
+///
+/// The stack could not be changed while a handler is running on it.
+pub fn sys_sigaltstack(new_ptr: usize, old_ptr: usize) -> isize {
+    let task = Processor::curr_task();
+    let page_table = task.lock().page_table();
+    let (mut old_stack, on_stack) = {
+        let task_guard = task.lock();
+        let sig_stack = task_guard.sig_stack;
+        (
+            sig_stack,
+            sig_stack.contains(task_guard.trap_ctx().user_sp()),
+        )
+    };
+
+    if new_ptr != 0 {
+        if on_stack {
+            return -EPERM;
+        }
+        let new_stack: SignalStack = try_user!(page_table.read_user(new_ptr.into()));
+        let new_stack = match new_stack.flags {
+            0 if new_stack.size < MIN_SIG_STACK_SIZE => return -ENOMEM,
+            0 => new_stack,
+            SS_DISABLE => SignalStack::disabled(),
+            _ => return -EINVAL,
+        };
+        task.lock().sig_stack = new_stack;
+    }
+
+    if old_ptr != 0 {
+        if on_stack {
+            old_stack.flags |= SS_ONSTACK;
+        }
+        try_user!(page_table.write_user(old_ptr.into(), &old_stack));
+    }
+    0
+}
+
+/// Get the signals that are pending on the thread or on the process.
+pub fn sys_sigpending() -> isize {
     let proc = Processor::curr_proc();
+    let proc_guard = proc.lock();
+    let task = Processor::curr_task();
+    let sigs = task.lock().sigs | proc_guard.sigs;
+    sigs.bits() as isize
+}
+
+/// Replace the signal mask with `mask` and wait until a signal that is not masked arrives, then fail with `EINTR`.
+///
+/// The original mask is restored after the signal is delivered on the way back to user mode.
+pub fn sys_sigsuspend(mask: u32) -> isize {
+    let mask = match SignalFlags::from_bits(mask) {
+        Some(mask) => mask.maskable(),
+        None => return -EINVAL,
+    };
+    let proc = Processor::curr_proc();
+    let task = Processor::curr_task();
+    {
+        let mut task_guard = task.lock();
+        let old_mask = mem::replace(&mut task_guard.sig_mask, mask);
+        task_guard.sig_suspended.get_or_insert(old_mask);
+    }
+
+    loop {
+        {
+            // the signals pending on the process are checked with the lock held, so that they would never be missed
+            let proc_guard = proc.lock();
+            let task_guard = task.lock();
+            if !((task_guard.sigs | proc_guard.sigs) & !mask).is_empty() {
+                break;
+            }
+        }
+        Processor::suspend_interruptible();
+    }
+    -EINTR
+}
+
+/// Move the process `pid` into the group `pgid`, where 0 means the caller for `pid` and the process itself for `pgid`.
+///
+/// The process should be the caller or one of its children, and it should not lead a session.
+/// The group should be in the same session unless it's newly created.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let proc = Processor::curr_proc();
+    let target = if pid == 0 || pid == proc.pid() {
+        proc.clone()
+    } else {
+        let child = proc
+            .lock()
+            .children
+            .iter()
+            .find(|child| child.pid() == pid)
+            .cloned();
+        match child {
+            Some(child) => child,
+            None => return -ESRCH,
+        }
+    };
+    let pgid = if pgid == 0 { target.pid() } else { pgid };
+    let sid = proc.lock().sid;
+
+    let target_sid = target.lock().sid;
+    if target_sid != sid || target_sid == target.pid() {
+        return -EPERM;
+    }
+    if pgid != target.pid()
+        && !PROC_MANAGER
+            .group(pgid)
+            .iter()
+            .any(|member| member.lock().sid == sid)
+    {
+        return -EPERM;
+    }
+    target.lock().pgid = pgid;
+    0
+}
+
+/// Get the group of the process `pid`, where 0 means the caller.
+pub fn sys_getpgid(pid: usize) -> isize {
+    match target_proc(pid) {
+        Some(target) => target.lock().pgid as isize,
+        None => -ESRCH,
+    }
+}
+
+/// Get the session of the process `pid`, where 0 means the caller.
+pub fn sys_getsid(pid: usize) -> isize {
+    match target_proc(pid) {
+        Some(target) => target.lock().sid as isize,
+        None => -ESRCH,
+    }
+}
+
+/// Create a new session led by the caller, which also leads a new group in it.
+///
+/// Fail with `EPERM` if the caller already leads a group.
+pub fn sys_setsid() -> isize {
+    let proc = Processor::curr_proc();
+    let pid = proc.pid();
+    if !PROC_MANAGER.group(pid).is_empty() {
+        return -EPERM;
+    }
     let mut proc_guard = proc.lock();
-    proc_guard.lock_table.alloc(Arc::new(if blocked {
+    proc_guard.pgid = pid;
+    proc_guard.sid = pid;
+    pid as isize
+}
+
+/// Write the interval timer `which` to `curr_ptr`, where the value is the time until it fires.
+pub fn sys_getitimer(which: usize, curr_ptr: usize) -> isize {
+    if which > ITIMER_PROF {
+        return -EINVAL;
+    }
+    let proc = Processor::curr_proc();
+    let (itimer, page_table) = {
+        let proc_guard = proc.lock();
+        (proc_guard.itimers[which], proc_guard.page_table())
+    };
+    let curr = itimer.to_user(which == ITIMER_REAL);
+    try_user!(page_table.write_user(curr_ptr.into(), &curr));
+    0
+}
+
+/// Set the interval timer `which` to the `ITimerVal` at `new_ptr`, writing the old one to `old_ptr` if it's not null.
+///
+/// The timer is disarmed if the new value is zero.
+/// When the real timer fires, it's reloaded with the interval and `SIGALRM` is posted to the process.
+/// The virtual and profiling timers count down the time in user mode and on CPU, posting `SIGVTALRM` and `SIGPROF`.
+pub fn sys_setitimer(which: usize, new_ptr: usize, old_ptr: usize) -> isize {
+    if which > ITIMER_PROF {
+        return -EINVAL;
+    }
+    let proc = Processor::curr_proc();
+    let page_table = proc.lock().page_table();
+    let new: ITimerVal = try_user!(page_table.read_user(new_ptr.into()));
+    if !new.interval.is_valid() || !new.value.is_valid() {
+        return -EINVAL;
+    }
+
+    let is_real = which == ITIMER_REAL;
+    let itimer = ITimer::from_user(&new, is_real);
+    let old = mem::replace(&mut proc.lock().itimers[which], itimer);
+    if is_real && itimer.value() != 0 {
+        // the entry of the old timer would be ignored since it doesn't match the new value
+        TIMER.subscribe_alarm(itimer.value(), &proc);
+    }
+
+    if old_ptr != 0 {
+        try_user!(page_table.write_user(old_ptr.into(), &old.to_user(is_real)));
+    }
+    0
+}
+
+pub fn sys_mutex_create(blocked: bool) -> isize {
+    let lock = Arc::new(if blocked {
         Lockable::BlockMutex(BlockLock::new())
     } else {
         Lockable::SpinMutex(SpinLock::new())
-    })) as isize
+    });
+    let id = Processor::curr_proc().lock().lock_table.lock().alloc(lock);
+    id as isize
 }
 
 pub fn sys_mutex_lock(id: usize) -> isize {
-    let lock = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.lock_table.get(id)
-    };
+    let lock = Processor::curr_proc().lock().lock_table.lock().get(id);
     if let Some(lock) = lock {
         lock.lock();
         0
@@ -185,11 +737,7 @@ pub fn sys_mutex_lock(id: usize) -> isize {
 }
 
 pub fn sys_mutex_unlock(id: usize) -> isize {
-    let lock = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.lock_table.get(id)
-    };
+    let lock = Processor::curr_proc().lock().lock_table.lock().get(id);
     if let Some(lock) = lock {
         lock.unlock();
         0
@@ -199,19 +747,16 @@ pub fn sys_mutex_unlock(id: usize) -> isize {
 }
 
 pub fn sys_semaphore_create(counter: usize) -> isize {
-    let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    proc_guard
+    let id = Processor::curr_proc()
+        .lock()
         .sema_table
-        .alloc(Arc::new(Semaphore::new(counter))) as isize
+        .lock()
+        .alloc(Arc::new(Semaphore::new(counter)));
+    id as isize
 }
 
 pub fn sys_semaphore_down(id: usize) -> isize {
-    let sema = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.sema_table.get(id)
-    };
+    let sema = Processor::curr_proc().lock().sema_table.lock().get(id);
     if let Some(sema) = sema {
         sema.down();
         0
@@ -221,11 +766,7 @@ pub fn sys_semaphore_down(id: usize) -> isize {
 }
 
 pub fn sys_semaphore_up(id: usize) -> isize {
-    let sema = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.sema_table.get(id)
-    };
+    let sema = Processor::curr_proc().lock().sema_table.lock().get(id);
     if let Some(sema) = sema {
         sema.up();
         0
@@ -235,22 +776,21 @@ pub fn sys_semaphore_up(id: usize) -> isize {
 }
 
 pub fn sys_condvar_create() -> isize {
-    let proc = Processor::curr_proc();
-    let mut proc_guard = proc.lock();
-    proc_guard.condvar_table.alloc(Arc::new(Observable::new())) as isize
+    let id = Processor::curr_proc()
+        .lock()
+        .condvar_table
+        .lock()
+        .alloc(Arc::new(Observable::new()));
+    id as isize
 }
 
 pub fn sys_condvar_wait(condvar_id: usize, lock_id: usize) -> isize {
-    let condvar = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.condvar_table.get(condvar_id)
-    };
-    let lock = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.lock_table.get(lock_id)
-    };
+    let condvar = Processor::curr_proc()
+        .lock()
+        .condvar_table
+        .lock()
+        .get(condvar_id);
+    let lock = Processor::curr_proc().lock().lock_table.lock().get(lock_id);
     let task = Processor::curr_task();
     if let (Some(condvar), Some(lock)) = (condvar, lock) {
         if lock.is_locked() {
@@ -267,11 +807,7 @@ pub fn sys_condvar_wait(condvar_id: usize, lock_id: usize) -> isize {
 }
 
 pub fn sys_condvar_notify_one(id: usize) -> isize {
-    let condvar = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.condvar_table.get(id)
-    };
+    let condvar = Processor::curr_proc().lock().condvar_table.lock().get(id);
     if let Some(condvar) = condvar {
         condvar.notify_one();
         0
@@ -281,11 +817,7 @@ pub fn sys_condvar_notify_one(id: usize) -> isize {
 }
 
 pub fn sys_condvar_notify_all(id: usize) -> isize {
-    let condvar = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.condvar_table.get(id)
-    };
+    let condvar = Processor::curr_proc().lock().condvar_table.lock().get(id);
     if let Some(condvar) = condvar {
         condvar.notify_all();
         0
@@ -293,3 +825,50 @@ pub fn sys_condvar_notify_all(id: usize) -> isize {
         -1
     }
 }
+
+/// Wait on or wake up the tasks waiting on the 32-bit word at `addr`, which is keyed by its physical address.
+///
+/// `FUTEX_WAIT` sleeps only if the word still holds `val`, for at most the `TimeSpec` at `timeout_ptr` unless it's null.
+/// `FUTEX_WAKE` wakes up at most `val` tasks, returning how many are woken up.
+pub fn sys_futex(addr: usize, op: usize, val: usize, timeout_ptr: usize) -> isize {
+    if addr % size_of::<u32>() != 0 {
+        return -EINVAL;
+    }
+    let page_table = Processor::curr_proc().lock().page_table();
+    // the word is only read, so a read-only mapping is enough,
+    // while a writable page is still copied first if it's shared with copy-on-write, so that the key stays the same
+    let addr = try_user!(page_table
+        .translate_user(addr.into(), true)
+        .or_else(|_| page_table.translate_user(addr.into(), false)));
+
+    match op {
+        FUTEX_WAIT => {
+            let deadline = if timeout_ptr != 0 {
+                let timeout: TimeSpec = try_user!(page_table.read_user(timeout_ptr.into()));
+                if !timeout.is_valid() {
+                    return -EINVAL;
+                }
+                Some(get_time() + ns_to_ticks(timeout.as_ns()))
+            } else {
+                None
+            };
+            match FUTEX_TABLE.wait(addr, val as u32, deadline) {
+                FutexWait::Woken => 0,
+                FutexWait::Mismatched => -EAGAIN,
+                FutexWait::TimedOut => -ETIMEDOUT,
+                FutexWait::Interrupted => -EINTR,
+            }
+        }
+        FUTEX_WAKE => FUTEX_TABLE.wake(addr, val) as isize,
+        _ => -ENOSYS,
+    }
+}
+
+/// Get the process `pid`, where 0 means the caller.
+fn target_proc(pid: usize) -> Option<Arc<Proc>> {
+    if pid == 0 {
+        Some(Processor::curr_proc())
+    } else {
+        PROC_MANAGER.get(pid)
+    }
+}
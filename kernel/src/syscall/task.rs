@@ -1,67 +1,155 @@
+use alloc::sync::Arc;
+use fosix::{
+    errno::{ECHILD, EFAULT, EINTR, EINVAL, ESRCH},
+    syscall::WaitFlags,
+    time::TimeSpec,
+};
+
 use crate::{
-    config::CLOCK_FREQ,
     task::{
-        processor::{Processor, PROCESSORS},
-        task::TaskStatus,
+        processor::Processor,
+        task::{Task, TaskStatus},
         timer::TIMER,
     },
-    time::get_time,
+    time::{get_time, ns_to_ticks, ticks_to_ns},
 };
 
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
-    let proc = Processor::curr_proc();
-    let task = proc.new_task(entry.into(), arg);
-
-    PROCESSORS[Processor::hart_id()].lock().push_normal(&task);
-
-    let tid = task.lock().tid();
-    infoln!("Process {} has created new thread {}", proc.pid(), tid);
-    tid as isize
-}
-
 pub fn sys_gettid() -> isize {
-    let tid = Processor::curr_proc().lock().main_task().lock().tid();
+    let tid = Processor::curr_task().lock().tid();
     tid as isize
 }
 
-pub fn sys_waittid(tid: isize, exit_code_ptr: usize) -> isize {
+/// Detach the thread `tid`, which is reaped once it exits instead of being waited for by `waittid()`.
+///
+/// Return `-ESRCH` if there's no such thread, or `-EINVAL` if it has been detached.
+pub fn sys_thread_detach(tid: usize) -> isize {
     let proc = Processor::curr_proc();
     let mut proc_guard = proc.lock();
-
-    // find satisfied children
-    let result = proc_guard.tasks.iter().position(|task| {
-        (tid == -1 || tid as usize == task.lock().tid())
-            && task.lock().task_status == TaskStatus::Zombie
-    });
-
-    return if let Some(pos) = result {
-        let removed_task = proc_guard.tasks.remove(pos);
-        unsafe {
-            *proc_guard
-                .page_table()
-                .translate_any::<isize>(exit_code_ptr.into()) = removed_task.lock().exit_code;
-        }
-        let tid = removed_task.lock().tid() as isize;
-        tid
-    } else if proc_guard
+    let pos = match proc_guard
         .tasks
         .iter()
-        .any(|task| tid == -1 || task.lock().tid() == tid as usize)
+        .position(|task| task.lock().tid() == tid)
     {
-        -2
-    } else {
-        -1
+        Some(pos) => pos,
+        None => return -ESRCH,
     };
+    let exited = {
+        let mut task_guard = proc_guard.tasks[pos].lock();
+        if task_guard.detached {
+            return -EINVAL;
+        }
+        task_guard.detached = true;
+        task_guard.task_status == TaskStatus::Zombie
+    };
+    if exited {
+        let task = proc_guard.tasks.remove(pos);
+        let times = task.lock().task_time.times();
+        proc_guard.dead_times += times;
+    }
+    0
 }
 
-/// Sleep the thread for the specified millisecond.
+/// Wait for a thread matching `tid` to exit and reap it, where -1 matches any thread other than the caller.
+/// The detached threads are never matched.
 ///
-/// In the current design, the sleep would be interrupted by a external signal SIGCONT.
-pub fn sys_sleep(time_ms: usize) -> isize {
+/// The exit code is written to `exit_code_ptr` if it's not null.
+/// Return 0 if no thread has exited with `WaitFlags::NOHANG` set, or `-ECHILD` if there is no such thread.
+/// The wait is interrupted with `-EINTR` if the task is woken up by a signal.
+pub fn sys_waittid(tid: isize, exit_code_ptr: usize, flags: u32) -> isize {
+    let flags = match WaitFlags::from_bits(flags as u8) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    let proc = Processor::curr_proc();
+    let curr_task = Processor::curr_task();
+    let mut woken = false;
+    let (mut proc_guard, pos) = loop {
+        let mut proc_guard = proc.lock();
+        if woken {
+            proc_guard.exit_waiters.remove(&curr_task);
+            curr_task.discard_wakeup();
+        }
+        let matched = |task: &Arc<Task>| {
+            let task_guard = task.lock();
+            !Arc::ptr_eq(task, &curr_task)
+                && !task_guard.detached
+                && (tid == -1 || tid as usize == task_guard.tid())
+        };
+        if !proc_guard.tasks.iter().any(matched) {
+            return -ECHILD;
+        }
+
+        // find satisfied threads
+        let result = proc_guard
+            .tasks
+            .iter()
+            .position(|task| matched(task) && task.lock().task_status == TaskStatus::Zombie);
+        if let Some(pos) = result {
+            break (proc_guard, pos);
+        }
+
+        if flags.contains(WaitFlags::NOHANG) {
+            return 0;
+        }
+        if woken && curr_task.lock().has_pending_sigs() {
+            return -EINTR;
+        }
+        proc_guard.exit_waiters.push(&curr_task);
+        drop(proc_guard);
+        Processor::suspend_interruptible();
+        woken = true;
+    };
+
+    let removed_task = proc_guard.tasks.remove(pos);
+    let page_table = proc_guard.page_table();
+    drop(proc_guard);
+
+    let (tid, exit_code, times) = {
+        let task_guard = removed_task.lock();
+        (
+            task_guard.tid(),
+            task_guard.exit_code as i32,
+            task_guard.task_time.times(),
+        )
+    };
+    if exit_code_ptr != 0
+        && page_table
+            .write_user(exit_code_ptr.into(), &exit_code)
+            .is_err()
+    {
+        // keep the task for the next try
+        proc.lock().tasks.push(removed_task);
+        return -EFAULT;
+    }
+    proc.lock().dead_times += times;
+    tid as isize
+}
+
+/// Sleep the thread for the `TimeSpec` at `req_ptr`.
+///
+/// The sleep might be interrupted by a signal, in which case the remaining time is written to `rem_ptr` if it's not null.
+pub fn sys_nanosleep(req_ptr: usize, rem_ptr: usize) -> isize {
+    let page_table = Processor::curr_proc().lock().page_table();
+    let req: TimeSpec = try_user!(page_table.read_user(req_ptr.into()));
+    if !req.is_valid() {
+        return -EINVAL;
+    }
+
+    let target_time = get_time() + ns_to_ticks(req.as_ns());
+    {
+        let task = Processor::curr_task();
+        TIMER.subscribe(target_time, &task);
+    }
+    Processor::suspend_interruptible();
+
+    TIMER.unsubscribe(&Processor::curr_task());
     let curr_time = get_time();
-    let target_time = curr_time + CLOCK_FREQ * time_ms / 1000;
-    let task = Processor::curr_task();
-    TIMER.subscribe(target_time, &task);
-    Processor::suspend();
-    0
+    if curr_time >= target_time {
+        return 0;
+    }
+    if rem_ptr != 0 {
+        let rem = TimeSpec::from_ns(ticks_to_ns(target_time - curr_time));
+        try_user!(page_table.write_user(rem_ptr.into(), &rem));
+    }
+    -EINTR
 }
@@ -1,6 +1,7 @@
-use core::arch::asm;
+use core::{arch::asm, hint::spin_loop, mem};
 
 use alloc::sync::{Arc, Weak};
+use fosix::time::ITIMER_PROF;
 use lazy_static::lazy_static;
 use spin::Spin;
 
@@ -66,10 +67,16 @@ impl Processor {
     /// Yield the task.
     ///
     /// It's not like the `suspend()', because it would be put into the task manager when called.
+    /// The task is marked as ready, so that it's told apart from one that is woken up while switching out.
     pub fn yield_now() {
         {
             let task = Processor::curr_task();
-            task.lock().task_time.runout();
+            let mut task_guard = task.lock();
+            task_guard.task_time.runout();
+            // the task might have exited with its process on another hart
+            if task_guard.task_status == TaskStatus::Running {
+                task_guard.task_status = TaskStatus::Ready;
+            }
         }
         Processor::switch();
     }
@@ -78,10 +85,44 @@ impl Processor {
     ///
     /// When `suspend()` is called, the task would never be put into the task manager again.
     /// There should be other structure that holds the task, and it should wake up the task when needed.
+    /// It doesn't sleep at all if the task has been woken up since it's put there.
     pub fn suspend() {
         {
             let task = Processor::curr_task();
-            task.lock().task_status = TaskStatus::Stopped;
+            let mut task_guard = task.lock();
+            if mem::take(&mut task_guard.woken) {
+                return;
+            }
+            task_guard.task_status = TaskStatus::Blocked;
+        }
+        Processor::switch();
+    }
+
+    /// Suspend the task like `suspend()`, while it could also be woken up by `SIGKILL` or a signal that is not masked.
+    ///
+    /// It doesn't sleep at all if such a signal is pending, or if it has been woken up like `suspend()`.
+    /// Hence the caller should check the signals after it returns, and remove the task from where it waits if necessary.
+    pub fn suspend_interruptible() {
+        {
+            let task = Processor::curr_task();
+            let mut task_guard = task.lock();
+            if mem::take(&mut task_guard.woken) || task_guard.has_pending_sigs() {
+                return;
+            }
+            task_guard.task_status = TaskStatus::Interruptible;
+        }
+        Processor::switch();
+    }
+
+    /// Stop the task for job control, which should have been marked as stopped by the signal handler.
+    ///
+    /// It doesn't stop if it has been continued in the meantime.
+    pub fn stop() {
+        {
+            let task = Processor::curr_task();
+            if task.lock().task_status != TaskStatus::Stopped {
+                return;
+            }
         }
         Processor::switch();
     }
@@ -129,10 +170,22 @@ impl Processor {
     ///
     /// When the next task yields, it will get into this function again.
     pub fn schedule() {
-        let task = Processor::curr_processor().lock().pop();
+        // the tasks that have exited with their processes are dropped
+        let task = Processor::curr_processor()
+            .lock()
+            .pop()
+            .filter(|(task, _, _)| task.lock().task_status != TaskStatus::Zombie);
         if let Some((task, time, _)) = task {
-            if task.lock().task_status == TaskStatus::Ready {
-                task.lock().task_status = TaskStatus::Running;
+            // the task might be woken up by others before it has switched out of another hart
+            while task.lock().on_cpu {
+                spin_loop();
+            }
+            {
+                let mut task_guard = task.lock();
+                if task_guard.task_status == TaskStatus::Ready {
+                    task_guard.task_status = TaskStatus::Running;
+                }
+                task_guard.on_cpu = true;
             }
 
             // set up rest time
@@ -148,12 +201,26 @@ impl Processor {
             extern "C" {
                 fn _switch(curr_ctx: *mut TaskContext, next_ctx: *const TaskContext);
             }
+            let switched = get_time();
             unsafe {
                 _switch(idle_task_ctx, task_ctx);
             }
 
+            // the profiling timer counts the time on CPU both in user mode and in kernel mode
+            let ran = get_time() - switched;
+            let status = {
+                let mut task_guard = task.lock();
+                task_guard.task_time.run(ran);
+                task_guard.on_cpu = false;
+                task_guard.task_status
+            };
+            if status != TaskStatus::Zombie {
+                task.proc().charge(ITIMER_PROF, ran);
+            }
+
+            // only the task that yields is put back, while the others are put by whoever wakes them up
             let mut processor = PROCESSORS[Processor::hart_id()].lock();
-            if task.lock().task_status == TaskStatus::Running {
+            if task.lock().task_status == TaskStatus::Ready {
                 processor.push_normal(&task);
             }
 
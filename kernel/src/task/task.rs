@@ -1,16 +1,18 @@
 use alloc::sync::{Arc, Weak};
-use fosix::signal::SignalFlags;
+use core::ptr;
+use fosix::signal::{SigInfo, SignalFlags, SignalStack};
 use riscv::register::sstatus::{self, SPP};
 use spin::{Spin, SpinGuard};
 
 use crate::{
+    config::NUM_SIGNAL,
     mm::{
         address::VirAddr,
         page_table::{PageTable, KERNEL_PAGE_TABLE},
     },
     proc::{
         id::Id,
-        proc::Proc,
+        proc::{Proc, ProcStatus},
         stack::{KernelStack, UserStack},
     },
     trap::{
@@ -39,13 +41,30 @@ pub struct TaskInner {
     pub task_status: TaskStatus,
     task_ctx: TaskContext,
     trap_ctx_handle: TrapCtxHandle,
-    pub trap_ctx_backup: Option<TrapCtx>,
     pub user_stack: UserStack,
     kernel_stack: KernelStack,
     pub exit_code: isize,
+    /// Whether the thread is reaped once it exits instead of being waited for by `waittid()`.
+    pub detached: bool,
     pub sigs: SignalFlags,
     pub sig_mask: SignalFlags,
-    pub sig_handling: Option<usize>,
+    /// The information of the pending signals, indexed by the signal.
+    pub sig_infos: [SigInfo; NUM_SIGNAL],
+    /// The mask to restore once a signal is delivered, which is set while the task is in `sigsuspend()`.
+    pub sig_suspended: Option<SignalFlags>,
+    /// The frame of the innermost running handler on the user stack, which is 0 if there is none.
+    pub sig_frame: usize,
+    /// The alternate stack for the handlers with `SignalActionFlags::ONSTACK`.
+    pub sig_stack: SignalStack,
+    /// The original `a0` of the syscall that has just failed with `EINTR`,
+    /// which is restarted if it's interrupted by a handler with `SignalActionFlags::RESTART`.
+    pub sig_restart: Option<usize>,
+    /// Whether the task is woken up before it goes to sleep, so that its next suspension returns at once.
+    pub woken: bool,
+    /// The subscription of the timer that could wake up the task, which is renewed every time it's subscribed.
+    pub timer_token: usize,
+    /// Whether the task is running on a hart, which is cleared only after its context is saved by the switch.
+    pub on_cpu: bool,
     pub task_time: TaskTime,
 }
 
@@ -53,6 +72,11 @@ pub struct TaskInner {
 pub enum TaskStatus {
     Ready,
     Running,
+    /// Waiting until it's woken up by what it waits for, e.g. a lock or the disk.
+    Blocked,
+    /// Waiting like `Blocked`, but it's also woken up by `SIGKILL` or a signal that is not masked.
+    Interruptible,
+    /// Stopped by a signal for job control, until it's continued by `SIGCONT` or killed by `SIGKILL`.
     Stopped,
     Zombie,
 }
@@ -66,10 +90,10 @@ impl Task {
         proc: Weak<Proc>,
         page_table: Arc<PageTable>,
         weight: usize,
-    ) -> Arc<Self> {
-        let kernel_stack = KernelStack::new(gid.id());
+    ) -> Option<Arc<Self>> {
+        let kernel_stack = KernelStack::new(gid.id())?;
         let task_ctx = TaskContext::new(restore as usize, kernel_stack.top().into());
-        let trap_ctx_handle = page_table.new_trap_ctx(tid.id());
+        let trap_ctx_handle = page_table.new_trap_ctx(tid.id())?;
         let user_stack = page_table.new_user_stack(base, tid.id());
 
         let trap_ctx = trap_ctx_handle.trap_ctx_mut();
@@ -84,7 +108,7 @@ impl Task {
             KERNEL_PAGE_TABLE.to_satp(),
         );
 
-        Arc::new(Self {
+        Some(Arc::new(Self {
             inner: Spin::new(TaskInner {
                 tid,
                 gid,
@@ -92,17 +116,24 @@ impl Task {
                 task_status: TaskStatus::Running,
                 task_ctx,
                 trap_ctx_handle,
-                trap_ctx_backup: None,
                 user_stack,
                 kernel_stack,
                 exit_code: 0,
+                detached: false,
                 sigs: SignalFlags::empty(),
                 sig_mask: SignalFlags::empty(),
-                sig_handling: None,
+                sig_infos: [SigInfo::default(); NUM_SIGNAL],
+                sig_suspended: None,
+                sig_frame: 0,
+                sig_stack: SignalStack::disabled(),
+                sig_restart: None,
+                woken: false,
+                timer_token: 0,
+                on_cpu: false,
                 task_time: TaskTime::new(weight),
             }),
             proc,
-        })
+        }))
     }
 
     pub fn renew(
@@ -112,18 +143,21 @@ impl Task {
         proc: Weak<Proc>,
         page_table: Arc<PageTable>,
         weight: usize,
-    ) -> Arc<Self> {
+    ) -> Option<Arc<Self>> {
         assert_eq!(self.lock().tid.id(), tid.id());
-        let kernel_stack = KernelStack::new(gid.id());
+        let kernel_stack = KernelStack::new(gid.id())?;
         let task_ctx = TaskContext::new(restore as usize, kernel_stack.top().into());
-        let trap_ctx_handle = self.lock().trap_ctx_handle.renew(&page_table);
-        let trap_ctx_backup = self.lock().trap_ctx_backup.clone();
-        let user_stack = self.lock().user_stack.renew(&page_table);
+        let trap_ctx_handle = self.lock().trap_ctx_handle.renew(&page_table)?;
+        let (sig_mask, sig_frame, sig_stack) = {
+            let task = self.lock();
+            (task.sig_mask, task.sig_frame, task.sig_stack)
+        };
+        let user_stack = self.lock().user_stack.renew(&page_table)?;
 
         let trap_ctx = trap_ctx_handle.trap_ctx_mut();
         trap_ctx.kernel_sp = kernel_stack.top().into();
 
-        Arc::new(Self {
+        Some(Arc::new(Self {
             inner: Spin::new(TaskInner {
                 tid,
                 gid,
@@ -131,25 +165,40 @@ impl Task {
                 task_status: TaskStatus::Running,
                 task_ctx,
                 trap_ctx_handle,
-                trap_ctx_backup,
                 user_stack,
                 kernel_stack,
                 exit_code: 0,
-                sigs: SignalFlags::empty(), // FIX: inherit signal
-                sig_mask: SignalFlags::empty(),
-                sig_handling: None,
+                detached: false,
+                sigs: SignalFlags::empty(),
+                sig_mask,
+                sig_infos: [SigInfo::default(); NUM_SIGNAL],
+                sig_suspended: None,
+                sig_frame,
+                sig_stack,
+                sig_restart: None,
+                woken: false,
+                timer_token: 0,
+                on_cpu: false,
                 task_time: TaskTime::new(weight),
             }),
             proc,
-        })
+        }))
     }
 
-    pub fn exec(&self, tid: Arc<Id>, base: VirAddr, entry: VirAddr, page_table: Arc<PageTable>) {
+    /// Reset the task to run from `entry` on `user_stack`, which has been prepared in `page_table` by the caller.
+    ///
+    /// Return `None` if the memory runs out for the trap context, in which case the task is not changed.
+    pub fn exec(
+        &self,
+        tid: Arc<Id>,
+        user_stack: UserStack,
+        entry: VirAddr,
+        page_table: Arc<PageTable>,
+    ) -> Option<()> {
+        let trap_ctx_handle = page_table.new_trap_ctx(tid.id())?;
         let mut task = self.lock();
 
         let task_ctx = TaskContext::new(restore as usize, task.kernel_stack.top().into());
-        let trap_ctx_handle = page_table.new_trap_ctx(task.tid());
-        let user_stack = page_table.new_user_stack(base, task.tid());
 
         let trap_ctx = trap_ctx_handle.trap_ctx_mut();
         let mut sstatus = sstatus::read();
@@ -164,10 +213,14 @@ impl Task {
         );
 
         task.tid = tid;
+        task.detached = false;
         task.task_ctx = task_ctx;
+        task.sig_frame = 0;
+        task.sig_stack = SignalStack::disabled();
         task.trap_ctx_handle = trap_ctx_handle;
         task.user_stack = user_stack;
         task.page_table = page_table;
+        Some(())
     }
 
     pub fn lock(&self) -> SpinGuard<TaskInner> {
@@ -189,34 +242,95 @@ impl Task {
     /// It's the companion method with `suspend()`.
     /// When the `suspend()` is called, the caller is reponsible to maintain the task elsewhere.
     /// Then the caller should wake up the task by calling this function, which would put the task into task manager again.
+    ///
+    /// Only a blocked task is put into the task manager, so a task is never put twice by different wakers.
+    /// A running task is about to sleep after it's put where it waits, whose next suspension returns at once instead.
+    /// The task is left alone in any other state, e.g. stopped or exited.
     pub fn wakeup(self: &Arc<Self>) {
-        self.lock().task_status = TaskStatus::Running;
-        Processor::curr_processor().lock().push_realtime(self);
+        self.wake(self.lock());
+    }
+
+    /// Wake up the task like `wakeup()` if it's still waiting for the subscription of timer with `token`.
+    pub fn wakeup_timer(self: &Arc<Self>, token: usize) {
+        let task = self.lock();
+        if task.timer_token == token {
+            self.wake(task);
+        }
+    }
+
+    fn wake(self: &Arc<Self>, mut task: SpinGuard<TaskInner>) {
+        match task.task_status {
+            TaskStatus::Blocked | TaskStatus::Interruptible => {
+                task.task_status = TaskStatus::Running;
+                drop(task);
+                Processor::curr_processor().lock().push_realtime(self);
+            }
+            TaskStatus::Running => task.woken = true,
+            _ => {}
+        }
+    }
+
+    /// Forget the wakeup that comes after the task stops waiting, e.g. when it's interrupted by a signal,
+    /// which should be called once the task is removed from where it waits.
+    pub fn discard_wakeup(&self) {
+        self.lock().woken = false;
     }
 
+    /// Exit the thread, which ends the process with `exit_code` if it's the last running thread.
+    ///
+    /// A detached thread is reaped at once, while the others are kept until they are waited for by `waittid()`.
     pub fn exit(&self, exit_code: isize) {
         let mut guard = self.lock();
         guard.task_status = TaskStatus::Zombie;
         guard.exit_code = exit_code;
+        let (tid, detached, times) = (guard.tid(), guard.detached, guard.task_time.times());
+        drop(guard);
+
+        // the process might have exited and even been reaped already
+        let proc = match self.proc.upgrade() {
+            Some(proc) => proc,
+            None => return,
+        };
+        let mut proc_guard = proc.lock();
+        if proc_guard.proc_status == ProcStatus::Zombie {
+            return;
+        }
         infoln!(
             "Process {} thread {} exits with code {}.",
-            self.proc().pid(),
-            guard.tid(),
+            proc.pid(),
+            tid,
             exit_code
         );
-
-        // in case that it's the main thread
-        if guard.tid() == 1 {
-            self.proc().exit(exit_code);
+        let last = proc_guard.tasks.iter().all(|task| {
+            ptr::eq(Arc::as_ptr(task), self) || task.lock().task_status == TaskStatus::Zombie
+        });
+        if last {
+            drop(proc_guard);
+            proc.exit(exit_code);
+            return;
         }
+        if detached {
+            proc_guard
+                .tasks
+                .retain(|task| !ptr::eq(Arc::as_ptr(task), self));
+            proc_guard.dead_times += times;
+        }
+        drop(proc_guard);
+        proc.notify_waiters();
     }
 }
 
 impl Task {
     /// Append the task's signal flags by locking it.
     pub fn kill(self: &Arc<Self>, sig: SignalFlags) {
+        self.send(sig, SigInfo::kernel(sig.bits().trailing_zeros()));
+    }
+
+    /// Append the task's signal flags with the information of the signal.
+    pub fn send(self: &Arc<Self>, sig: SignalFlags, info: SigInfo) {
         let mut task = self.lock();
         task.sigs |= sig;
+        task.sig_infos[info.signo as usize] = info;
 
         infoln!(
             "Process {} thread {} receives signal {}.",
@@ -225,10 +339,23 @@ impl Task {
             sig.bits()
         );
 
-        if sig.contains(SignalFlags::SIGCONT) && task.task_status == TaskStatus::Stopped {
-            infoln!("Process {} thread 1 is continued.", self.proc().pid());
+        let uncatchable = SignalFlags::SIGKILL | SignalFlags::SIGCONT;
+        if task.task_status == TaskStatus::Stopped && uncatchable.intersects(sig) {
+            infoln!(
+                "Process {} thread {} is continued.",
+                self.proc().pid(),
+                task.tid()
+            );
+            task.task_status = TaskStatus::Running;
             drop(task);
             PROCESSORS[Processor::hart_id()].lock().push_normal(self);
+        } else if task.task_status == TaskStatus::Interruptible
+            && (sig.contains(SignalFlags::SIGKILL) || !task.sig_mask.contains(sig))
+        {
+            // the task is waiting in e.g. `sigsuspend()` or `futex()`, which would fail with `EINTR`
+            task.task_status = TaskStatus::Running;
+            drop(task);
+            Processor::curr_processor().lock().push_realtime(self);
         }
     }
 
@@ -244,6 +371,11 @@ impl Task {
 }
 
 impl TaskInner {
+    /// Whether there are signals that are not masked but not handled yet.
+    pub fn has_pending_sigs(&self) -> bool {
+        !(self.sigs & !self.sig_mask).is_empty()
+    }
+
     pub fn task_ctx_ptr(&mut self) -> *mut TaskContext {
         &mut self.task_ctx
     }
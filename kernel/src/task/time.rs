@@ -1,9 +1,11 @@
-use core::cmp::max;
+use core::{cmp::max, ops::AddAssign};
+
+use fosix::{syscall::RUsage, time::TimeSpec};
 
 use crate::{
     config::{MIN_EXEC_TIME_SLICE, PELT_ATTENUATION, PELT_PERIOD},
     task::processor::Processor,
-    time::get_time,
+    time::{get_time, ticks_to_ns},
 };
 
 pub struct TaskTime {
@@ -18,6 +20,17 @@ pub struct TaskTime {
     period: usize,
     history_load: usize,
     running_load: usize,
+
+    // accounting related
+    utime: usize,
+    cpu_time: usize,
+}
+
+/// The time spent in user mode and in kernel mode, counted in CLINT ticks.
+#[derive(Clone, Copy, Default)]
+pub struct CpuTimes {
+    pub utime: usize,
+    pub stime: usize,
 }
 
 impl TaskTime {
@@ -31,6 +44,8 @@ impl TaskTime {
             period: now / PELT_PERIOD,
             history_load: 0,
             running_load: 0,
+            utime: 0,
+            cpu_time: 0,
         }
     }
 
@@ -53,11 +68,13 @@ impl TaskTime {
         self.vruntime = 0;
     }
 
-    pub fn trap(&mut self) {
+    /// Account the runtime in user mode since the last restore, which is returned.
+    pub fn trap(&mut self) -> usize {
         let now = get_time();
         let runtime = now - self.last_restore;
         self.remaining = self.remaining.saturating_sub(runtime);
         self.vruntime += (runtime + self.weight - 1) / self.weight;
+        self.utime += runtime;
 
         if pelt_period(now) == pelt_period(self.last_restore) {
             self.running_load += runtime;
@@ -73,6 +90,20 @@ impl TaskTime {
             self.running_load = now % PELT_PERIOD;
             self.period = pelt_period(now);
         }
+        runtime
+    }
+
+    /// Account the time on CPU, which is measured by the processor around switching to the task.
+    pub fn run(&mut self, ticks: usize) {
+        self.cpu_time += ticks;
+    }
+
+    /// The time on CPU except that in user mode is spent in kernel mode.
+    pub fn times(&self) -> CpuTimes {
+        CpuTimes {
+            utime: self.utime,
+            stime: self.cpu_time.saturating_sub(self.utime),
+        }
     }
 
     pub fn restore(&mut self) {
@@ -96,6 +127,22 @@ impl TaskTime {
     }
 }
 
+impl CpuTimes {
+    pub fn to_rusage(&self) -> RUsage {
+        RUsage {
+            utime: TimeSpec::from_ns(ticks_to_ns(self.utime)),
+            stime: TimeSpec::from_ns(ticks_to_ns(self.stime)),
+        }
+    }
+}
+
+impl AddAssign for CpuTimes {
+    fn add_assign(&mut self, rhs: Self) {
+        self.utime += rhs.utime;
+        self.stime += rhs.stime;
+    }
+}
+
 fn pelt_period(now: usize) -> usize {
     now / PELT_PERIOD
 }
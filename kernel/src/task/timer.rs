@@ -4,7 +4,7 @@ use alloc::{
 };
 use lazy_static::lazy_static;
 
-use crate::sync::mcs::Mcs;
+use crate::{proc::proc::Proc, sync::mcs::Mcs};
 
 use super::task::Task;
 
@@ -14,7 +14,15 @@ pub struct Timer {
 
 pub struct TimerUnit {
     time: usize,
-    task: Weak<Task>,
+    target: TimerTarget,
+}
+
+/// What to do when the time is up.
+enum TimerTarget {
+    /// Wake up the sleeping task, if it's still waiting for the subscription with the token.
+    Wakeup(Weak<Task>, usize),
+    /// Fire the real interval timer of the process, which is ignored if the timer has been reset since.
+    Alarm(Weak<Proc>),
 }
 
 lazy_static! {
@@ -30,13 +38,47 @@ impl Timer {
 }
 
 impl Timer {
+    /// Wake up `task` at `time`, unless it's unsubscribed before then.
     pub fn subscribe(&self, time: usize, task: &Arc<Task>) {
+        let token = {
+            let mut task_guard = task.lock();
+            task_guard.timer_token += 1;
+            task_guard.timer_token
+        };
+        self.tasks.lock().push(TimerUnit {
+            time,
+            target: TimerTarget::Wakeup(task.phantom(), token),
+        });
+    }
+
+    /// Fire the real interval timer of `proc` at `time`.
+    pub fn subscribe_alarm(&self, time: usize, proc: &Arc<Proc>) {
         self.tasks.lock().push(TimerUnit {
             time,
-            task: task.phantom(),
+            target: TimerTarget::Alarm(proc.phantom()),
         });
     }
 
+    /// Cancel all the subscriptions of `task`, e.g. when it's woken up by others before the time.
+    ///
+    /// The token is renewed, so that the subscription that is being fired would never wake it up again,
+    /// and the wakeup that has come since it stops waiting is discarded.
+    pub fn unsubscribe(&self, task: &Arc<Task>) {
+        {
+            let mut task_guard = task.lock();
+            task_guard.timer_token += 1;
+            task_guard.woken = false;
+        }
+        self.tasks
+            .lock()
+            .retain(|timer_unit| match &timer_unit.target {
+                TimerTarget::Wakeup(waiting, _) => {
+                    !core::ptr::eq(waiting.as_ptr(), Arc::as_ptr(task))
+                }
+                TimerTarget::Alarm(_) => true,
+            });
+    }
+
     pub fn notify(&self, time: usize) {
         let mut top = self.tasks.lock().pop();
         while let Some(timer_unit) = top {
@@ -44,8 +86,17 @@ impl Timer {
                 self.tasks.lock().push(timer_unit);
                 break;
             }
-            if let Some(task) = timer_unit.task.upgrade() {
-                task.wakeup();
+            match timer_unit.target {
+                TimerTarget::Wakeup(task, token) => {
+                    if let Some(task) = task.upgrade() {
+                        task.wakeup_timer(token);
+                    }
+                }
+                TimerTarget::Alarm(proc) => {
+                    if let Some(proc) = proc.upgrade() {
+                        proc.alarm(timer_unit.time);
+                    }
+                }
             }
             top = self.tasks.lock().pop();
         }
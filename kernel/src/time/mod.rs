@@ -1,9 +1,14 @@
-use core::arch::global_asm;
+use core::{
+    arch::global_asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use crate::{
-    config::{CLINT, CPUS, SCHED_PERIOD},
+    config::{CLINT, CLOCK_FREQ, CPUS, SCHED_PERIOD},
+    drivers::rtc::RTC,
     task::processor::Processor,
 };
+use fosix::time::{TimeSpec, NSEC_PER_SEC};
 use riscv::register::*;
 
 global_asm!(include_str!("trap.s"));
@@ -15,6 +20,14 @@ pub fn set_timer(id: usize, time: usize) {
     }
 }
 
+/// Raise the machine-mode software interrupt of the hart `id`, which is forwarded to the supervisor mode by `_timertrap`.
+pub fn send_ipi(id: usize) {
+    unsafe {
+        let msip = (CLINT + 4 * id) as *mut u32;
+        msip.write_volatile(1);
+    }
+}
+
 // The function uses read_volatile, otherwise the compiler would over-optimize it.
 pub fn get_time() -> usize {
     unsafe {
@@ -23,6 +36,35 @@ pub fn get_time() -> usize {
     }
 }
 
+/// Convert CLINT ticks into nanoseconds through `CLOCK_FREQ`, where the multiplication would not overflow.
+pub fn ticks_to_ns(ticks: usize) -> usize {
+    ticks / CLOCK_FREQ * NSEC_PER_SEC + ticks % CLOCK_FREQ * NSEC_PER_SEC / CLOCK_FREQ
+}
+
+/// Convert nanoseconds into CLINT ticks through `CLOCK_FREQ`, rounding up so that a sleep is never too short.
+pub fn ns_to_ticks(ns: usize) -> usize {
+    ns / NSEC_PER_SEC * CLOCK_FREQ
+        + (ns % NSEC_PER_SEC * CLOCK_FREQ + NSEC_PER_SEC - 1) / NSEC_PER_SEC
+}
+
+/// The real time in nanoseconds when the CLINT timer is zero, which is seeded from the RTC by `init_realtime()`.
+static BOOT_REALTIME_NS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init_realtime() {
+    let boot_ns = RTC.read_ns().saturating_sub(ticks_to_ns(get_time()));
+    BOOT_REALTIME_NS.store(boot_ns, Ordering::Relaxed);
+}
+
+/// The time since boot, which never goes backwards.
+pub fn monotonic_time() -> TimeSpec {
+    TimeSpec::from_ns(ticks_to_ns(get_time()))
+}
+
+/// The time since the Unix epoch.
+pub fn real_time() -> TimeSpec {
+    TimeSpec::from_ns(BOOT_REALTIME_NS.load(Ordering::Relaxed) + ticks_to_ns(get_time()))
+}
+
 #[link_section = ".bss.stack"]
 #[no_mangle]
 pub static mut TIMER_SCRATCH: [[usize; 5]; CPUS] = [[0; 5]; CPUS];
@@ -53,8 +95,9 @@ pub unsafe fn init_timer() {
     // enable machine-mode interrupts
     mstatus::set_mie();
 
-    // enable machine-mode timer interrupts
+    // enable machine-mode timer interrupts and software interrupts
     mie::set_mtimer();
+    mie::set_msoft();
 }
 
 /// It is spinning until time is up.
@@ -10,6 +10,19 @@ _timertrap:
     sd t1, 1*8(sp)
     sd t2, 2*8(sp)
 
+    # a software interrupt is sent by another hart, which is acknowledged and forwarded as it is
+    csrr t0, mcause
+    andi t0, t0, 0xff
+    li t1, 3
+    bne t0, t1, 1f
+    csrr t0, mhartid
+    slli t0, t0, 2
+    li t1, 0x2000000 # address of CLINT
+    add t0, t0, t1
+    sw zero, 0(t0) # clear msip
+    j 2f
+
+1:
     # setup next timer trigger
     ld t0, 3*8(sp) # address of mtimercmp
     ld t1, 4*8(sp) # timer interval      
@@ -17,6 +30,7 @@ _timertrap:
     add t2, t2, t1 # new time
     sd t2, 0(t0) # set newtime
 
+2:
     # setup timer interrupt for supervisor 
     li t0, 2
     csrw sip, t0
@@ -14,6 +14,8 @@ pub struct TrapCtx {
     pub kernel_sp: usize,
     pub kernel_pc: usize, // addr of trap handler
     pub kernel_satp: usize,
+    /// The tp of kernel, which is saved on returning to user since the task might have moved to another hart.
+    pub kernel_tp: usize,
 }
 
 /// The handle for trap context.
@@ -44,6 +46,7 @@ impl TrapCtx {
             kernel_sp,
             kernel_pc,
             kernel_satp,
+            kernel_tp: 0,
         }
     }
 
@@ -59,6 +62,18 @@ impl TrapCtx {
         &mut self.saved_regs[11]
     }
 
+    pub fn a2_mut(&mut self) -> &mut usize {
+        &mut self.saved_regs[12]
+    }
+
+    pub fn ra_mut(&mut self) -> &mut usize {
+        &mut self.saved_regs[1]
+    }
+
+    pub fn tp_mut(&mut self) -> &mut usize {
+        &mut self.saved_regs[4]
+    }
+
     pub fn user_sp(&self) -> usize {
         self.saved_regs[2]
     }
@@ -69,30 +84,30 @@ impl TrapCtx {
 }
 
 impl TrapCtxHandle {
-    pub fn new(tid: usize, page_table: &Arc<PageTable>) -> Self {
-        Self {
+    pub fn new(tid: usize, page_table: &Arc<PageTable>) -> Option<Self> {
+        Some(Self {
             tid,
             area: page_table.new_framed_area(
                 VirAddr::from(TRAMPOLINE_ADDR - PAGE_SIZE * tid).floor_to_vir_page_num(),
                 VirAddr::from(TRAMPOLINE_ADDR - PAGE_SIZE * (tid - 1)).ceil_to_vir_page_num(),
                 MappingPermission::R | MappingPermission::W,
-            ),
-        }
+            )?,
+        })
     }
 
-    pub fn renew(&self, page_table: &Arc<PageTable>) -> Self {
-        Self {
+    pub fn renew(&self, page_table: &Arc<PageTable>) -> Option<Self> {
+        Some(Self {
             tid: self.tid,
-            area: self.area.renew(page_table),
-        }
+            area: self.area.renew(page_table)?,
+        })
     }
 
     pub fn trap_ctx(&self) -> &TrapCtx {
-        unsafe { &*(usize::from(self.area.frames().first().unwrap().ppn) as *const TrapCtx) }
+        unsafe { &*(usize::from(self.area.frame(0).ppn) as *const TrapCtx) }
     }
 
     pub fn trap_ctx_mut(&self) -> &mut TrapCtx {
-        unsafe { &mut *(usize::from(self.area.frames().first().unwrap().ppn) as *mut TrapCtx) }
+        unsafe { &mut *(usize::from(self.area.frame(0).ppn) as *mut TrapCtx) }
     }
 
     pub fn trap_ctx_ptr(&self) -> usize {
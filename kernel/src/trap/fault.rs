@@ -0,0 +1,74 @@
+use fosix::signal::{
+    SigInfo, SignalFlags, BUS_ADRALN, ILL_ILLOPC, SEGV_ACCERR, SEGV_MAPERR, SIGBUS, SIGILL,
+    SIGSEGV, SIG_DFL, SIG_IGN,
+};
+use riscv::register::stval;
+
+use crate::{
+    config::USER_SPACE_END,
+    mm::{address::VirPageNum, memory::MappingPermission},
+    task::processor::Processor,
+};
+
+use super::signal::terminate;
+
+/// The handler for page faults from user mode, where `access` is the kind of the faulting access.
+///
+/// The fault is resolved by the process's memory if it's legal, otherwise `SIGSEGV` is posted.
+pub fn page_fault_handler(access: MappingPermission) {
+    let addr = stval::read();
+    let handled = addr < USER_SPACE_END
+        && Processor::curr_proc().handle_fault(VirPageNum::from(addr), access);
+
+    if !handled {
+        fault_signal(SIGSEGV as usize, SEGV_MAPERR, addr);
+    }
+}
+
+/// The handler for access faults from user mode, which are never legal.
+pub fn access_fault_handler() {
+    fault_signal(SIGSEGV as usize, SEGV_ACCERR, stval::read());
+}
+
+/// The handler for illegal instructions from user mode, where `stval` might hold the faulting instruction.
+pub fn illegal_instruction_handler() {
+    fault_signal(SIGILL as usize, ILL_ILLOPC, stval::read());
+}
+
+/// The handler for misaligned accesses from user mode, which are never legal.
+pub fn misaligned_handler() {
+    fault_signal(SIGBUS as usize, BUS_ADRALN, stval::read());
+}
+
+/// Post a signal caused by the fault at `addr` to the current task, where `code` tells the kind of the fault.
+///
+/// If the signal could not be caught by the user, the process is terminated by it directly, even if it's ignored.
+/// Otherwise, the faulting instruction would be executed again after `signal_handler()` and trap again forever.
+fn fault_signal(sigid: usize, code: i32, addr: usize) {
+    let sig = SignalFlags::from_bits(1 << sigid).unwrap();
+    let proc = Processor::curr_proc();
+    let task = Processor::curr_task();
+
+    warnln!(
+        "[trap] Process {} faults at {:#x} with sepc {:#x}.",
+        proc.pid(),
+        addr,
+        task.lock().trap_ctx().user_sepc
+    );
+
+    let catchable = {
+        // always lock proc first
+        let proc_guard = proc.lock();
+        let task_guard = task.lock();
+        let handler = proc_guard.sig_actions[sigid].handler();
+        handler != SIG_DFL && handler != SIG_IGN && !task_guard.sig_mask.contains(sig)
+    };
+
+    if catchable {
+        task.send(sig, SigInfo::new(sigid as u32, code, 0, addr));
+    } else {
+        drop(task);
+        drop(proc);
+        terminate(sigid);
+    }
+}
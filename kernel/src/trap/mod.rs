@@ -1,5 +1,10 @@
 use core::arch::asm;
 
+use fosix::{
+    errno::EINTR,
+    syscall::{SYSCALL_SIGRETURN, SYSCALL_SIGSUSPEND},
+    time::ITIMER_VIRTUAL,
+};
 use riscv::register::{satp, scause, sepc, sip, stval, utvec::TrapMode};
 
 use crate::{
@@ -9,13 +14,25 @@ use crate::{
         uart::UART,
     },
     fs::FS,
+    mm::{
+        memory::MappingPermission,
+        page_table::{leave_user, take_shootdown},
+    },
+    proc::oom::handle_oom_request,
     syscall::syscall,
     task::processor::Processor,
 };
 
-use self::{signal::signal_handler, trampoline::restore};
+use self::{
+    fault::{
+        access_fault_handler, illegal_instruction_handler, misaligned_handler, page_fault_handler,
+    },
+    signal::signal_handler,
+    trampoline::restore,
+};
 
 pub mod context;
+pub mod fault;
 pub mod signal;
 pub mod trampoline;
 
@@ -25,8 +42,11 @@ pub mod trampoline;
 /// Hence there is no supervisor mode interrupt or exception that could enter the trap handler again.
 #[no_mangle]
 pub fn trap_handler() -> ! {
+    leave_user();
+
     // yielding should be done after all the traps are handled, because the scause is not maintained.
-    Processor::curr_task().lock().task_time.trap();
+    let runtime = Processor::curr_task().lock().task_time.trap();
+    Processor::curr_proc().charge(ITIMER_VIRTUAL, runtime);
 
     set_kernel_stvec();
     let trap = scause::read().cause();
@@ -39,7 +59,10 @@ pub fn trap_handler() -> ! {
                     asm! {"csrw sip, {sip}", sip = in(reg) sip ^ 2};
                 }
 
-                Processor::yield_now();
+                // the TLB has been flushed by the trampoline, which is all that a shootdown asks for
+                if !take_shootdown() {
+                    Processor::yield_now();
+                }
             }
             scause::Interrupt::SupervisorTimer => todo!(),
             scause::Interrupt::SupervisorExternal => {
@@ -77,32 +100,31 @@ pub fn trap_handler() -> ! {
                     let mut task_guard = task.lock();
                     let trap_ctx = task_guard.trap_ctx_mut();
                     trap_ctx.user_sepc += 4; // it must be added here
-                    (
-                        trap_ctx.saved_regs[17],
-                        [
-                            trap_ctx.saved_regs[10],
-                            trap_ctx.saved_regs[11],
-                            trap_ctx.saved_regs[12],
-                        ],
-                    )
+                    let mut args = [0; 6];
+                    args.copy_from_slice(&trap_ctx.saved_regs[10..16]);
+                    (trap_ctx.saved_regs[17], args)
                 };
                 let result = syscall(id, args);
                 {
                     let task = Processor::curr_task();
                     let mut task_guard = task.lock();
                     *task_guard.trap_ctx_mut().a0_mut() = result as usize;
+                    // `sigsuspend()` always fails, and `sigreturn()` only returns the restored `a0`
+                    if result == -EINTR && id != SYSCALL_SIGSUSPEND && id != SYSCALL_SIGRETURN {
+                        task_guard.sig_restart = Some(args[0]);
+                    }
                 }
             }
-            scause::Exception::InstructionMisaligned => todo!(),
-            scause::Exception::InstructionFault => todo!(),
-            scause::Exception::IllegalInstruction => Processor::exit(-1),
+            scause::Exception::InstructionMisaligned => misaligned_handler(),
+            scause::Exception::InstructionFault => access_fault_handler(),
+            scause::Exception::IllegalInstruction => illegal_instruction_handler(),
             scause::Exception::Breakpoint => todo!(),
-            scause::Exception::LoadFault => todo!(),
-            scause::Exception::StoreMisaligned => todo!(),
-            scause::Exception::StoreFault => todo!(),
-            scause::Exception::InstructionPageFault => todo!(),
-            scause::Exception::LoadPageFault => todo!(),
-            scause::Exception::StorePageFault => todo!(),
+            scause::Exception::LoadFault => access_fault_handler(),
+            scause::Exception::StoreMisaligned => misaligned_handler(),
+            scause::Exception::StoreFault => access_fault_handler(),
+            scause::Exception::InstructionPageFault => page_fault_handler(MappingPermission::X),
+            scause::Exception::LoadPageFault => page_fault_handler(MappingPermission::R),
+            scause::Exception::StorePageFault => page_fault_handler(MappingPermission::W),
             scause::Exception::Unknown => todo!(),
             scause::Exception::VirtualSupervisorEnvCall => todo!(),
             scause::Exception::InstructionGuestPageFault => todo!(),
@@ -111,6 +133,8 @@ pub fn trap_handler() -> ! {
             scause::Exception::StoreGuestPageFault => todo!(),
         },
     }
+    // the kill asked by the kernel heap is done here, where no lock is held
+    handle_oom_request();
     signal_handler();
     restore();
 }
@@ -1,99 +1,297 @@
-use fosix::signal::SignalFlags;
+use core::mem::size_of;
+
+use fosix::signal::{SigInfo, SignalActionFlags, SignalFlags, SIGBUS, SIGSEGV, SIG_DFL, SIG_IGN};
 
 use crate::{
     config::NUM_SIGNAL,
-    task::{processor::Processor, task::TaskStatus},
+    mm::address::VirAddr,
+    proc::coredump::dump_core,
+    task::{
+        processor::Processor,
+        task::{TaskInner, TaskStatus},
+    },
 };
 
+/// The frame pushed onto the user stack before a handler runs, which is restored by `sigreturn()`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    /// The information is kept first, so that its address is the same as the frame.
+    info: SigInfo,
+    regs: [usize; 32],
+    sepc: usize,
+    /// The mask before the handler runs.
+    mask: SignalFlags,
+    /// The frame of the handler that is interrupted by this one, which is 0 if there is none.
+    prev: usize,
+}
+
+impl SignalFrame {
+    /// Restore the context and the mask of the current task from the frame, and pop it.
+    ///
+    /// The mask comes from user memory, so `SIGKILL` and `SIGSTOP` are removed from it.
+    pub fn restore(&self, task: &mut TaskInner) {
+        let trap_ctx = task.trap_ctx_mut();
+        trap_ctx.saved_regs = self.regs;
+        trap_ctx.user_sepc = self.sepc;
+        task.sig_mask = self.mask.maskable();
+        task.sig_frame = self.prev;
+    }
+}
+
 /// The handler that handles all signals.
 pub fn signal_handler() {
-    if Processor::curr_task().lock().sig_handling.is_none() {
-        loop {
-            let sigs = {
-                // always lock proc first
-                let proc = Processor::curr_proc();
-                let proc_guard = proc.lock();
-                let task = Processor::curr_task();
-                let task_guard = task.lock();
-                let sig = task_guard.sigs;
-                let sig_mask = task_guard.sig_mask;
-
-                if let Some(sig_handling) = task_guard.sig_handling {
-                    sig & !sig_mask & !proc_guard.sig_actions[sig_handling].mask()
+    // the process might have exited on another hart while the task is running
+    let exited = {
+        let task = Processor::curr_task();
+        let task_guard = task.lock();
+        if task_guard.task_status == TaskStatus::Zombie {
+            Some(task_guard.exit_code)
+        } else {
+            None
+        }
+    };
+    if let Some(exit_code) = exited {
+        Processor::exit(exit_code);
+        return;
+    }
+
+    loop {
+        let sigs = {
+            // always lock proc first
+            let proc = Processor::curr_proc();
+            let proc_guard = proc.lock();
+            let task = Processor::curr_task();
+            let task_guard = task.lock();
+            (task_guard.sigs | proc_guard.sigs) & !task_guard.sig_mask
+        };
+
+        for i in 0..NUM_SIGNAL {
+            let sig = SignalFlags::from_bits(1 << i).unwrap();
+            if sigs.contains(sig) {
+                infoln!(
+                    "Process {} receives signal {}",
+                    Processor::curr_proc().pid(),
+                    i
+                );
+                let info = take_signal(sig);
+                let uncatchable =
+                    SignalFlags::SIGKILL | SignalFlags::SIGSTOP | SignalFlags::SIGCONT;
+                let handler = if uncatchable.contains(sig) {
+                    SIG_DFL
                 } else {
-                    sig & !sig_mask
-                }
-            };
-
-            for i in 0..NUM_SIGNAL {
-                let sig = SignalFlags::from_bits(1 << i).unwrap();
-                if sigs.contains(sig) {
-                    infoln!(
-                        "Process {} receives signal {}",
-                        Processor::curr_proc().pid(),
-                        i
-                    );
-                    Processor::curr_task().lock().sigs ^= sig;
-                    if sig == SignalFlags::SIGKILL
-                        || sig == SignalFlags::SIGSTOP
-                        || sig == SignalFlags::SIGCONT
-                    {
-                        // signal is a kernel signal
-                        kernel_signal_handler(i);
-                    } else {
-                        // signal is a user signal
-                        user_signal_handler(i);
+                    Processor::curr_proc().lock().sig_actions[i].handler()
+                };
+                match handler {
+                    SIG_DFL => default_signal_handler(i),
+                    SIG_IGN => {}
+                    _ => {
+                        user_signal_handler(i, info);
 
                         // break to do the action in user mode
                         break;
                     }
                 }
             }
+        }
 
-            let status = Processor::curr_task().lock().task_status;
-            if status != TaskStatus::Stopped {
-                break;
-            }
-
-            // for stop, it yields here
-            Processor::suspend();
+        let status = Processor::curr_task().lock().task_status;
+        if status != TaskStatus::Stopped {
+            break;
         }
+
+        // for stop, it yields here
+        Processor::stop();
+    }
+
+    // the mask replaced by `sigsuspend()` is restored once the signal is delivered
+    let task = Processor::curr_task();
+    let mut task_guard = task.lock();
+    if let Some(sig_mask) = task_guard.sig_suspended.take() {
+        task_guard.sig_mask = sig_mask;
+    }
+    task_guard.sig_restart = None;
+}
+
+/// Remove the signal from the pending ones of the thread, or from those of the process if it's directed to the process.
+fn take_signal(sig: SignalFlags) -> SigInfo {
+    let sigid = sig.bits().trailing_zeros() as usize;
+    // always lock proc first
+    let proc = Processor::curr_proc();
+    let mut proc_guard = proc.lock();
+    let task = Processor::curr_task();
+    let mut task_guard = task.lock();
+    if task_guard.sigs.contains(sig) {
+        task_guard.sigs.remove(sig);
+        task_guard.sig_infos[sigid]
+    } else {
+        proc_guard.sigs.remove(sig);
+        proc_guard.sig_infos[sigid]
     }
 }
 
-/// The handler that handles all kernel signals, which should be delegated by the `signal_handler()`.
-fn kernel_signal_handler(sigid: usize) {
+/// The action taken for a signal whose handler is `SIG_DFL`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    Terminate,
+    /// Terminate the process just like `Terminate`, and dump the core as well.
+    Core,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+/// Get the default action of the signal, which follows the table of Linux.
+pub fn default_action(sigid: usize) -> DefaultAction {
     let sig = SignalFlags::from_bits(1 << sigid).unwrap();
-    match sig {
-        SignalFlags::SIGKILL => Processor::exit(-2), // yield immediately
-        SignalFlags::SIGSTOP => Processor::curr_task().lock().task_status = TaskStatus::Stopped, // do not yield immediately
-        SignalFlags::SIGCONT => {
+    let ignore =
+        SignalFlags::SIGDEF | SignalFlags::SIGCHLD | SignalFlags::SIGURG | SignalFlags::SIGWINCH;
+    let stop =
+        SignalFlags::SIGSTOP | SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU;
+    let core = SignalFlags::SIGQUIT
+        | SignalFlags::SIGILL
+        | SignalFlags::SIGTRAP
+        | SignalFlags::SIGABRT
+        | SignalFlags::SIGBUS
+        | SignalFlags::SIGFPE
+        | SignalFlags::SIGSEGV
+        | SignalFlags::SIGXCPU
+        | SignalFlags::SIGXFSZ
+        | SignalFlags::SIGSYS;
+    if ignore.contains(sig) {
+        DefaultAction::Ignore
+    } else if stop.contains(sig) {
+        DefaultAction::Stop
+    } else if sig == SignalFlags::SIGCONT {
+        DefaultAction::Continue
+    } else if core.contains(sig) {
+        DefaultAction::Core
+    } else {
+        DefaultAction::Terminate
+    }
+}
+
+/// Terminate the current process by the signal, which is reported to the parent by `waitpid()`.
+///
+/// The other threads exit as well, and the first signal that terminates the process is kept.
+/// The core is dumped by the first one if its default action asks for it.
+pub fn terminate(sigid: usize) {
+    let proc = Processor::curr_proc();
+    let first = {
+        let mut proc_guard = proc.lock();
+        let first = proc_guard.term_sig.is_none();
+        proc_guard.term_sig.get_or_insert(sigid);
+        first
+    };
+    if first && default_action(sigid) == DefaultAction::Core {
+        let dumped = dump_core(&proc, &Processor::curr_task(), sigid);
+        proc.lock().core_dumped = dumped;
+    }
+    proc.exit(-2);
+    drop(proc);
+    Processor::exit(-2) // yield immediately
+}
+
+/// The handler that takes the default action of the signal, which should be delegated by the `signal_handler()`.
+fn default_signal_handler(sigid: usize) {
+    match default_action(sigid) {
+        DefaultAction::Terminate | DefaultAction::Core => terminate(sigid),
+        DefaultAction::Ignore => {}
+        DefaultAction::Stop => {
+            Processor::curr_task().lock().task_status = TaskStatus::Stopped; // do not yield immediately
+            Processor::curr_proc().report_stop(sigid);
+        }
+        DefaultAction::Continue => {
             // the task would be waken up in other process
             assert!(Processor::curr_task().lock().task_status == TaskStatus::Running)
         }
-        _ => {
-            panic!("Unhandled kernel signal.")
-        }
     }
 }
 
-/// The handler that handles all user signals, which should be delegated by the `signal_handler()`.
-fn user_signal_handler(sigid: usize) {
-    let handler = {
-        let proc = Processor::curr_proc();
-        let proc_guard = proc.lock();
-        proc_guard.sig_actions[sigid].handler()
-    };
+/// The handler that runs the handler of the user, which should be delegated by the `signal_handler()`.
+///
+/// The context is saved into a `SignalFrame` on the user stack, or on the alternate stack if the action asks for it,
+/// and the handler returns to the restorer of the action with the frame as its stack.
+/// The frame is written without holding the lock of the task, since the page might fault and lock the process.
+/// The task is killed by `SIGSEGV` if the frame could not be written.
+fn user_signal_handler(sigid: usize, info: SigInfo) {
+    let action = Processor::curr_proc().lock().sig_actions[sigid];
+    let flags = action.flags();
 
-    if handler != 0 {
-        let task = Processor::curr_task();
+    let task = Processor::curr_task();
+    let (frame, frame_ptr, page_table) = {
         let mut task_guard = task.lock();
+        let trap_ctx = task_guard.trap_ctx();
+        let mut regs = trap_ctx.saved_regs;
+        let mut sepc = trap_ctx.user_sepc;
+        if let Some(a0) = task_guard.sig_restart.take() {
+            if flags.contains(SignalActionFlags::RESTART) {
+                // execute the `ecall` again with the original arguments
+                sepc -= 4;
+                regs[10] = a0;
+            }
+        }
+
+        let sp = regs[2];
+        let sig_stack = task_guard.sig_stack;
+        let top = if flags.contains(SignalActionFlags::ONSTACK)
+            && !sig_stack.is_disabled()
+            && !sig_stack.contains(sp)
+        {
+            sig_stack.sp + sig_stack.size
+        } else {
+            sp
+        };
 
-        assert!(task_guard.trap_ctx_backup.is_none());
-        task_guard.trap_ctx_backup = Some(task_guard.trap_ctx().clone());
-        task_guard.sig_handling = Some(sigid);
+        let frame = SignalFrame {
+            info,
+            regs,
+            sepc,
+            mask: task_guard
+                .sig_suspended
+                .take()
+                .unwrap_or(task_guard.sig_mask),
+            prev: task_guard.sig_frame,
+        };
+        let frame_ptr = match top.checked_sub(size_of::<SignalFrame>()) {
+            Some(frame_ptr) => frame_ptr & !0xf,
+            None => 0,
+        };
+        (frame, frame_ptr, task_guard.page_table())
+    };
+    if frame_ptr == 0
+        || page_table
+            .write_user(VirAddr::from(frame_ptr), &frame)
+            .is_err()
+    {
+        warnln!(
+            "[signal] Process {} fails to push the frame of signal {}.",
+            Processor::curr_proc().pid(),
+            sigid
+        );
+        drop(task);
+        terminate(SIGSEGV as usize);
+        return;
+    }
+
+    let sig = SignalFlags::from_bits(1 << sigid).unwrap();
+    let mut task_guard = task.lock();
+    task_guard.sig_frame = frame_ptr;
+    task_guard.sig_mask |= action.mask();
+    if !flags.contains(SignalActionFlags::NODEFER) {
+        task_guard.sig_mask |= sig;
+    }
 
-        task_guard.trap_ctx_mut().user_sepc = handler;
-        *task_guard.trap_ctx_mut().a0_mut() = sigid as usize;
+    let trap_ctx = task_guard.trap_ctx_mut();
+    trap_ctx.user_sepc = action.handler();
+    *trap_ctx.user_sp_mut() = frame_ptr;
+    *trap_ctx.ra_mut() = action.restorer();
+    *trap_ctx.a0_mut() = sigid;
+    if flags.contains(SignalActionFlags::SIGINFO) {
+        // the information is at the start of the frame
+        *trap_ctx.a1_mut() = frame_ptr;
+        *trap_ctx.a2_mut() = frame_ptr;
+    } else if sigid == SIGSEGV as usize || sigid == SIGBUS as usize {
+        *trap_ctx.a1_mut() = info.addr;
     }
 }
@@ -1,6 +1,7 @@
 use core::arch::{asm, global_asm};
 
 use crate::config::{MIN_EXEC_TIME_SLICE, TRAMPOLINE_ADDR};
+use crate::mm::page_table::enter_user;
 use crate::task::processor::Processor;
 use crate::trap::set_user_stvec;
 
@@ -40,6 +41,7 @@ pub fn restore() -> ! {
 
     // set the timer
     Processor::curr_task().lock().task_time.restore();
+    enter_user(user_satp);
 
     unsafe {
         asm! {
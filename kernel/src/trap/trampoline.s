@@ -14,6 +14,7 @@ _alltraps:
     csrrw sp, sscratch, sp
     sd ra, 1*8(sp) # store ra of user
     sd gp, 3*8(sp) # store gp of user
+    sd tp, 4*8(sp) # store tp of user
     .set n, 5
     .rept 27
         STORE_REG %n
@@ -26,6 +27,7 @@ _alltraps:
     csrr t1, sstatus 
     sd t0, 32*8(sp) # store sepc of user
     sd t1, 33*8(sp) # store sstatus of user
+    ld tp, 37*8(sp) # load tp of kernel, which keeps the hart id
     ld t0, 36*8(sp) # load satp of kernel 
     ld t1, 35*8(sp) # load pc of kernel
     ld sp, 34*8(sp) # load sp of kernel
@@ -40,12 +42,14 @@ _restore:
     sfence.vma
     csrw sscratch, a0
     mv sp, a0
+    sd tp, 37*8(sp) # store tp of kernel for the next trap
     ld t0, 32*8(sp) 
     ld t1, 33*8(sp)
     csrw sepc, t0
     csrw sstatus, t1
     ld ra, 1*8(sp)
     ld gp, 3*8(sp)
+    ld tp, 4*8(sp)
     .set n, 5
     .rept 27
         LOAD_REG %n
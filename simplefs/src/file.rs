@@ -86,6 +86,10 @@ impl<D: DiskManager> FileInner<D> {
         inode.trunc(self.fs.clone())
     }
 
+    pub fn perm(&self) -> FilePerm {
+        self.perm
+    }
+
     pub fn size(&self) -> usize {
         let cache = self.fs.cache_manager().get(self.myself.bid());
         let cache_guard = cache.lock();
@@ -30,6 +30,16 @@ SECTIONS
         *(.data .data.*)
         *(.sdata .sdata.*)
     }
+    .tdata : {
+        __tdata_start = .;
+        *(.tdata .tdata.*)
+        __tdata_end = .;
+    }
+    .tbss : {
+        *(.tbss .tbss.*)
+        __tbss_end = .;
+    }
+    __tls_align = MAX(ALIGNOF(.tdata), ALIGNOF(.tbss));
     .bss : {
         *(.bss .bss.*)
         *(.sbss .sbss.*)
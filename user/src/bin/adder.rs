@@ -6,7 +6,7 @@ extern crate user;
 extern crate alloc;
 
 use alloc::vec::Vec;
-use user::{exit, thread_create, waittid};
+use user::{thread_create, thread_exit, waittid};
 
 static mut A: usize = 0;
 const PER_THREAD_DEFAULT: usize = 10000;
@@ -27,7 +27,7 @@ unsafe fn f() -> ! {
     for _ in 0..PER_THREAD {
         critical_section(&mut t);
     }
-    exit(t as i32)
+    thread_exit(t as i32)
 }
 
 #[no_mangle]
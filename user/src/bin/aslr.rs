@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+#[macro_use]
+extern crate alloc;
+
+use alloc::boxed::Box;
+use fosix::syscall::ADDR_NO_RANDOMIZE;
+use user::{exec, personality};
+
+static DATA: usize = 0;
+
+/// Print where the code, the data, the stack and the heap lie, which differ between runs unless `-R` is given.
+///
+/// With `-R`, the program executes itself again without randomization.
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 && argv[1] == "-R" {
+        personality(ADDR_NO_RANDOMIZE);
+        let arg = "aslr\0";
+        exec("/aslr\0", &vec![arg.as_ptr(), core::ptr::null()]);
+        println!("[aslr] Fail to execute itself.");
+        return -1;
+    }
+
+    let stack = 0usize;
+    let heap = Box::new(0usize);
+    println!("code:  {:#x}", main as usize);
+    println!("data:  {:#x}", &DATA as *const usize as usize);
+    println!("stack: {:#x}", &stack as *const usize as usize);
+    println!("heap:  {:#x}", &*heap as *const usize as usize);
+    0
+}
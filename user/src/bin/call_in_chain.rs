@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 
-use user::{exit, semaphore_create, semaphore_down, semaphore_up, thread_create, waittid};
+use user::{semaphore_create, semaphore_down, semaphore_up, thread_create, thread_exit, waittid};
 
 #[macro_use]
 extern crate user;
@@ -15,20 +15,20 @@ static mut SEMAPHORE_BC: usize = 0;
 fn threada() {
     println!("Now, it's thread a.");
     semaphore_up(unsafe { SEMAPHORE_AB });
-    exit(0);
+    thread_exit(0);
 }
 
 fn threadb() {
     semaphore_down(unsafe { SEMAPHORE_AB });
     println!("Now, it's thread b.");
     semaphore_up(unsafe { SEMAPHORE_BC });
-    exit(0);
+    thread_exit(0);
 }
 
 fn threadc() {
     semaphore_down(unsafe { SEMAPHORE_BC });
     println!("Now, it's thread c.");
-    exit(0);
+    thread_exit(0);
 }
 
 #[no_mangle]
@@ -28,7 +28,7 @@ fn task(id: usize) {
     unsafe {
         CURRENT = 0;
     }
-    exit(0);
+    thread_exit(0);
 }
 
 #[no_mangle]
@@ -7,23 +7,27 @@ extern crate user;
 #[macro_use]
 extern crate alloc;
 
-use fosix::syscall::WaitFlags;
-use user::{exec, fork, waitpid, yield_now};
+use fosix::{
+    errno::ECHILD,
+    syscall::{WaitFlags, WaitStatus},
+};
+use user::{exec, fork, waitpid};
 
 #[no_mangle]
 fn main() {
     if fork() == 0 {
         exec("shell\0", &vec![0 as *const u8]);
     } else {
-        let mut exit_code: i32 = 0;
+        let mut status: i32 = 0;
         loop {
-            let pid = waitpid(-1, &mut exit_code, WaitFlags::empty());
+            let pid = waitpid(-1, &mut status, WaitFlags::empty());
             match pid {
-                -1 => return,
-                -2 => yield_now(),
+                pid if pid == -ECHILD => return,
+                pid if pid < 0 => continue,
                 pid => println!(
-                    "[initproc] Recycle child process {} with exit code {}.",
-                    pid, exit_code
+                    "[initproc] Recycle child process {} with {:?}.",
+                    pid,
+                    WaitStatus::decode(status)
                 ),
             }
         }
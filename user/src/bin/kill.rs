@@ -13,7 +13,11 @@ fn main(argc: usize, argv: &[&str]) {
     assert!(argc == 3);
     assert!(argv[1].starts_with("-"));
     let sig = argv[1][1..].parse::<usize>().unwrap();
-    let pid = argv[2].parse::<usize>().unwrap();
-    println!("kill process {} with {}.", pid, sig);
+    let pid = argv[2].parse::<isize>().unwrap();
+    if pid < 0 {
+        println!("kill group {} with {}.", -pid, sig);
+    } else {
+        println!("kill process {} with {}.", pid, sig);
+    }
     kill(pid, sig);
 }
@@ -9,19 +9,25 @@ extern crate alloc;
 
 use core::{
     fmt::Display,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use fosix::{
     fs::OpenFlags,
-    signal::{SignalAction, SignalFlags, SIGCHLD},
-    syscall::WaitFlags,
+    signal::{
+        SignalAction, SignalActionFlags, SignalFlags, SIGCHLD, SIGCONT, SIGINT, SIGTSTP, SIG_DFL,
+        SIG_IGN,
+    },
+    syscall::{WaitFlags, WaitStatus},
 };
 use lazy_static::lazy_static;
 use spin::Spin;
 use user::{
-    chdir, close, console, dup, exec, fork, mkdir, open, sigaction, sigreturn, waitpid, yield_now,
+    chdir, close, console, dup,
+    env::{environ, setenv},
+    exec, fork, getpgid, getpid, kill, mkdir, open, setpgid, setsid, sigaction, sigprocmask,
+    sigreturn, tcsetpgrp, waitpid, yield_now,
 };
 
 const BS: char = 8 as char;
@@ -73,30 +79,78 @@ lazy_static! {
     static ref JOBS: Arc<Spin<BTreeMap<usize, ProcState>>> = Arc::new(Spin::new(BTreeMap::new()));
 }
 
+/// The job that the shell is waiting for, which is also the foreground group of the console.
+static FOREGROUND: AtomicUsize = AtomicUsize::new(0);
 static FINISHED: AtomicBool = AtomicBool::new(false);
 
 fn sigchld_handler() {
     loop {
-        let mut exit_code = 0;
-        let pid = waitpid(-1, &mut exit_code, WaitFlags::NOHANG);
+        let mut status = 0;
+        let pid = waitpid(-1, &mut status, WaitFlags::NOHANG | WaitFlags::UNTRACED);
         if pid > 0 {
             let pid = pid as usize;
-            let res = JOBS.lock().remove(&pid);
-            if let Some(_) = res {
-                println!("[shell] Process {} exited with code {}.", pid, exit_code);
-            } else {
+            let mut jobs = JOBS.lock();
+            if !jobs.contains_key(&pid) {
                 panic!(
                     "[shell] Cannot find process {}, however, there is a sigchld.",
                     pid
                 );
             }
-            FINISHED.store(true, Ordering::SeqCst);
+            match WaitStatus::decode(status) {
+                WaitStatus::Exited(code) => {
+                    jobs.remove(&pid);
+                    println!("[shell] Process {} exited with code {}.", pid, code);
+                }
+                WaitStatus::Signaled(sig, core) => {
+                    jobs.remove(&pid);
+                    let core = if core { " (core dumped)" } else { "" };
+                    println!(
+                        "[shell] Process {} was killed by signal {}{}.",
+                        pid, sig, core
+                    );
+                }
+                WaitStatus::Stopped(sig) => {
+                    jobs.insert(pid, ProcState::Stopped);
+                    println!("[shell] Process {} was stopped by signal {}.", pid, sig);
+                }
+            }
+            drop(jobs);
+            if FOREGROUND.load(Ordering::SeqCst) == pid {
+                FINISHED.store(true, Ordering::SeqCst);
+            }
         } else {
             sigreturn();
         }
     }
 }
 
+/// Give the console to the job `pid` and wait until it exits or stops.
+///
+/// `SIGCHLD` should have been masked since the job is started or continued, so that its change is never missed.
+fn wait_foreground(pid: usize) {
+    FOREGROUND.store(pid, Ordering::SeqCst);
+    tcsetpgrp(pid);
+    sigprocmask(SignalFlags::SIGTTOU);
+    while !FINISHED.load(Ordering::SeqCst) {
+        yield_now();
+    }
+    FINISHED.store(false, Ordering::SeqCst);
+    FOREGROUND.store(0, Ordering::SeqCst);
+    tcsetpgrp(getpgid(0) as usize);
+}
+
+/// Find the job given by the arguments of `fg` or `bg`, which is the latest one by default.
+fn find_job(args: &[&str]) -> Option<usize> {
+    let jobs = JOBS.lock();
+    match args.get(1) {
+        Some(pid) => pid
+            .parse::<usize>()
+            .ok()
+            .filter(|pid| jobs.contains_key(pid)),
+        None => jobs.keys().last().copied(),
+    }
+}
+
 fn getline() -> String {
     let mut c = console::stdin().getchar();
     let mut result = String::new();
@@ -136,8 +190,22 @@ fn find_drain_once(args: &mut Vec<String>, s: &String) -> bool {
 #[no_mangle]
 fn main() {
     let mut old_action = SignalAction::default();
-    let new_action = SignalAction::new(sigchld_handler as usize, SignalFlags::empty());
+    // the read of the command line is restarted after a background job exits
+    let new_action = SignalAction::with_flags(
+        sigchld_handler as usize,
+        SignalFlags::empty(),
+        SignalActionFlags::RESTART,
+    );
     sigaction(SIGCHLD as usize, &new_action, &mut old_action);
+    // keep the shell itself from being interrupted by Ctrl-C or stopped by Ctrl-Z
+    let new_action = SignalAction::new(SIG_IGN, SignalFlags::empty());
+    sigaction(SIGINT as usize, &new_action, &mut old_action);
+    sigaction(SIGTSTP as usize, &new_action, &mut old_action);
+
+    // the shell leads its own session, and takes back the console when a job exits or stops
+    setsid();
+    sigprocmask(SignalFlags::SIGTTOU);
+    tcsetpgrp(getpgid(0) as usize);
 
     let mut cwd: Path = Path::new();
     loop {
@@ -188,6 +256,24 @@ fn main() {
                 }
             }
 
+            "export" => {
+                if args.len() == 1 {
+                    for env in environ() {
+                        println!("export {}", env);
+                    }
+                }
+                for arg in &args[1..] {
+                    let res = match arg.split_once('=') {
+                        Some((name, value)) => setenv(name, value, true),
+                        None => setenv(arg, "", false),
+                    };
+                    if res < 0 {
+                        println!("[shell] export {}: not a valid identifier", arg);
+                    }
+                }
+                println!("");
+            }
+
             "jobs" => {
                 let jobs = JOBS.lock();
                 for (pid, state) in jobs.iter() {
@@ -199,9 +285,39 @@ fn main() {
                 println!("");
             }
 
+            "fg" | "bg" => match find_job(&args) {
+                Some(pid) => {
+                    sigprocmask(SignalFlags::SIGTTOU | SignalFlags::SIGCHLD);
+                    JOBS.lock().insert(pid, ProcState::Running);
+                    kill(-(pid as isize), SIGCONT as usize);
+                    if args[0] == "fg" {
+                        wait_foreground(pid);
+                    } else {
+                        sigprocmask(SignalFlags::SIGTTOU);
+                        println!("[shell] Process {} is running in background", pid);
+                    }
+                }
+                None => println!("[shell] {}: no such job", args[0]),
+            },
+
             cmd => {
+                // the job can't be reaped before it's recorded
+                sigprocmask(SignalFlags::SIGTTOU | SignalFlags::SIGCHLD);
                 let pid = fork();
                 if pid == 0 {
+                    // every job runs in its own group, which takes the console if it's in the foreground
+                    setpgid(0, 0);
+                    // the job could be interrupted or stopped from the console, unlike the shell
+                    let mut old_action = SignalAction::default();
+                    let default_action = SignalAction::new(SIG_DFL, SignalFlags::empty());
+                    sigaction(SIGINT as usize, &default_action, &mut old_action);
+                    sigaction(SIGTSTP as usize, &default_action, &mut old_action);
+                    if !background {
+                        sigprocmask(SignalFlags::SIGTTOU);
+                        tcsetpgrp(getpid() as usize);
+                    }
+                    sigprocmask(SignalFlags::empty());
+
                     if let Some(mut input) = input {
                         input.push('\0');
                         let input_fd = open(
@@ -241,21 +357,21 @@ fn main() {
                         cargs[0] = path;
                     }
 
-                    if exec(cargs[0].as_str(), &uargs) == -1 {
+                    if exec(cargs[0].as_str(), &uargs) < 0 {
                         println!("[shell] Exec {} failed", str);
                         return;
                     }
                 } else if pid > 0 {
+                    setpgid(pid as usize, pid as usize);
                     JOBS.lock().insert(pid as usize, ProcState::Running);
                     if !background {
-                        while FINISHED.load(Ordering::SeqCst) == false {
-                            yield_now();
-                        }
-                        FINISHED.store(false, Ordering::SeqCst);
+                        wait_foreground(pid as usize);
                     } else {
+                        sigprocmask(SignalFlags::SIGTTOU);
                         println!("[shell] Process {} is running in background", pid);
                     }
                 } else {
+                    sigprocmask(SignalFlags::SIGTTOU);
                     println!("[user] Fail to exec {}", cmd);
                 }
             }
@@ -0,0 +1,48 @@
+#![no_main]
+#![no_std]
+
+use fosix::{
+    fs::OpenFlags,
+    mm::{MapFlags, MapProt},
+    syscall::WaitFlags,
+};
+use user::{close, fork, ftruncate, mmap, shm_open, shm_unlink, waitpid};
+
+#[macro_use]
+extern crate user;
+
+const NAME: &str = "shm_test\0";
+const SIZE: usize = 0x2000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let fd = shm_open(NAME, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    ftruncate(fd as usize, SIZE);
+    let addr = mmap(
+        0,
+        SIZE,
+        MapProt::READ | MapProt::WRITE,
+        MapFlags::SHARED,
+        fd as usize,
+        0,
+    );
+    assert!(addr > 0);
+    close(fd as usize);
+    let buf = unsafe { core::slice::from_raw_parts_mut(addr as *mut u32, SIZE / 4) };
+
+    let pid = fork();
+    if pid == 0 {
+        for (i, word) in buf.iter_mut().enumerate() {
+            *word = i as u32;
+        }
+        return 0;
+    }
+
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code, WaitFlags::empty());
+    assert!(buf.iter().enumerate().all(|(i, word)| *word == i as u32));
+    shm_unlink(NAME);
+    println!("[shm] Shared memory test passed.");
+    0
+}
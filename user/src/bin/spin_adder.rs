@@ -6,7 +6,7 @@ extern crate user;
 extern crate alloc;
 
 use alloc::vec::Vec;
-use user::{exit, mutex_create, mutex_lock, mutex_unlock, thread_create, waittid};
+use user::{mutex_create, mutex_lock, mutex_unlock, thread_create, thread_exit, waittid};
 
 static mut mutex: usize = 0;
 static mut A: usize = 0;
@@ -30,7 +30,7 @@ unsafe fn f() -> ! {
     for _ in 0..PER_THREAD {
         critical_section(&mut t);
     }
-    exit(t as i32)
+    thread_exit(t as i32)
 }
 
 #[no_mangle]
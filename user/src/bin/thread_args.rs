@@ -7,7 +7,7 @@ extern crate user;
 extern crate alloc;
 
 use alloc::vec::Vec;
-use user::{exit, thread_create, waittid};
+use user::{thread_create, thread_exit, waittid};
 
 struct Argument {
     pub ch: char,
@@ -19,7 +19,7 @@ fn thread_print(arg: *const Argument) -> ! {
     for _ in 0..1000 {
         print!("{}", arg.ch);
     }
-    exit(arg.rc)
+    thread_exit(arg.rc)
 }
 
 #[no_mangle]
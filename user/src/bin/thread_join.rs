@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+use user::{gettid, sleep, thread_create, thread_detach, thread_exit, thread_join};
+
+#[macro_use]
+extern crate user;
+
+#[macro_use]
+extern crate alloc;
+
+pub fn joined(arg: usize) -> ! {
+    println!("joinable thread#{} runs with {}", gettid(), arg);
+    sleep(10);
+    thread_exit(arg as i32)
+}
+
+pub fn detached() -> ! {
+    println!("detached thread#{} runs", gettid());
+    thread_exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("main thread#{}", gettid());
+    let detached = thread_create(detached as usize, 0) as usize;
+    assert_eq!(thread_detach(detached), 0);
+    assert!(thread_detach(detached) < 0);
+
+    let v = vec![
+        thread_create(joined as usize, 1) as usize,
+        thread_create(joined as usize, 2) as usize,
+    ];
+    for tid in v {
+        let mut exit_code = 0;
+        assert_eq!(thread_join(tid, Some(&mut exit_code)), 0);
+        println!("thread#{} joined with code {}", tid, exit_code);
+    }
+    sleep(10);
+    // the detached thread has been reaped
+    assert!(thread_join(detached, None) < 0);
+    println!("thread_join passed!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+use core::cell::Cell;
+
+use user::{gettid, thread_create, thread_create_on, thread_exit, waittid};
+
+#[macro_use]
+extern crate user;
+
+#[macro_use]
+extern crate alloc;
+
+const STACK_SIZE: usize = 0x2000;
+
+user::thread_local! {
+    static COUNTER: Cell<usize> = Cell::new(0);
+    static NAME: Cell<&'static str> = Cell::new("main");
+}
+
+fn count(name: &'static str, times: usize) {
+    NAME.set(name);
+    for _ in 0..times {
+        COUNTER.set(COUNTER.get() + 1);
+    }
+    println!(
+        "thread#{} {} counts {}",
+        gettid(),
+        NAME.get(),
+        COUNTER.get()
+    );
+}
+
+pub fn thread_a() -> ! {
+    count("a", 100);
+    thread_exit(COUNTER.get() as i32)
+}
+
+pub fn thread_b() -> ! {
+    count("b", 200);
+    thread_exit(COUNTER.get() as i32)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the second thread runs on a stack owned by the main thread
+    let stack = vec![0u8; STACK_SIZE];
+    let stack_top = (stack.as_ptr() as usize + STACK_SIZE) & !0xf;
+    let v = vec![
+        thread_create(thread_a as usize, 0),
+        thread_create_on(thread_b as usize, 0, stack_top),
+    ];
+    count("main", 300);
+    for tid in v.iter() {
+        let exit_code = 0;
+        waittid(*tid, &exit_code as *const i32 as usize);
+        println!("thread#{} exited with code {}", tid, exit_code);
+    }
+    assert_eq!(COUNTER.get(), 300);
+    assert_eq!(NAME.get(), "main");
+    println!("thread_local passed!");
+    0
+}
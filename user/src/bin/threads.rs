@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 
-use user::{exit, thread_create, waittid, yield_now};
+use user::{thread_create, thread_exit, waittid, yield_now};
 
 #[macro_use]
 extern crate user;
@@ -16,7 +16,7 @@ pub fn thread_a() -> ! {
             yield_now();
         }
     }
-    exit(1)
+    thread_exit(1)
 }
 
 pub fn thread_b() -> ! {
@@ -26,7 +26,7 @@ pub fn thread_b() -> ! {
             yield_now();
         }
     }
-    exit(2)
+    thread_exit(2)
 }
 
 pub fn thread_c() -> ! {
@@ -36,7 +36,7 @@ pub fn thread_c() -> ! {
             yield_now();
         }
     }
-    exit(3)
+    thread_exit(3)
 }
 
 #[no_mangle]
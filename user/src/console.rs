@@ -1,6 +1,7 @@
 use core::fmt::Write;
 
 use alloc::string::String;
+use fosix::errno::EINTR;
 
 use crate::syscall::file::{sys_read, sys_write};
 
@@ -39,9 +40,10 @@ macro_rules! println {
 }
 
 impl Stdin {
+    /// Read a char, which is read again if the read is interrupted by a signal.
     pub fn getchar(&self) -> char {
         let mut buffer: [u8; 1] = [0];
-        sys_read(STDIN, &mut buffer);
+        while sys_read(STDIN, &mut buffer) == -EINTR {}
         buffer[0] as char
     }
 
@@ -0,0 +1,99 @@
+//! The environment of the process, which is initialized from the `envp` given by the kernel,
+//! and passed to the new program by `exec()`.
+
+use alloc::{format, string::String, vec::Vec};
+use fosix::errno::EINVAL;
+use lazy_static::lazy_static;
+use spin::Spin;
+
+lazy_static! {
+    /// The variables in the form of `NAME=value`, each of which ends with `'\0'` to be passed to the kernel directly.
+    pub(crate) static ref ENVIRON: Spin<Vec<String>> = Spin::new(Vec::new());
+}
+
+/// Read the null-terminated array of C strings at `ptr`, which is empty if `ptr` is null.
+pub(crate) fn read_str_array(ptr: usize) -> Vec<&'static str> {
+    let mut strs = Vec::new();
+    if ptr == 0 {
+        return strs;
+    }
+    for i in 0.. {
+        let start =
+            unsafe { ((ptr + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
+        if start == 0 {
+            break;
+        }
+        let len = (0usize..)
+            .find(|i| unsafe { ((start + *i) as *const u8).read_volatile() == 0 })
+            .unwrap();
+        strs.push(
+            core::str::from_utf8(unsafe { core::slice::from_raw_parts(start as *const u8, len) })
+                .unwrap(),
+        );
+    }
+    strs
+}
+
+pub(crate) fn init(envp: usize) {
+    *ENVIRON.lock() = read_str_array(envp)
+        .into_iter()
+        .map(|env| {
+            let mut env = String::from(env);
+            env.push('\0');
+            env
+        })
+        .collect();
+}
+
+/// Find the variable `name`, returning the index in `environ` and its value.
+fn find<'a>(environ: &'a Vec<String>, name: &str) -> Option<(usize, &'a str)> {
+    environ.iter().enumerate().find_map(|(i, env)| {
+        let (key, value) = env.trim_end_matches('\0').split_once('=')?;
+        if key == name {
+            Some((i, value))
+        } else {
+            None
+        }
+    })
+}
+
+pub fn getenv(name: &str) -> Option<String> {
+    find(&ENVIRON.lock(), name).map(|(_, value)| String::from(value))
+}
+
+/// Set the variable `name` to `value`, which keeps the old value unless `overwrite` is set.
+///
+/// Return `-EINVAL` if `name` is empty or contains `'='`.
+pub fn setenv(name: &str, value: &str, overwrite: bool) -> isize {
+    if name.is_empty() || name.contains('=') {
+        return -EINVAL;
+    }
+    let mut environ = ENVIRON.lock();
+    let env = format!("{}={}\0", name, value);
+    match find(&environ, name) {
+        Some((i, _)) if overwrite => environ[i] = env,
+        Some(_) => {}
+        None => environ.push(env),
+    }
+    0
+}
+
+pub fn unsetenv(name: &str) -> isize {
+    if name.is_empty() || name.contains('=') {
+        return -EINVAL;
+    }
+    let mut environ = ENVIRON.lock();
+    if let Some((i, _)) = find(&environ, name) {
+        environ.remove(i);
+    }
+    0
+}
+
+/// Get all the variables in the form of `NAME=value`.
+pub fn environ() -> Vec<String> {
+    ENVIRON
+        .lock()
+        .iter()
+        .map(|env| String::from(env.trim_end_matches('\0')))
+        .collect()
+}
@@ -0,0 +1,51 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp::max,
+    ptr::null_mut,
+};
+
+use allocator::buddy_allocator::BuddyAllocator;
+
+use crate::syscall::mm::sys_sbrk;
+
+const USER_HEAP_GRANULARITY: usize = 8;
+/// The minimum size that the heap grows by each time.
+const USER_HEAP_INCREMENT: usize = 0x4000;
+
+/// The heap of user, which asks the kernel for more memory by `sbrk()` when its free lists are exhausted.
+pub struct UserHeap {
+    allocator: BuddyAllocator,
+}
+
+impl UserHeap {
+    pub const fn empty() -> Self {
+        Self {
+            allocator: BuddyAllocator::empty(USER_HEAP_GRANULARITY),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for UserHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut allocator = self.allocator.lock();
+        loop {
+            let ptr = allocator.alloc(layout);
+            if !ptr.is_null() {
+                return ptr;
+            }
+
+            // a segment twice the size always contains a block that is aligned to the size
+            let size = max(layout.size().next_power_of_two(), layout.align());
+            let increment = max(2 * size, USER_HEAP_INCREMENT);
+            let start = sys_sbrk(increment as isize);
+            if start < 0 {
+                return null_mut();
+            }
+            allocator.add_segment(start as usize, start as usize + increment);
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.allocator.lock().dealloc(ptr, layout);
+    }
+}
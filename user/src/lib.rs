@@ -1,59 +1,54 @@
 #![no_std]
 #![no_main]
-#![feature(linkage, panic_info_message, optimize_attribute)]
+#![feature(
+    linkage,
+    panic_info_message,
+    optimize_attribute,
+    thread_local,
+    allow_internal_unstable
+)]
 
 extern crate alloc;
 
 pub mod complement;
 pub mod console;
+pub mod env;
+pub mod heap;
+pub mod sync;
 pub mod syscall;
+pub mod tls;
 
 use alloc::vec::Vec;
-use allocator::buddy_allocator::BuddyAllocator;
+use core::sync::atomic::AtomicU32;
 use fosix::{
+    errno::EINTR,
     fs::{DirEntry, FileStat, OpenFlags, SeekFlag},
-    signal::{SignalAction, SignalFlags},
-    syscall::WaitFlags,
+    mm::{MapFlags, MapProt, MsyncFlags},
+    signal::{SignalAction, SignalFlags, SignalStack},
+    syscall::{CloneFlags, RUsage, WaitFlags, FUTEX_WAIT, FUTEX_WAKE},
+    time::{ITimerVal, TimeSpec, CLOCK_MONOTONIC, ITIMER_REAL},
 };
+use heap::UserHeap;
 use syscall::{
     debug::sys_procdump,
-    dev::{sys_shutdown, sys_time},
+    dev::{sys_clock_gettime, sys_shutdown, sys_tcgetpgrp, sys_tcsetpgrp},
     file::*,
+    mm::*,
     proc::*,
     task::*,
 };
 
-const USER_HEAP_SIZE: usize = 0x4000;
-const USER_HEAP_GRANULARITY: usize = 8;
 const STDIN: usize = 0;
 const STDOUT: usize = 1;
 
-static mut USER_HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
-
 #[global_allocator]
-static HEAP: BuddyAllocator = BuddyAllocator::empty(USER_HEAP_GRANULARITY);
+static HEAP: UserHeap = UserHeap::empty();
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-extern "C" fn _start(argc: usize, argv: usize) {
-    unsafe {
-        let start = USER_HEAP_SPACE.as_ptr() as usize;
-        let end = start + USER_HEAP_SPACE.len();
-        HEAP.add_segment(start, end);
-    }
-
-    let mut v = Vec::new();
-    for i in 0..argc {
-        let start =
-            unsafe { ((argv + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
-        let len = (0usize..)
-            .find(|i| unsafe { ((start + *i) as *const u8).read_volatile() == 0 })
-            .unwrap();
-        v.push(
-            core::str::from_utf8(unsafe { core::slice::from_raw_parts(start as *const u8, len) })
-                .unwrap(),
-        );
-    }
+extern "C" fn _start(argc: usize, argv: usize, envp: usize) {
+    let v = env::read_str_array(argv);
+    env::init(envp);
     exit(main(argc, v.as_slice()));
 }
 
@@ -63,8 +58,9 @@ fn main(argc: usize, argv: &[&str]) -> i32 {
     panic!("[user] main() is not implemented.")
 }
 
+/// Exit the process with all its threads.
 pub fn exit(exit_code: i32) -> ! {
-    sys_exit(exit_code);
+    sys_exit_group(exit_code);
 }
 
 pub extern "C" fn yield_now() {
@@ -72,28 +68,72 @@ pub extern "C" fn yield_now() {
 }
 
 pub fn fork() -> isize {
-    sys_fork()
+    sys_clone(CloneFlags::empty(), 0, 0)
 }
 
+/// Execute the program at `path` with the environment of the caller.
 pub fn exec(path: &str, args: &Vec<*const u8>) -> isize {
-    sys_exec(path, args)
+    let environ = env::ENVIRON.lock();
+    let mut envs: Vec<*const u8> = environ.iter().map(|env| env.as_ptr()).collect();
+    envs.push(core::ptr::null());
+    sys_exec(path, args, &envs)
+}
+
+/// Execute the program at `path` with the environment `envs`, both of which are null-terminated.
+pub fn execve(path: &str, args: &Vec<*const u8>, envs: &Vec<*const u8>) -> isize {
+    sys_exec(path, args, envs)
+}
+
+/// Set the personality to `persona` unless it's `PER_QUERY`, returning the previous one.
+///
+/// `ADDR_NO_RANDOMIZE` keeps the programs executed later from randomizing their address spaces, e.g. for debugging.
+pub fn personality(persona: usize) -> isize {
+    sys_personality(persona)
 }
 
 pub fn getpid() -> isize {
     sys_getpid()
 }
 
-pub fn waitpid(pid: isize, exit_code: &mut i32, flags: WaitFlags) -> isize {
-    if flags.contains(WaitFlags::NOHANG) {
-        sys_waitpid(pid, exit_code)
-    } else {
-        loop {
-            match sys_waitpid(pid, exit_code) {
-                -2 => yield_now(),
-                pid => return pid,
-            }
-        }
-    }
+/// Wait for a child to change state, where the encoded `WaitStatus` is written to `status`.
+pub fn waitpid(pid: isize, status: &mut i32, flags: WaitFlags) -> isize {
+    sys_waitpid(pid, status, flags, None)
+}
+
+/// Wait for a child to change state just like `waitpid()`, where the resource usage of the child is written to `rusage`.
+pub fn wait4(pid: isize, status: &mut i32, flags: WaitFlags, rusage: &mut RUsage) -> isize {
+    sys_waitpid(pid, status, flags, Some(rusage))
+}
+
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+
+pub fn sbrk(increment: isize) -> isize {
+    sys_sbrk(increment)
+}
+
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: MapProt,
+    flags: MapFlags,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+pub fn mprotect(addr: usize, len: usize, prot: MapProt) -> isize {
+    sys_mprotect(addr, len, prot)
+}
+
+pub fn msync(addr: usize, len: usize, flags: MsyncFlags) -> isize {
+    sys_msync(addr, len, flags)
 }
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
@@ -136,20 +176,87 @@ pub fn pipe(fds: &mut [usize; 2]) -> isize {
     sys_pipe(fds)
 }
 
+pub fn ftruncate(fd: usize, len: usize) -> isize {
+    sys_ftruncate(fd, len)
+}
+
+pub fn shm_open(name: &str, flags: OpenFlags) -> isize {
+    sys_shm_open(name, flags)
+}
+
+pub fn shm_unlink(name: &str) -> isize {
+    sys_shm_unlink(name)
+}
+
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
 
-pub fn kill(pid: usize, sig: usize) -> isize {
+/// Send `sig` to the process `pid`, or to the group `-pid` if it's negative.
+pub fn kill(pid: isize, sig: usize) -> isize {
     sys_kill(pid, sig)
 }
 
+/// Send `sig` to the thread `tid` of the process `pid`.
+pub fn tgkill(pid: usize, tid: usize, sig: usize) -> isize {
+    sys_tgkill(pid, tid, sig)
+}
+
+/// Get the signals that are pending on the calling thread or on its process.
+pub fn sigpending() -> SignalFlags {
+    SignalFlags::from_bits_truncate(sys_sigpending() as u32)
+}
+
+/// Wait for a signal with the mask replaced by `mask`, which always returns `-EINTR` after the handler runs.
+pub fn sigsuspend(mask: SignalFlags) -> isize {
+    sys_sigsuspend(mask)
+}
+
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
+
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+
+/// Get the foreground group of the console.
+pub fn tcgetpgrp() -> isize {
+    sys_tcgetpgrp()
+}
+
+/// Make the group `pgid` the foreground of the console, which receives Ctrl-C and Ctrl-Z.
+pub fn tcsetpgrp(pgid: usize) -> isize {
+    sys_tcsetpgrp(pgid)
+}
+
 pub fn sigreturn() -> isize {
     sys_sigreturn()
 }
 
+/// The address that handlers return to, which restores the context interrupted by the signal.
+extern "C" fn sig_restorer() {
+    sys_sigreturn();
+}
+
+/// Set the action of the signal, where the handler could simply return instead of calling `sigreturn()`.
 pub fn sigaction(sig_id: usize, new_action: &SignalAction, old_action: &mut SignalAction) -> isize {
-    sys_sigaction(sig_id, new_action, old_action)
+    let mut new_action = *new_action;
+    new_action.set_restorer(sig_restorer as usize);
+    sys_sigaction(sig_id, &new_action, old_action)
+}
+
+/// Set the alternate stack for the handlers with `SignalActionFlags::ONSTACK`, where either could be `None` to skip it.
+pub fn sigaltstack(new: Option<&SignalStack>, old: Option<&mut SignalStack>) -> isize {
+    sys_sigaltstack(new, old)
 }
 
 pub fn sigprocmask(mask: SignalFlags) -> Option<SignalFlags> {
@@ -161,8 +268,59 @@ pub fn sigprocmask(mask: SignalFlags) -> Option<SignalFlags> {
     }
 }
 
+pub fn getitimer(which: usize, curr: &mut ITimerVal) -> isize {
+    sys_getitimer(which, curr)
+}
+
+/// Set the interval timer `which`, where the old one is written to `old` if it's given.
+pub fn setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(which, new, old)
+}
+
+/// Deliver `SIGALRM` after `secs` seconds, or cancel the pending alarm if `secs` is 0.
+///
+/// Return the seconds remaining until the previous alarm, rounded up just like Linux.
+pub fn alarm(secs: usize) -> usize {
+    let new = ITimerVal::new(TimeSpec::zero(), TimeSpec::new(secs, 0));
+    let mut old = ITimerVal::zero();
+    sys_setitimer(ITIMER_REAL, &new, Some(&mut old));
+    old.value.sec + (old.value.nsec != 0) as usize
+}
+
+/// Create a thread with its own thread-local storage, which starts from `entry` on a stack allocated by the kernel.
 pub fn thread_create(entry: usize, arg: usize) -> isize {
-    sys_thread_create(entry, arg)
+    thread_create_on(entry, arg, 0)
+}
+
+/// Create a thread that starts from `entry` on `stack`, the top of a stack owned by the caller,
+/// or on a stack allocated by the kernel if it's null.
+pub fn thread_create_on(entry: usize, arg: usize, stack: usize) -> isize {
+    let block = tls::alloc_block();
+    let tid = sys_thread_create(entry, arg, stack, block);
+    if tid < 0 {
+        tls::dealloc_block(block);
+    }
+    tid
+}
+
+/// Exit the calling thread, which ends the process if it's the last running thread.
+pub fn thread_exit(exit_code: i32) -> ! {
+    tls::dealloc_curr_block();
+    sys_exit(exit_code)
+}
+
+/// Wait for the thread `tid` to exit, whose exit code is written to `exit_code` if it's given.
+pub fn thread_join(tid: usize, exit_code: Option<&mut i32>) -> isize {
+    let exit_code_ptr = exit_code.map_or(0, |exit_code| exit_code as *mut i32 as usize);
+    match sys_waittid(tid as isize, exit_code_ptr, WaitFlags::empty()) {
+        ret if ret < 0 => ret,
+        _ => 0,
+    }
+}
+
+/// Detach the thread `tid`, which is reaped once it exits and could not be joined.
+pub fn thread_detach(tid: usize) -> isize {
+    sys_thread_detach(tid)
 }
 
 pub fn gettid() -> isize {
@@ -170,16 +328,16 @@ pub fn gettid() -> isize {
 }
 
 pub fn waittid(tid: isize, exit_code_ptr: usize) -> isize {
-    loop {
-        match sys_waittid(tid, exit_code_ptr) {
-            -2 => yield_now(),
-            tid => return tid,
-        }
-    }
+    sys_waittid(tid, exit_code_ptr, WaitFlags::empty())
 }
 
 pub fn sleep(ms: usize) -> isize {
-    sys_sleep(ms)
+    sys_nanosleep(&TimeSpec::from_ms(ms), None)
+}
+
+/// Sleep for `req`, where the remaining time is written to `rem` if the sleep is interrupted.
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(req, rem)
 }
 
 pub fn mutex_create(blocked: bool) -> isize {
@@ -222,6 +380,16 @@ pub fn condvar_notify_all(id: usize) -> isize {
     sys_condvar_notify_all(id)
 }
 
+/// Sleep if `addr` still holds `val`, for at most `timeout` if provided.
+pub fn futex_wait(addr: &AtomicU32, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    sys_futex(addr, FUTEX_WAIT, val, timeout)
+}
+
+/// Wake up at most `count` threads sleeping on `addr`.
+pub fn futex_wake(addr: &AtomicU32, count: u32) -> isize {
+    sys_futex(addr, FUTEX_WAKE, count, None)
+}
+
 pub fn shutdown(exit_code: usize) -> ! {
     sys_shutdown(exit_code)
 }
@@ -230,17 +398,25 @@ pub fn procdump() -> isize {
     sys_procdump()
 }
 
+/// Get the milliseconds since boot.
 pub fn time() -> isize {
-    sys_time()
+    let mut ts = TimeSpec::zero();
+    sys_clock_gettime(CLOCK_MONOTONIC, &mut ts);
+    ts.as_ms() as isize
+}
+
+pub fn clock_gettime(clock_id: usize, ts: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, ts)
 }
 
 pub extern "C" fn putchar(c: u8) {
     sys_write(STDOUT, &[c]);
 }
 
+/// Read a byte from stdin, which is read again if the read is interrupted by a signal.
 pub extern "C" fn getchar() -> u8 {
     let mut buf = [0u8; 1];
-    sys_read(STDIN, &mut buf);
+    while sys_read(STDIN, &mut buf) == -EINTR {}
     buf[0]
 }
 
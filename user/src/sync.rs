@@ -0,0 +1,260 @@
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{futex_wait, futex_wake};
+
+/// The mutex that only enters the kernel when it's contended.
+///
+/// The state is 0 if unlocked, 1 if locked without waiters, and 2 if there might be waiters.
+pub struct Mutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<T> {
+        if self
+            .state
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+        self.state
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    /// Mark the mutex as contended and sleep until it's unlocked.
+    ///
+    /// The mutex stays contended after it's acquired here, since others might still be waiting.
+    fn lock_contended(&self) {
+        while self.state.swap(2, Ordering::Acquire) != 0 {
+            futex_wait(&self.state, 2, None);
+        }
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(0, Ordering::Release) == 2 {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+    pub fn mutex(&self) -> &'a Mutex<T> {
+        self.mutex
+    }
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+/// The condition variable that works with `Mutex`.
+///
+/// The sequence number changes on every notification, so a notification between unlocking and sleeping is not lost.
+pub struct Condvar {
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Unlock the mutex and sleep until notified, where spurious wakeups are possible.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let seq = self.seq.load(Ordering::Relaxed);
+        let mutex = guard.mutex();
+        drop(guard);
+        futex_wait(&self.seq, seq, None);
+
+        // the waiters woken up together would contend for the mutex
+        mutex.lock_contended();
+        MutexGuard { mutex }
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, u32::MAX);
+    }
+}
+
+/// The readers-writer lock that only enters the kernel when it's contended.
+///
+/// The state is the number of readers, or `WRITE_LOCKED` if a writer holds it,
+/// with `WAITERS` set if there might be waiters, so that unlocking only wakes them when it's set.
+/// Writers might starve if readers keep coming.
+pub struct RwLock<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+const WAITERS: u32 = 1 << 31;
+const WRITE_LOCKED: u32 = WAITERS - 1;
+
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+unsafe impl<T: Send> Send for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & !WAITERS < WRITE_LOCKED - 1 {
+                match self.state.compare_exchange_weak(
+                    state,
+                    state + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return RwLockReadGuard { lock: self },
+                    Err(curr) => state = curr,
+                }
+            } else {
+                state = self.wait(state);
+            }
+        }
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & !WAITERS == 0 {
+                // `WAITERS` is kept, so that the others are woken when the writer unlocks
+                match self.state.compare_exchange_weak(
+                    state,
+                    state | WRITE_LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return RwLockWriteGuard { lock: self },
+                    Err(curr) => state = curr,
+                }
+            } else {
+                state = self.wait(state);
+            }
+        }
+    }
+
+    /// Mark the lock as waited for and sleep until its state is no longer `state`, returning the new state.
+    fn wait(&self, state: u32) -> u32 {
+        if state & WAITERS == 0 {
+            if let Err(curr) = self.state.compare_exchange(
+                state,
+                state | WAITERS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                return curr;
+            }
+        }
+        futex_wait(&self.state, state | WAITERS, None);
+        self.state.load(Ordering::Relaxed)
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        // the last reader lets the writers in, unless a new reader or writer has come and taken over `WAITERS`
+        if self.lock.state.fetch_sub(1, Ordering::Release) == WAITERS | 1
+            && self
+                .lock
+                .state
+                .compare_exchange(WAITERS, 0, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            futex_wake(&self.lock.state, u32::MAX);
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.lock.state.swap(0, Ordering::Release) & WAITERS != 0 {
+            futex_wake(&self.lock.state, u32::MAX);
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use fosix::syscall::{SYSCALL_SHUTDOWN, SYSCALL_TIME};
+use fosix::{
+    syscall::{
+        SYSCALL_CLOCK_GETTIME, SYSCALL_SHUTDOWN, SYSCALL_TCGETPGRP, SYSCALL_TCSETPGRP, SYSCALL_TIME,
+    },
+    time::TimeSpec,
+};
 
 use super::syscall;
 
@@ -10,3 +15,18 @@ pub fn sys_shutdown(exit_code: usize) -> ! {
 pub fn sys_time() -> isize {
     syscall(SYSCALL_TIME, [0, 0, 0])
 }
+
+pub fn sys_clock_gettime(clock_id: usize, ts: &mut TimeSpec) -> isize {
+    syscall(
+        SYSCALL_CLOCK_GETTIME,
+        [clock_id, ts as *mut TimeSpec as usize, 0],
+    )
+}
+
+pub fn sys_tcgetpgrp() -> isize {
+    syscall(SYSCALL_TCGETPGRP, [0; 3])
+}
+
+pub fn sys_tcsetpgrp(pgid: usize) -> isize {
+    syscall(SYSCALL_TCSETPGRP, [pgid, 0, 0])
+}
@@ -51,3 +51,18 @@ pub fn sys_pipe(pipe: &mut [usize; 2]) -> isize {
 pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
+
+pub fn sys_ftruncate(fd: usize, len: usize) -> isize {
+    syscall(SYSCALL_FTRUNCATE, [fd, len, 0])
+}
+
+pub fn sys_shm_open(name: &str, flags: OpenFlags) -> isize {
+    syscall(
+        SYSCALL_SHM_OPEN,
+        [name.as_ptr() as usize, flags.bits() as usize, 0],
+    )
+}
+
+pub fn sys_shm_unlink(name: &str) -> isize {
+    syscall(SYSCALL_SHM_UNLINK, [name.as_ptr() as usize, 0, 0])
+}
@@ -0,0 +1,47 @@
+use fosix::{
+    mm::{MapFlags, MapProt, MsyncFlags},
+    syscall::*,
+};
+
+use crate::syscall::{syscall, syscall6};
+
+pub fn sys_brk(addr: usize) -> isize {
+    syscall(SYSCALL_BRK, [addr, 0, 0])
+}
+
+pub fn sys_sbrk(increment: isize) -> isize {
+    syscall(SYSCALL_SBRK, [increment as usize, 0, 0])
+}
+
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: MapProt,
+    flags: MapFlags,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(
+        SYSCALL_MMAP,
+        [
+            addr,
+            len,
+            prot.bits() as usize,
+            flags.bits() as usize,
+            fd,
+            offset,
+        ],
+    )
+}
+
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+pub fn sys_mprotect(addr: usize, len: usize, prot: MapProt) -> isize {
+    syscall(SYSCALL_MPROTECT, [addr, len, prot.bits() as usize])
+}
+
+pub fn sys_msync(addr: usize, len: usize, flags: MsyncFlags) -> isize {
+    syscall(SYSCALL_MSYNC, [addr, len, flags.bits() as usize])
+}
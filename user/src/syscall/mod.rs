@@ -1,6 +1,7 @@
 pub mod debug;
 pub mod dev;
 pub mod file;
+pub mod mm;
 pub mod proc;
 pub mod task;
 
@@ -19,3 +20,21 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     }
     return ret;
 }
+
+/// The syscall that takes up to six arguments, which are passed by `a0` to `a5`.
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => ret,
+            in("a1") args[1],
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a5") args[5],
+            in("a7") id
+        );
+    }
+    return ret;
+}
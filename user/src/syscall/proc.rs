@@ -1,44 +1,109 @@
 use alloc::vec::Vec;
+use core::sync::atomic::AtomicU32;
 use fosix::{
-    signal::{SignalAction, SignalFlags},
+    signal::{SignalAction, SignalFlags, SignalStack},
     syscall::*,
+    time::{ITimerVal, TimeSpec},
 };
 
-use crate::syscall::syscall;
+use crate::syscall::{syscall, syscall6};
 
 pub fn sys_exit(exit_code: i32) -> ! {
     syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
     panic!("[user] Return from syscall_exit()");
 }
 
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT_GROUP, [exit_code as usize, 0, 0]);
+    panic!("[user] Return from syscall_exit_group()");
+}
+
 pub fn sys_yield() {
     syscall(SYSCALL_YIELD, [0; 3]);
 }
 
-pub fn sys_fork() -> isize {
-    syscall(SYSCALL_FORK, [0; 3])
+/// Clone the caller, which returns to the same place in the child.
+///
+/// Hence, a thread must be created by `sys_thread_create()` instead, since it can't return on another stack.
+pub fn sys_clone(flags: CloneFlags, stack: usize, tls: usize) -> isize {
+    syscall(SYSCALL_CLONE, [flags.bits() as usize, stack, tls])
 }
 
-pub fn sys_exec(path: &str, args: &Vec<*const u8>) -> isize {
+pub fn sys_exec(path: &str, args: &Vec<*const u8>, envs: &Vec<*const u8>) -> isize {
     syscall(
         SYSCALL_EXEC,
-        [path.as_ptr() as usize, args.as_ptr() as usize, 0],
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            envs.as_ptr() as usize,
+        ],
     )
 }
 
+pub fn sys_personality(persona: usize) -> isize {
+    syscall(SYSCALL_PERSONALITY, [persona, 0, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(SYSCALL_GETPID, [0; 3])
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: &mut i32) -> isize {
-    syscall(
+pub fn sys_waitpid(
+    pid: isize,
+    status: &mut i32,
+    flags: WaitFlags,
+    rusage: Option<&mut RUsage>,
+) -> isize {
+    let rusage = rusage.map_or(0, |rusage| rusage as *mut RUsage as usize);
+    syscall6(
         SYSCALL_WAITPID,
-        [pid as usize, exit_code as *mut i32 as usize, 0],
+        [
+            pid as usize,
+            status as *mut i32 as usize,
+            flags.bits() as usize,
+            rusage,
+            0,
+            0,
+        ],
     )
 }
 
-pub fn sys_kill(pid: usize, sig: usize) -> isize {
-    syscall(SYSCALL_KILL, [pid, sig, 0])
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, sig, 0])
+}
+
+pub fn sys_tgkill(pid: usize, tid: usize, sig: usize) -> isize {
+    syscall(SYSCALL_TGKILL, [pid, tid, sig])
+}
+
+pub fn sys_sigaltstack(new: Option<&SignalStack>, old: Option<&mut SignalStack>) -> isize {
+    let new = new.map_or(0, |new| new as *const SignalStack as usize);
+    let old = old.map_or(0, |old| old as *mut SignalStack as usize);
+    syscall(SYSCALL_SIGALTSTACK, [new, old, 0])
+}
+
+pub fn sys_sigpending() -> isize {
+    syscall(SYSCALL_SIGPENDING, [0; 3])
+}
+
+pub fn sys_sigsuspend(mask: SignalFlags) -> isize {
+    syscall(SYSCALL_SIGSUSPEND, [mask.bits() as usize, 0, 0])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
+pub fn sys_getsid(pid: usize) -> isize {
+    syscall(SYSCALL_GETSID, [pid, 0, 0])
+}
+
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0; 3])
 }
 
 pub fn sys_sigreturn() -> isize {
@@ -64,6 +129,21 @@ pub fn sys_sigprocmask(mask: SignalFlags) -> isize {
     syscall(SYSCALL_SIGPROCMASK, [mask.bits() as usize, 0, 0])
 }
 
+pub fn sys_getitimer(which: usize, curr: &mut ITimerVal) -> isize {
+    syscall(
+        SYSCALL_GETITIMER,
+        [which, curr as *mut ITimerVal as usize, 0],
+    )
+}
+
+pub fn sys_setitimer(which: usize, new: &ITimerVal, old: Option<&mut ITimerVal>) -> isize {
+    let old = old.map_or(0, |old| old as *mut ITimerVal as usize);
+    syscall(
+        SYSCALL_SETITIMER,
+        [which, new as *const ITimerVal as usize, old],
+    )
+}
+
 pub fn sys_mutex_create(blocked: bool) -> isize {
     syscall(SYSCALL_MUTEX_CREATE, [blocked as usize, 0, 0])
 }
@@ -103,3 +183,18 @@ pub fn sys_condvar_notify_one(id: usize) -> isize {
 pub fn sys_condvar_notify_all(id: usize) -> isize {
     syscall(SYSCALL_CONDVAR_NOTIFY_ALL, [id, 0, 0])
 }
+
+pub fn sys_futex(addr: &AtomicU32, op: usize, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    let timeout = timeout.map_or(0, |timeout| timeout as *const TimeSpec as usize);
+    syscall6(
+        SYSCALL_FUTEX,
+        [
+            addr as *const AtomicU32 as usize,
+            op,
+            val as usize,
+            timeout,
+            0,
+            0,
+        ],
+    )
+}
@@ -1,19 +1,66 @@
-use fosix::syscall::{SYSCALL_GETTID, SYSCALL_SLEEP, SYSCALL_THREAD_CREATE, SYSCALL_WAITTID};
+use core::arch::asm;
+
+use fosix::{
+    syscall::{
+        CloneFlags, WaitFlags, SYSCALL_CLONE, SYSCALL_GETTID, SYSCALL_NANOSLEEP,
+        SYSCALL_THREAD_DETACH, SYSCALL_WAITTID,
+    },
+    time::TimeSpec,
+};
 
 use super::syscall;
 
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
-    syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
+/// Create a thread by `clone()` that shares everything with the caller and starts from `entry` with `arg`,
+/// where `stack` and `tls` are ignored if they are null.
+///
+/// The new thread jumps to `entry` right after `ecall` on its own stack, so it never returns into this function.
+/// Hence, `entry` must not return either.
+pub fn sys_thread_create(entry: usize, arg: usize, stack: usize, tls: usize) -> isize {
+    let mut flags = CloneFlags::VM
+        | CloneFlags::FS
+        | CloneFlags::FILES
+        | CloneFlags::SIGHAND
+        | CloneFlags::THREAD
+        | CloneFlags::SYSVSEM;
+    if tls != 0 {
+        flags |= CloneFlags::SETTLS;
+    }
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            "bnez a0, 1f",
+            "mv a0, {arg}",
+            "jalr {entry}",
+            "unimp",
+            "1:",
+            entry = in(reg) entry,
+            arg = in(reg) arg,
+            inlateout("a0") flags.bits() as usize => ret,
+            in("a1") stack,
+            in("a2") tls,
+            in("a7") SYSCALL_CLONE
+        );
+    }
+    ret
+}
+
+pub fn sys_thread_detach(tid: usize) -> isize {
+    syscall(SYSCALL_THREAD_DETACH, [tid, 0, 0])
 }
 
 pub fn sys_gettid() -> isize {
     syscall(SYSCALL_GETTID, [0, 0, 0])
 }
 
-pub fn sys_waittid(tid: isize, exit_code_ptr: usize) -> isize {
-    syscall(SYSCALL_WAITTID, [tid as usize, exit_code_ptr, 0])
+pub fn sys_waittid(tid: isize, exit_code_ptr: usize, flags: WaitFlags) -> isize {
+    syscall(
+        SYSCALL_WAITTID,
+        [tid as usize, exit_code_ptr, flags.bits() as usize],
+    )
 }
 
-pub fn sys_sleep(ms: usize) -> isize {
-    syscall(SYSCALL_SLEEP, [ms, 0, 0])
+pub fn sys_nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    let rem = rem.map_or(0, |rem| rem as *mut TimeSpec as usize);
+    syscall(SYSCALL_NANOSLEEP, [req as *const TimeSpec as usize, rem, 0])
 }
@@ -0,0 +1,87 @@
+//! The thread-local storage, whose template is the `PT_TLS` segment laid out by the linker script.
+//!
+//! The main thread gets its block from the kernel,
+//! while every thread created by `thread_create()` gets a copy of the template on the heap,
+//! which is freed by the thread itself in `thread_exit()`.
+
+use alloc::{
+    alloc::{alloc_zeroed, dealloc},
+    collections::BTreeSet,
+};
+use core::{alloc::Layout, arch::asm, cmp::max, ptr::copy_nonoverlapping};
+
+use lazy_static::lazy_static;
+use spin::Spin;
+
+extern "C" {
+    fn __tdata_start();
+    fn __tdata_end();
+    fn __tbss_end();
+    fn __tls_align();
+}
+
+lazy_static! {
+    /// The blocks allocated on the heap, which excludes the one of the main thread.
+    static ref TLS_BLOCKS: Spin<BTreeSet<usize>> = Spin::new(BTreeSet::new());
+}
+
+/// Declare statics that every thread has its own copy of, which are initialized by constant expressions.
+///
+/// The statics are placed in the `PT_TLS` segment and accessed by the thread pointer,
+/// so they don't need to be `Sync`.
+#[macro_export]
+#[allow_internal_unstable(thread_local)]
+macro_rules! thread_local {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            #[thread_local]
+            $vis static $name: $ty = $init;
+        )*
+    };
+}
+
+/// The layout of a block, which is `None` if there's no thread-local storage.
+fn layout() -> Option<Layout> {
+    let size = __tbss_end as usize - __tdata_start as usize;
+    if size == 0 {
+        return None;
+    }
+    Layout::from_size_align(size, max(__tls_align as usize, 1)).ok()
+}
+
+/// Allocate a copy of the template, returning the thread pointer to it, which is null if there's no thread-local storage.
+pub fn alloc_block() -> usize {
+    match layout() {
+        Some(layout) => unsafe {
+            let block = alloc_zeroed(layout);
+            copy_nonoverlapping(
+                __tdata_start as *const u8,
+                block,
+                __tdata_end as usize - __tdata_start as usize,
+            );
+            TLS_BLOCKS.lock().insert(block as usize);
+            block as usize
+        },
+        None => 0,
+    }
+}
+
+/// Free the block that is no longer used by any thread, which is ignored if it's not allocated by `alloc_block()`.
+pub fn dealloc_block(block: usize) {
+    if !TLS_BLOCKS.lock().remove(&block) {
+        return;
+    }
+    if let Some(layout) = layout() {
+        unsafe { dealloc(block as *mut u8, layout) };
+    }
+}
+
+/// Free the block of the calling thread, after which its thread-local statics must not be accessed.
+pub fn dealloc_curr_block() {
+    let tp: usize;
+    unsafe {
+        asm!("mv {tp}, tp", tp = out(reg) tp);
+    }
+    dealloc_block(tp);
+}